@@ -395,30 +395,60 @@ impl DeliverToRead for Node {
     }
 }
 
+/// A count of references of one kind, strong or weak, held by a [`NodeRef`].
+///
+/// Unlike a `refcount_t`, it legitimately goes back up from zero, since a [`NodeRef`] may hold
+/// weak references only and then acquire strong ones again. It is protected by the lock of the
+/// process that owns the [`NodeRef`]. Decrementing it when it is zero is refused rather than
+/// wrapping around, so a userspace request dropping a reference it does not hold cannot underflow
+/// the counts of the node.
+#[derive(Default)]
+struct RefCount(usize);
+
+impl RefCount {
+    /// Increments the count, returning whether it was zero, i.e., whether the node must be
+    /// notified that a reference of this kind is now held.
+    fn inc(&mut self) -> bool {
+        self.0 += 1;
+        self.0 == 1
+    }
+
+    /// Decrements the count, returning whether it dropped to zero, i.e., whether the node must be
+    /// notified that no reference of this kind is held anymore.
+    ///
+    /// Returns `None`, leaving the count untouched, if it was already zero.
+    fn dec(&mut self) -> Option<bool> {
+        self.0 = self.0.checked_sub(1)?;
+        Some(self.0 == 0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
 pub struct NodeRef {
     pub(crate) node: Arc<Node>,
-    strong_count: usize,
-    weak_count: usize,
+    strong: RefCount,
+    weak: RefCount,
 }
 
 impl NodeRef {
     pub(crate) fn new(node: Arc<Node>, strong_count: usize, weak_count: usize) -> Self {
         Self {
             node,
-            strong_count,
-            weak_count,
+            strong: RefCount(strong_count),
+            weak: RefCount(weak_count),
         }
     }
 
     pub(crate) fn absorb(&mut self, mut other: Self) {
-        self.strong_count += other.strong_count;
-        self.weak_count += other.weak_count;
-        other.strong_count = 0;
-        other.weak_count = 0;
+        self.strong.0 += core::mem::take(&mut other.strong).0;
+        self.weak.0 += core::mem::take(&mut other.weak).0;
     }
 
     pub(crate) fn clone(&self, strong: bool) -> BinderResult<NodeRef> {
-        if strong && self.strong_count == 0 {
+        if strong && self.strong.is_zero() {
             return Err(BinderError::new_failed());
         }
 
@@ -430,46 +460,77 @@ impl NodeRef {
             .new_node_ref(self.node.clone(), strong, None))
     }
 
-    /// Updates (increments or decrements) the number of references held against the node. If the
-    /// count being updated transitions from 0 to 1 or from 1 to 0, the node is notified by having
-    /// its `update_refcount` function called.
+    /// Acquires a strong reference on the node.
     ///
-    /// Returns whether `self` should be removed (when both counts are zero).
-    pub(crate) fn update(&mut self, inc: bool, strong: bool) -> bool {
-        if strong && self.strong_count == 0 {
+    /// Returns `false`, and does nothing, if no strong reference is held at all: holders of weak
+    /// references only must go through the node owner to acquire strong ones.
+    pub(crate) fn get_strong(&mut self) -> bool {
+        if self.strong.is_zero() {
             return false;
         }
+        self.strong.inc();
+        true
+    }
 
-        let (count, other_count) = if strong {
-            (&mut self.strong_count, self.weak_count)
-        } else {
-            (&mut self.weak_count, self.strong_count)
-        };
+    /// Releases a strong reference on the node.
+    ///
+    /// Returns whether `self` should be removed (when both counts are zero). Releasing a strong
+    /// reference that is not held does nothing.
+    pub(crate) fn put_strong(&mut self) -> bool {
+        if self.strong.dec() == Some(true) {
+            self.node.update_refcount(false, true);
+            return self.weak.is_zero();
+        }
+        false
+    }
 
-        if inc {
-            if *count == 0 {
-                self.node.update_refcount(true, strong);
-            }
-            *count += 1;
-        } else {
-            *count -= 1;
-            if *count == 0 {
-                self.node.update_refcount(false, strong);
-                return other_count == 0;
-            }
+    /// Acquires a weak reference on the node, notifying it if it is the first one.
+    pub(crate) fn get_weak(&mut self) {
+        if self.weak.inc() {
+            self.node.update_refcount(true, false);
         }
+    }
 
+    /// Releases a weak reference on the node.
+    ///
+    /// Returns whether `self` should be removed (when both counts are zero). Releasing a weak
+    /// reference that is not held does nothing.
+    pub(crate) fn put_weak(&mut self) -> bool {
+        if self.weak.dec() == Some(true) {
+            self.node.update_refcount(false, false);
+            return self.strong.is_zero();
+        }
         false
     }
+
+    /// Updates (increments or decrements) the number of references held against the node, as
+    /// requested by userspace. If the count being updated transitions from 0 to 1 or from 1 to 0,
+    /// the node is notified by having its `update_refcount` function called.
+    ///
+    /// Returns whether `self` should be removed (when both counts are zero).
+    pub(crate) fn update(&mut self, inc: bool, strong: bool) -> bool {
+        match (inc, strong) {
+            (true, true) => {
+                self.get_strong();
+                false
+            }
+            (true, false) => {
+                self.get_weak();
+                false
+            }
+            (false, true) => self.put_strong(),
+            (false, false) => self.put_weak(),
+        }
+    }
 }
 
 impl Drop for NodeRef {
     fn drop(&mut self) {
-        if self.strong_count > 0 {
+        if !self.strong.is_zero() {
             self.node.update_refcount(false, true);
         }
 
-        if self.weak_count > 0 {
+        if !self.weak.is_zero() {
             self.node.update_refcount(false, false);
         }
     }
@@ -10,8 +10,8 @@ use kernel::{
     file_operations::{FileOpener, FileOperations},
     io_buffer::IoBufferWriter,
     miscdev,
-    of::ConstOfMatchTable,
-    platdev::PlatformDriver,
+    of::ConstOfMatchDataTable,
+    platdev::{PlatformDevice, PlatformDriver},
     prelude::*,
     {c_str, platdev},
 };
@@ -35,31 +35,74 @@ impl FileOpener<()> for RngDevice {
 impl FileOperations for RngDevice {
     kernel::declare_file_operations!(read);
 
-    fn read<T: IoBufferWriter>(_: &Self, _: &File, data: &mut T, offset: u64) -> Result<usize> {
-        // Succeed if the caller doesn't provide a buffer or if not at the start.
-        if data.is_empty() || offset != 0 {
+    fn read_with_pos<T: IoBufferWriter>(
+        _: &Self,
+        _: &File,
+        data: &mut T,
+        _offset: &mut u64,
+    ) -> Result<usize> {
+        // Succeed if the caller doesn't provide a buffer.
+        if data.is_empty() {
             return Ok(0);
         }
 
+        // The device is a stream of random numbers, so every read returns new ones, and the
+        // position is left unchanged.
         data.write(&0_u32)?;
         Ok(4)
     }
 }
 
+/// The variants of the RNG block, as told apart by the devicetree compatible.
+#[derive(Clone, Copy)]
+enum Variant {
+    Bcm2835,
+    Nsp,
+}
+
+impl Variant {
+    fn name(self) -> &'static str {
+        match self {
+            Variant::Bcm2835 => "bcm2835",
+            Variant::Nsp => "nsp",
+        }
+    }
+
+    /// Whether the RNG interrupt has to be masked before the block is enabled.
+    fn mask_interrupts(self) -> bool {
+        matches!(self, Variant::Nsp)
+    }
+}
+
 struct RngDriver;
 
 impl PlatformDriver for RngDriver {
     type DrvData = Pin<Box<miscdev::Registration<()>>>;
-
-    fn probe(device_id: i32) -> Result<Self::DrvData> {
-        pr_info!("probing discovered hwrng with id {}\n", device_id);
+    type MatchData = Variant;
+
+    fn probe(
+        pdev: &mut PlatformDevice,
+        match_data: Option<&'static Variant>,
+    ) -> Result<Self::DrvData> {
+        let variant = match match_data {
+            Some(variant) => *variant,
+            // Devices registered by name, without a devicetree node, are the original BCM2835.
+            None if pdev.of_node().is_none() => Variant::Bcm2835,
+            None => return Err(Error::EINVAL),
+        };
+        pr_info!(
+            "probing discovered {} hwrng with id {}, mask interrupts: {}\n",
+            variant.name(),
+            pdev.id(),
+            variant.mask_interrupts()
+        );
         let drv_data =
             miscdev::Registration::new_pinned::<RngDevice>(c_str!("rust_hwrng"), None, ())?;
         Ok(drv_data)
     }
 
-    fn remove(device_id: i32, _drv_data: Self::DrvData) -> Result {
-        pr_info!("removing hwrng with id {}\n", device_id);
+    fn remove(pdev: &mut PlatformDevice, _drv_data: Self::DrvData) -> Result {
+        pr_info!("removing hwrng with id {}\n", pdev.id());
         Ok(())
     }
 }
@@ -70,12 +113,16 @@ struct RngModule {
 
 impl KernelModule for RngModule {
     fn init() -> Result<Self> {
-        const OF_MATCH_TBL: ConstOfMatchTable<1> =
-            ConstOfMatchTable::new_const([c_str!("brcm,bcm2835-rng")]);
-
-        let pdev = platdev::Registration::new_pinned::<RngDriver>(
+        const OF_MATCH_TBL: ConstOfMatchDataTable<Variant, 4> = ConstOfMatchDataTable::new_const([
+            (c_str!("brcm,bcm2835-rng"), &Variant::Bcm2835),
+            (c_str!("brcm,bcm-nsp-rng"), &Variant::Nsp),
+            (c_str!("brcm,bcm5301x-rng"), &Variant::Nsp),
+            (c_str!("brcm,bcm6368-rng"), &Variant::Bcm2835),
+        ]);
+
+        let pdev = platdev::Registration::new_pinned_with_data::<RngDriver>(
             c_str!("bcm2835-rng-rust"),
-            Some(&OF_MATCH_TBL),
+            &OF_MATCH_TBL,
             &THIS_MODULE,
         )?;
 
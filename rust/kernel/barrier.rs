@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Memory barriers.
+//!
+//! These map to the kernel's per-architecture barrier macros and are meant for lockless code that
+//! shares data between, e.g., an interrupt handler and process context. Code that only accesses
+//! shared data through atomics should prefer the `Ordering` arguments of the atomic operations
+//! (`Acquire` on loads, `Release` on stores), which express the same constraints more precisely
+//! and are often cheaper. The barriers below are needed when ordering plain (non-atomic) accesses
+//! or [`Relaxed`] atomic accesses against each other.
+//!
+//! C header: [`include/asm-generic/barrier.h`](../../../../include/asm-generic/barrier.h)
+//!
+//! Reference: <https://www.kernel.org/doc/Documentation/memory-barriers.txt>
+//!
+//! [`Relaxed`]: core::sync::atomic::Ordering::Relaxed
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+extern "C" {
+    fn rust_helper_smp_mb();
+    fn rust_helper_smp_rmb();
+    fn rust_helper_smp_wmb();
+}
+
+/// A compiler barrier.
+///
+/// Prevents the compiler from reordering memory accesses across it, but emits no instruction, so
+/// it does not order accesses as observed by other CPUs. Suitable for ordering accesses against
+/// an interrupt handler running on the same CPU.
+///
+/// Equivalent to the kernel's `barrier()` macro.
+#[inline]
+pub fn barrier() {
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// A full memory barrier between CPUs.
+///
+/// Orders all loads and stores before the barrier against all loads and stores after it, as
+/// observed by other CPUs. Similar to an [`SeqCst`] fence.
+///
+/// Equivalent to the kernel's `smp_mb()` macro.
+///
+/// [`SeqCst`]: core::sync::atomic::Ordering::SeqCst
+#[inline]
+pub fn smp_mb() {
+    // SAFETY: FFI call without preconditions.
+    unsafe { rust_helper_smp_mb() };
+}
+
+/// A read memory barrier between CPUs.
+///
+/// Orders loads before the barrier against loads after it. Typically paired with [`smp_wmb`] on
+/// the producer side, e.g. to read a "data ready" flag before reading the data it guards.
+///
+/// Equivalent to the kernel's `smp_rmb()` macro.
+#[inline]
+pub fn smp_rmb() {
+    // SAFETY: FFI call without preconditions.
+    unsafe { rust_helper_smp_rmb() };
+}
+
+/// A write memory barrier between CPUs.
+///
+/// Orders stores before the barrier against stores after it. Typically paired with [`smp_rmb`]
+/// on the consumer side, e.g. to publish data before setting the "data ready" flag.
+///
+/// Equivalent to the kernel's `smp_wmb()` macro.
+#[inline]
+pub fn smp_wmb() {
+    // SAFETY: FFI call without preconditions.
+    unsafe { rust_helper_smp_wmb() };
+}
@@ -1,14 +1,25 @@
 /* SPDX-License-Identifier: GPL-2.0 */
 
+#include <linux/capability.h>
 #include <linux/cdev.h>
+#include <linux/device.h>
+#include <linux/dev_printk.h>
+#include <linux/dma-mapping.h>
 #include <linux/errname.h>
+#include <linux/eventfd.h>
 #include <linux/fs.h>
+#include <linux/hrtimer.h>
+#include <linux/i2c.h>
+#include <linux/input.h>
+#include <linux/leds.h>
 #include <linux/module.h>
 #include <linux/random.h>
+#include <linux/rcupdate.h>
 #include <linux/slab.h>
 #include <linux/sysctl.h>
 #include <linux/uaccess.h>
 #include <linux/uio.h>
+#include <linux/wait.h>
 #include <linux/version.h>
 #include <linux/miscdevice.h>
 #include <linux/poll.h>
@@ -17,7 +28,15 @@
 #include <uapi/linux/android/binder.h>
 #include <linux/platform_device.h>
 #include <linux/of_platform.h>
+#include <linux/nvmem-consumer.h>
+#include <linux/property.h>
 #include <linux/security.h>
+#include <linux/regmap.h>
+#include <linux/irq.h>
+#include <linux/irqdomain.h>
+#include <linux/pinctrl/consumer.h>
+#include <linux/pm_opp.h>
+#include <linux/thermal.h>
 
 // `bindgen` gets confused at certain things
 const gfp_t BINDINGS_GFP_KERNEL = GFP_KERNEL;
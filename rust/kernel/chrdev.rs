@@ -4,33 +4,47 @@
 //!
 //! Also called "char devices", `chrdev`, `cdev`.
 //!
+//! Devices that answer commands written to them keep the pending command in a
+//! [`CommandBuffer`](crate::command_buffer::CommandBuffer) in the state of each open file.
+//!
 //! C header: [`include/linux/cdev.h`](../../../../include/linux/cdev.h)
 //!
 //! Reference: <https://www.kernel.org/doc/html/latest/core-api/kernel-api.html#char-devices>
 
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 use core::convert::TryInto;
 use core::marker::PhantomPinned;
 use core::pin::Pin;
+use core::ptr;
 
 use crate::bindings;
 use crate::c_types;
-use crate::error::{Error, Result};
-use crate::file_operations;
+use crate::error::{to_result, Error, Result};
+use crate::file_operations::{FileOpenAdapter, FileOpener, FileOperationsVtable};
 use crate::str::CStr;
+use crate::types::Opaque;
 
 /// Character device.
 ///
 /// # Invariants
 ///
 /// - [`self.0`] is valid and non-null.
-/// - [`(*self.0).ops`] is valid, non-null and has static lifetime.
+/// - [`(*self.0).ops`] has static lifetime.
 /// - [`(*self.0).owner`] is valid and, if non-null, has module lifetime.
+/// - [`(*self.0).kobj.parent`] is the kobject of a [`SharedContext`], to which it holds a
+///   reference.
 struct Cdev(*mut bindings::cdev);
 
 impl Cdev {
-    fn alloc(
+    /// Allocates a character device with the file operations `fops`, whose parent is `context`.
+    ///
+    /// # Safety
+    ///
+    /// `context` must be the kobject of a live [`SharedContext`].
+    unsafe fn alloc(
         fops: &'static bindings::file_operations,
+        context: &ContextRef,
         module: &'static crate::ThisModule,
     ) -> Result<Self> {
         // SAFETY: FFI call.
@@ -38,18 +52,20 @@ impl Cdev {
         if cdev.is_null() {
             return Err(Error::ENOMEM);
         }
-        // SAFETY: `cdev` is valid and non-null since `cdev_alloc()`
-        // returned a valid pointer which was null-checked.
+        // SAFETY: `cdev` is valid and non-null since `cdev_alloc()` returned a valid pointer which
+        // was null-checked. `context.0` is an initialised kobject by the safety requirements of
+        // this function, which `cdev_set_parent` takes a reference to.
         unsafe {
             (*cdev).ops = fops;
             (*cdev).owner = module.0;
+            bindings::cdev_set_parent(cdev, context.0);
         }
         // INVARIANTS:
         // - [`self.0`] is valid and non-null.
-        // - [`(*self.0).ops`] is valid, non-null and has static lifetime,
-        //   because it was coerced from a reference with static lifetime.
+        // - [`(*self.0).ops`] has static lifetime, since `fops` does.
         // - [`(*self.0).owner`] is valid and, if non-null, has module lifetime,
         //   guaranteed by the [`ThisModule`] invariant.
+        // - [`(*self.0).kobj.parent`] was set to `context.0` above.
         Ok(Self(cdev))
     }
 
@@ -59,26 +75,201 @@ impl Cdev {
         // - [`(*self.0).ops`] will live at least as long as [`self.0`].
         // - [`(*self.0).owner`] will live at least as long as the
         //   module, which is an implicit requirement.
-        let rc = unsafe { bindings::cdev_add(self.0, dev, count) };
-        if rc != 0 {
-            return Err(Error::from_kernel_errno(rc));
-        }
-        Ok(())
+        to_result(unsafe { bindings::cdev_add(self.0, dev, count) })
     }
 }
 
 impl Drop for Cdev {
     fn drop(&mut self) {
-        // SAFETY: [`self.0`] is valid and non-null by the type invariants.
-        unsafe {
-            bindings::cdev_del(self.0);
-        }
+        // SAFETY: [`self.0`] is valid and non-null by the type invariants. The kernel frees the
+        // `struct cdev`, and releases its reference to its parent, once the files opened from it
+        // are closed.
+        unsafe { bindings::cdev_del(self.0) };
     }
 }
 
+/// The context of the devices of a registration, shared by their `struct cdev`s.
+///
+/// It is the parent kobject of each of the devices, so the kernel keeps it alive for as long as
+/// any of them is: a `struct cdev` is only freed, and releases its reference to its parent, once
+/// the device is unregistered and the files opened from it are closed. It thus does not depend on
+/// the lifetime of the registration, which may be dropped while a device is being opened.
+///
+/// # Invariants
+///
+/// `kobj` is initialised with the kobject type of `SharedContext<T>`, whose release function
+/// frees the context.
+struct SharedContext<T> {
+    kobj: Opaque<bindings::kobject>,
+    context: T,
+}
+
+impl<T: Send + Sync> SharedContext<T> {
+    const KTYPE: bindings::kobj_type = bindings::kobj_type {
+        release: Some(Self::release),
+        sysfs_ops: ptr::null(),
+        default_attrs: ptr::null_mut(),
+        default_groups: ptr::null_mut(),
+        child_ns_type: None,
+        namespace: None,
+        get_ownership: None,
+    };
+
+    /// Allocates a shared context holding `context`.
+    fn try_new(context: T) -> Result<ContextRef> {
+        let ptr = Box::into_raw(Box::try_new(Self {
+            kobj: Opaque::new(bindings::kobject::default()),
+            context,
+        })?);
+        // SAFETY: `ptr` was just allocated, and its kobject zeroed.
+        let kobj = unsafe { (*ptr).kobj.get() };
+        // CAST: The kobject type is never written to, it is only mutable in the C signature.
+        let ktype = &Self::KTYPE as *const _ as *mut bindings::kobj_type;
+        // SAFETY: `kobj` is valid and not initialised yet. `ktype` has static lifetime.
+        unsafe { bindings::kobject_init(kobj, ktype) };
+        // INVARIANTS: `kobj` was initialised with `KTYPE` above, and its reference is passed on.
+        Ok(ContextRef(kobj))
+    }
+
+    unsafe extern "C" fn release(kobj: *mut bindings::kobject) {
+        // TODO: `SAFETY` comment required here even if `unsafe` is not present,
+        // because `container_of!` hides it. Ideally we would not allow
+        // `unsafe` code as parameters to macros.
+        let ptr = crate::container_of!(kobj, Self, kobj) as *mut Self;
+        // SAFETY: By the type invariants, `kobj` belongs to a context allocated by `try_new`,
+        // whose last reference was just released.
+        drop(unsafe { Box::from_raw(ptr) });
+    }
+
+    /// Returns the context of the device being opened from `inode`.
+    ///
+    /// # Safety
+    ///
+    /// `inode` must be being opened by `chrdev_open` from a device whose parent is a
+    /// [`SharedContext<T>`].
+    unsafe fn from_inode(inode: *mut bindings::inode) -> *const T {
+        // SAFETY: `chrdev_open` set `i_cdev` to the device being opened, and holds a reference to
+        // it, which in turn holds a reference to its parent.
+        let kobj = unsafe { (*(*inode).i_cdev).kobj.parent };
+        // TODO: `SAFETY` comment required here even if `unsafe` is not present,
+        // because `container_of!` hides it. Ideally we would not allow
+        // `unsafe` code as parameters to macros.
+        let ptr = crate::container_of!(kobj, Self, kobj);
+        // SAFETY: `ptr` is valid by the safety requirements of this function.
+        unsafe { &(*ptr).context }
+    }
+}
+
+/// A reference to a [`SharedContext`] of any type, released when dropped.
+///
+/// # Invariants
+///
+/// The pointer `ContextRef::0` is the initialised kobject of a [`SharedContext`], to which the
+/// instance holds a reference.
+struct ContextRef(*mut bindings::kobject);
+
+impl Drop for ContextRef {
+    fn drop(&mut self) {
+        // SAFETY: By the type invariants, `self` holds a reference to `self.0`.
+        unsafe { bindings::kobject_put(self.0) };
+    }
+}
+
+const NO_CDEV: Option<Cdev> = None;
+
+/// Adds the device `dev`, whose files are opened by `F` through the adapter `A`, with the context
+/// `context`.
+///
+/// # Safety
+///
+/// `context` must refer to a [`SharedContext<A::Arg>`], and `A` must find the context of the files
+/// it opens with [`SharedContext::from_inode`].
+unsafe fn add_cdev<A: FileOpenAdapter, F: FileOpener<A::Arg>>(
+    context: &ContextRef,
+    dev: bindings::dev_t,
+    module: &'static crate::ThisModule,
+) -> Result<Cdev> {
+    // SAFETY: The adapter is compatible with the device by the safety requirements of this
+    // function: it finds the context in the parent of the device, which is `context`.
+    let fops = unsafe { FileOperationsVtable::<A, F>::build() };
+    // SAFETY: `context` refers to a live `SharedContext`, since it holds a reference to it.
+    let mut cdev = unsafe { Cdev::alloc(fops, context, module)? };
+    cdev.add(dev, 1)?;
+    Ok(cdev)
+}
+
+/// Reserves `count` device numbers starting at minor `minors_start`, with the major number
+/// `major`, or a free one allocated by the kernel if [`None`], and returns the first one.
+fn reserve_region(
+    name: &'static CStr,
+    major: Option<u16>,
+    minors_start: u16,
+    count: usize,
+) -> Result<bindings::dev_t> {
+    let mut dev: bindings::dev_t = 0;
+    let res = match major {
+        // SAFETY: Calling unsafe function. `name` has `'static`
+        // lifetime.
+        None => unsafe {
+            bindings::alloc_chrdev_region(
+                &mut dev,
+                minors_start.into(),
+                count.try_into()?,
+                name.as_char_ptr(),
+            )
+        },
+        Some(major) => {
+            dev = mkdev(major, minors_start);
+            // SAFETY: Calling unsafe function. `name` has `'static`
+            // lifetime.
+            unsafe { bindings::register_chrdev_region(dev, count.try_into()?, name.as_char_ptr()) }
+        }
+    };
+    to_result(res)?;
+    Ok(dev)
+}
+
+/// Equivalent to the kernel's `MKDEV` macro.
+fn mkdev(major: u16, minor: u16) -> bindings::dev_t {
+    (bindings::dev_t::from(major) << bindings::MINORBITS) | bindings::dev_t::from(minor)
+}
+
+/// Equivalent to the kernel's `MAJOR` macro.
+fn major(dev: bindings::dev_t) -> u16 {
+    // CAST: Major numbers have `32 - MINORBITS` = 12 bits.
+    (dev >> bindings::MINORBITS) as u16
+}
+
+/// Equivalent to the kernel's `MINOR` macro.
+fn minor(dev: bindings::dev_t) -> u32 {
+    dev & ((1 << bindings::MINORBITS) - 1)
+}
+
+/// A description of the device numbers registered by a [`Registration`].
+///
+/// Returned by [`Registration::info()`].
+#[derive(Clone, Copy)]
+pub struct RegistrationInfo {
+    /// The name of the registration, as shown in `/proc/devices`.
+    pub name: &'static CStr,
+
+    /// The major number of the registered devices.
+    pub major: u16,
+
+    /// The first minor number of the registered range.
+    pub minors_start: u16,
+
+    /// The number of minors in use, i.e., of devices registered so far.
+    pub used: usize,
+
+    /// The number of minors reserved, i.e., the maximum number of devices.
+    pub capacity: usize,
+}
+
 struct RegistrationInner<const N: usize> {
     dev: bindings::dev_t,
     used: usize,
+    shared: ContextRef,
     cdevs: [Option<Cdev>; N],
     _pin: PhantomPinned,
 }
@@ -86,14 +277,53 @@ struct RegistrationInner<const N: usize> {
 /// Character device registration.
 ///
 /// May contain up to a fixed number (`N`) of devices. Must be pinned.
-pub struct Registration<const N: usize> {
+///
+/// The registration holds a context of type `T`, which is passed to [`FileOpener::open`] when
+/// any of its devices is opened, e.g. the state of the driver. Since `T` must be [`Sync`], devices
+/// opened concurrently share it safely. The first call to [`Self::register()`] clones the context
+/// into an allocation that the kernel keeps alive until the last of the devices is released, so
+/// `T` is usually a reference-counted handle to the state, e.g. an [`Arc`](alloc::sync::Arc).
+/// The kernel does not refer to the registration itself, so open files do not keep it alive:
+/// dropping it unregisters the devices, and files opened from them keep working until they are
+/// closed.
+///
+/// Modules that register their devices at init and keep them for their whole lifetime create the
+/// registration with [`Self::new_pinned()`] and store it in their [`crate::KernelModule`]
+/// instance; the devices are unregistered when the module is unloaded.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::{c_str, chrdev};
+/// struct MyModule {
+///     _chrdev: Pin<Box<chrdev::Registration<(), 2>>>,
+/// }
+///
+/// impl KernelModule for MyModule {
+///     fn init() -> Result<Self> {
+///         let mut chrdev =
+///             chrdev::Registration::new_pinned(c_str!("mydev"), 0, (), &THIS_MODULE)?;
+///         // `MyFile` implements `FileOpener<()>`.
+///         chrdev.as_mut().register::<MyFile>()?;
+///         chrdev.as_mut().register::<MyFile>()?;
+///         Ok(MyModule { _chrdev: chrdev })
+///     }
+/// }
+/// ```
+pub struct Registration<T: Clone + Send + Sync, const N: usize> {
     name: &'static CStr,
+    major: Option<u16>,
     minors_start: u16,
     this_module: &'static crate::ThisModule,
     inner: Option<RegistrationInner<N>>,
+
+    /// Context initialised on construction and made available to all file instances on
+    /// [`FileOpener::open`].
+    pub context: T,
 }
 
-impl<const N: usize> Registration<{ N }> {
+impl<T: Clone + Send + Sync, const N: usize> Registration<T, { N }> {
     /// Creates a [`Registration`] object for a character device.
     ///
     /// This does *not* register the device: see [`Self::register()`].
@@ -106,13 +336,16 @@ impl<const N: usize> Registration<{ N }> {
     pub fn new(
         name: &'static CStr,
         minors_start: u16,
+        context: T,
         this_module: &'static crate::ThisModule,
     ) -> Self {
         Registration {
             name,
+            major: None,
             minors_start,
             this_module,
             inner: None,
+            context,
         }
     }
 
@@ -122,91 +355,414 @@ impl<const N: usize> Registration<{ N }> {
     pub fn new_pinned(
         name: &'static CStr,
         minors_start: u16,
+        context: T,
         this_module: &'static crate::ThisModule,
     ) -> Result<Pin<Box<Self>>> {
         Ok(Pin::from(Box::try_new(Self::new(
             name,
             minors_start,
+            context,
             this_module,
         ))?))
     }
 
-    /// Registers a character device.
+    /// Creates a [`Registration`] object for a character device with a fixed major number.
+    ///
+    /// Unlike [`Self::new()`], which lets the kernel pick a free major number, the device numbers
+    /// `major:minors_start` to `major:minors_start + N - 1` are used, so that they are stable
+    /// across boots. [`Self::register()`] fails with `EBUSY` if any of them is already taken.
+    ///
+    /// This does *not* register the device: see [`Self::register()`].
+    pub fn new_with_major(
+        name: &'static CStr,
+        major: u16,
+        minors_start: u16,
+        context: T,
+        this_module: &'static crate::ThisModule,
+    ) -> Self {
+        Registration {
+            major: Some(major),
+            ..Self::new(name, minors_start, context, this_module)
+        }
+    }
+
+    /// Creates a pinned [`Registration`] object for a character device with a fixed major number.
+    ///
+    /// See [`Self::new_with_major()`].
     ///
-    /// You may call this once per device type, up to `N` times.
-    pub fn register<T: file_operations::FileOpener<()>>(self: Pin<&mut Self>) -> Result {
+    /// This does *not* register the device: see [`Self::register()`].
+    pub fn new_pinned_with_major(
+        name: &'static CStr,
+        major: u16,
+        minors_start: u16,
+        context: T,
+        this_module: &'static crate::ThisModule,
+    ) -> Result<Pin<Box<Self>>> {
+        Ok(Pin::from(Box::try_new(Self::new_with_major(
+            name,
+            major,
+            minors_start,
+            context,
+            this_module,
+        ))?))
+    }
+
+    /// Registers a character device, whose files are opened by `F`.
+    ///
+    /// You may call this up to `N` times, once per device. Each device gets the operations of
+    /// the `F` it was registered with, so the devices of a registration may behave differently,
+    /// e.g. one may be read-only and another write-only; they only share the context.
+    pub fn register<F: FileOpener<T>>(self: Pin<&mut Self>) -> Result {
         // SAFETY: We must ensure that we never move out of `this`.
         let this = unsafe { self.get_unchecked_mut() };
         if this.inner.is_none() {
-            let mut dev: bindings::dev_t = 0;
-            // SAFETY: Calling unsafe function. `this.name` has `'static`
-            // lifetime.
-            let res = unsafe {
-                bindings::alloc_chrdev_region(
-                    &mut dev,
-                    this.minors_start.into(),
-                    N.try_into()?,
-                    this.name.as_char_ptr(),
-                )
-            };
-            if res != 0 {
-                return Err(Error::from_kernel_errno(res));
-            }
-            const NONE: Option<Cdev> = None;
+            let shared = SharedContext::try_new(this.context.clone())?;
+            let dev = reserve_region(this.name, this.major, this.minors_start, N)?;
             this.inner = Some(RegistrationInner {
                 dev,
                 used: 0,
-                cdevs: [NONE; N],
+                shared,
+                cdevs: [NO_CDEV; N],
                 _pin: PhantomPinned,
             });
         }
 
-        let mut inner = this.inner.as_mut().unwrap();
+        let inner = this.inner.as_mut().unwrap();
         if inner.used == N {
             return Err(Error::EINVAL);
         }
 
-        // SAFETY: The adapter doesn't retrieve any state yet, so it's compatible with any
-        // registration.
-        let fops = unsafe { file_operations::FileOperationsVtable::<Self, T>::build() };
-        let mut cdev = Cdev::alloc(fops, this.this_module)?;
-        cdev.add(inner.dev + inner.used as bindings::dev_t, 1)?;
-        inner.cdevs[inner.used].replace(cdev);
+        let dev = inner.dev + inner.used as bindings::dev_t;
+        // SAFETY: `inner.shared` is a `SharedContext<T>`, which `Self` finds the context in.
+        let cdev = unsafe { add_cdev::<Self, F>(&inner.shared, dev, this.this_module)? };
+        inner.cdevs[inner.used] = Some(cdev);
         inner.used += 1;
         Ok(())
     }
+
+    /// Returns a description of the registered device numbers.
+    ///
+    /// Returns [`None`] if no device was registered yet, since device numbers are only reserved by
+    /// the first call to [`Self::register()`].
+    pub fn info(&self) -> Option<RegistrationInfo> {
+        let inner = self.inner.as_ref()?;
+        Some(RegistrationInfo {
+            name: self.name,
+            major: major(inner.dev),
+            minors_start: self.minors_start,
+            used: inner.used,
+            capacity: N,
+        })
+    }
+
+    /// Returns the major number of the registered devices, e.g. to create their device nodes.
+    ///
+    /// Returns [`None`] if no device was registered yet, since the major number is only allocated
+    /// by the first call to [`Self::register()`].
+    pub fn major(&self) -> Option<u32> {
+        Some(major(self.inner.as_ref()?.dev).into())
+    }
+
+    /// Returns the range of minor numbers of the devices registered so far, as the first minor and
+    /// the minor past the last one.
+    ///
+    /// The end of the range saturates at [`u16::MAX`], though minor numbers have 20 bits and
+    /// devices may have been registered beyond it. Returns [`None`] if no device was registered
+    /// yet.
+    pub fn minor_range(&self) -> Option<(u16, u16)> {
+        let inner = self.inner.as_ref()?;
+        // CAST: The first minor is `minors_start`, a `u16`.
+        let start = minor(inner.dev) as u16;
+        let used = inner.used.try_into().unwrap_or(u16::MAX);
+        Some((start, start.saturating_add(used)))
+    }
 }
 
-impl<const N: usize> file_operations::FileOpenAdapter for Registration<{ N }> {
-    type Arg = ();
+impl<T: Clone + Send + Sync, const N: usize> FileOpenAdapter for Registration<T, { N }> {
+    type Arg = T;
 
-    unsafe fn convert(
-        _inode: *mut bindings::inode,
-        _file: *mut bindings::file,
-    ) -> *const Self::Arg {
-        // TODO: Update the SAFETY comment on the call to `FileOperationsVTable::build` above once
-        // this is updated to retrieve state.
-        &()
+    unsafe fn convert(inode: *mut bindings::inode, _file: *mut bindings::file) -> *const Self::Arg {
+        // SAFETY: The adapter is only installed in devices added by registrations of type `Self`,
+        // whose parent is their `SharedContext<T>`.
+        unsafe { SharedContext::from_inode(inode) }
     }
 }
 
 // SAFETY: `Registration` does not expose any of its state across threads
-// (it is fine for multiple threads to have a shared reference to it).
-unsafe impl<const N: usize> Sync for Registration<{ N }> {}
+// (it is fine for multiple threads to have a shared reference to it), except `context`, which is
+// itself `Sync`.
+unsafe impl<T: Clone + Send + Sync, const N: usize> Sync for Registration<T, { N }> {}
 
-impl<const N: usize> Drop for Registration<{ N }> {
+impl<T: Clone + Send + Sync, const N: usize> Drop for Registration<T, { N }> {
     fn drop(&mut self) {
         if let Some(inner) = self.inner.as_mut() {
             // Replicate kernel C behaviour: drop [`Cdev`]s before calling
             // [`bindings::unregister_chrdev_region`].
-            for i in 0..inner.used {
-                inner.cdevs[i].take();
+            for cdev in inner.cdevs.iter_mut() {
+                cdev.take();
             }
             // SAFETY: [`self.inner`] is Some, so [`inner.dev`] was previously
-            // created using [`bindings::alloc_chrdev_region`].
+            // registered using [`bindings::alloc_chrdev_region`] or
+            // [`bindings::register_chrdev_region`], both of which are undone
+            // by [`bindings::unregister_chrdev_region`].
             unsafe {
                 bindings::unregister_chrdev_region(inner.dev, N.try_into().unwrap());
             }
         }
     }
 }
+
+/// Character device registration for a number of devices only known at runtime.
+///
+/// Like [`Registration`], but the number of devices is not part of the type: it is declared with
+/// [`Self::reserve()`], which reserves the device numbers, before up to that many devices are
+/// registered with [`Self::register()`]. This suits drivers that learn how many devices they have
+/// when probing, e.g. from a devicetree property. Must be pinned.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::{c_str, chrdev};
+/// fn register_channels(count: usize) -> Result<Pin<Box<chrdev::DynRegistration>>> {
+///     let mut chrdev =
+///         chrdev::DynRegistration::new_pinned(c_str!("mydev"), 0, (), &THIS_MODULE)?;
+///     chrdev.as_mut().reserve(count)?;
+///     for _ in 0..count {
+///         // `MyFile` implements `FileOpener<()>`.
+///         chrdev.as_mut().register::<MyFile>()?;
+///     }
+///     Ok(chrdev)
+/// }
+/// ```
+pub struct DynRegistration<T: Clone + Send + Sync = ()> {
+    name: &'static CStr,
+    minors_start: u16,
+    this_module: &'static crate::ThisModule,
+    /// The first of the reserved device numbers, if any.
+    dev: Option<bindings::dev_t>,
+    /// The number of reserved device numbers.
+    count: usize,
+    /// The context shared by the devices, allocated along with their device numbers.
+    shared: Option<ContextRef>,
+    cdevs: Vec<Cdev>,
+    _pin: PhantomPinned,
+
+    /// Context initialised on construction and made available to all file instances on
+    /// [`FileOpener::open`].
+    pub context: T,
+}
+
+impl<T: Clone + Send + Sync> DynRegistration<T> {
+    /// Creates a [`DynRegistration`] object for character devices.
+    ///
+    /// This does *not* reserve any device numbers: see [`Self::reserve()`].
+    pub fn new(
+        name: &'static CStr,
+        minors_start: u16,
+        context: T,
+        this_module: &'static crate::ThisModule,
+    ) -> Self {
+        Self {
+            name,
+            minors_start,
+            this_module,
+            dev: None,
+            count: 0,
+            shared: None,
+            cdevs: Vec::new(),
+            _pin: PhantomPinned,
+            context,
+        }
+    }
+
+    /// Creates a pinned [`DynRegistration`] object for character devices.
+    ///
+    /// This does *not* reserve any device numbers: see [`Self::reserve()`].
+    pub fn new_pinned(
+        name: &'static CStr,
+        minors_start: u16,
+        context: T,
+        this_module: &'static crate::ThisModule,
+    ) -> Result<Pin<Box<Self>>> {
+        Ok(Pin::from(Box::try_new(Self::new(
+            name,
+            minors_start,
+            context,
+            this_module,
+        ))?))
+    }
+
+    /// Reserves the device numbers of `count` devices, with a major number allocated by the kernel.
+    ///
+    /// Must be called once, before [`Self::register()`]. Fails with `EINVAL` if it was already
+    /// called, or if `count` is zero, and with `ENOMEM` if the devices cannot be allocated. As with
+    /// [`Registration`], this clones the context into an allocation shared by the devices.
+    pub fn reserve(self: Pin<&mut Self>, count: usize) -> Result {
+        // SAFETY: We must ensure that we never move out of `this`.
+        let this = unsafe { self.get_unchecked_mut() };
+        if this.dev.is_some() || count == 0 {
+            return Err(Error::EINVAL);
+        }
+        this.cdevs.try_reserve_exact(count)?;
+        let shared = SharedContext::try_new(this.context.clone())?;
+        this.dev = Some(reserve_region(this.name, None, this.minors_start, count)?);
+        this.shared = Some(shared);
+        this.count = count;
+        Ok(())
+    }
+
+    /// Registers a character device, whose files are opened by `F`.
+    ///
+    /// You may call this up to the number of devices passed to [`Self::reserve()`]; it fails with
+    /// `EINVAL` beyond that, or if no devices were reserved. As with [`Registration`], each device
+    /// gets the operations of the `F` it was registered with.
+    pub fn register<F: FileOpener<T>>(self: Pin<&mut Self>) -> Result {
+        // SAFETY: We must ensure that we never move out of `this`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let (dev, shared) = match (this.dev, this.shared.as_ref()) {
+            (Some(dev), Some(shared)) => (dev, shared),
+            _ => return Err(Error::EINVAL),
+        };
+        let index = this.cdevs.len();
+        if index == this.count {
+            return Err(Error::EINVAL);
+        }
+
+        let dev = dev + index as bindings::dev_t;
+        // SAFETY: `shared` is a `SharedContext<T>`, which `Self` finds the context in.
+        let cdev = unsafe { add_cdev::<Self, F>(shared, dev, this.this_module)? };
+        // This does not allocate: the capacity of `cdevs` is `count`.
+        this.cdevs.push(cdev);
+        Ok(())
+    }
+
+    /// Returns the major number of the devices, or [`None`] if no devices were reserved.
+    pub fn major(&self) -> Option<u32> {
+        Some(major(self.dev?).into())
+    }
+}
+
+impl<T: Clone + Send + Sync> FileOpenAdapter for DynRegistration<T> {
+    type Arg = T;
+
+    unsafe fn convert(inode: *mut bindings::inode, _file: *mut bindings::file) -> *const Self::Arg {
+        // SAFETY: The adapter is only installed in devices added by registrations of type `Self`,
+        // whose parent is their `SharedContext<T>`.
+        unsafe { SharedContext::from_inode(inode) }
+    }
+}
+
+// SAFETY: `DynRegistration` does not expose any of its state across threads (it is fine for
+// multiple threads to have a shared reference to it), except `context`, which is itself `Sync`.
+unsafe impl<T: Clone + Send + Sync> Sync for DynRegistration<T> {}
+
+impl<T: Clone + Send + Sync> Drop for DynRegistration<T> {
+    fn drop(&mut self) {
+        if let Some(dev) = self.dev {
+            // Replicate kernel C behaviour: drop [`Cdev`]s before calling
+            // [`bindings::unregister_chrdev_region`].
+            self.cdevs.clear();
+            // SAFETY: [`self.dev`] is Some, so `count` device numbers starting at `dev` were
+            // previously registered using [`bindings::alloc_chrdev_region`], which is undone by
+            // [`bindings::unregister_chrdev_region`].
+            unsafe {
+                bindings::unregister_chrdev_region(dev, self.count.try_into().unwrap());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_operations::FileOperations;
+
+    /// A read-only device, which records the context it was opened with.
+    struct Reader(u32);
+
+    impl FileOperations for Reader {
+        crate::declare_file_operations!(read);
+    }
+
+    impl FileOpener<u32> for Reader {
+        fn open(context: &u32) -> Result<Box<Self>> {
+            Ok(Box::try_new(Reader(*context))?)
+        }
+    }
+
+    /// A write-only device, which records the context it was opened with.
+    struct Writer(u32);
+
+    impl FileOperations for Writer {
+        crate::declare_file_operations!(write);
+    }
+
+    impl FileOpener<u32> for Writer {
+        fn open(context: &u32) -> Result<Box<Self>> {
+            Ok(Box::try_new(Writer(*context))?)
+        }
+    }
+
+    type TestRegistration = Registration<u32, 2>;
+
+    /// A `struct cdev` whose files are opened by `F`, with `parent` as its parent.
+    fn new_cdev<F: FileOpener<u32>>(parent: &SharedContext<u32>) -> bindings::cdev {
+        let mut cdev = bindings::cdev::default();
+        // SAFETY: The device is only opened through `open`, whose inode is that of a
+        // `TestRegistration`, and the adapter finds its context in `parent`.
+        cdev.ops = unsafe { FileOperationsVtable::<TestRegistration, F>::build() };
+        cdev.kobj.parent = parent.kobj.get();
+        cdev
+    }
+
+    /// Opens a file of `cdev`, like `chrdev_open` does.
+    fn open(cdev: &mut bindings::cdev) -> bindings::file {
+        // SAFETY: `struct inode` and `struct file` are plain C structs, for which all zeroes is a
+        // valid value.
+        let (mut inode, mut file): (bindings::inode, bindings::file) =
+            unsafe { (core::mem::zeroed(), core::mem::zeroed()) };
+        file.f_op = cdev.ops;
+        inode.i_cdev = cdev;
+        // SAFETY: The operations of `cdev` were set by `new_cdev`, and `inode` refers to it.
+        let ret = unsafe { (*file.f_op).open.unwrap()(&mut inode, &mut file) };
+        assert_eq!(ret, 0);
+        file
+    }
+
+    /// Closes `file`, like the kernel does when its last reference is dropped.
+    fn release(mut file: bindings::file) {
+        // SAFETY: `file` was opened by `open`, and is released once.
+        unsafe { (*file.f_op).release.unwrap()(ptr::null_mut(), &mut file) };
+    }
+
+    #[test]
+    fn test_each_device_has_its_own_operations() {
+        // The kobject is not initialised: nothing takes or releases references to it here.
+        let shared = SharedContext {
+            kobj: Opaque::new(bindings::kobject::default()),
+            context: 7,
+        };
+        let mut reader = new_cdev::<Reader>(&shared);
+        let mut writer = new_cdev::<Writer>(&shared);
+        assert!(reader.ops != writer.ops);
+
+        let file = open(&mut reader);
+        // SAFETY: `f_op` is a valid static vtable, and `private_data` was set by `Reader::open`.
+        unsafe {
+            assert!((*file.f_op).read.is_some());
+            assert!((*file.f_op).write.is_none());
+            assert_eq!((*(file.private_data as *const Reader)).0, 7);
+        }
+        release(file);
+
+        let file = open(&mut writer);
+        // SAFETY: As above, with `Writer`.
+        unsafe {
+            assert!((*file.f_op).read.is_none());
+            assert!((*file.f_op).write.is_some());
+            assert_eq!((*(file.private_data as *const Writer)).0, 7);
+        }
+        release(file);
+    }
+}
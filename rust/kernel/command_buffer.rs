@@ -0,0 +1,211 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! A buffer for write-then-read command protocols.
+//!
+//! Many simple devices implement a request/response protocol over a single file: userspace
+//! writes a command, then reads the response to it. A [`CommandBuffer`] in the per-open state of
+//! the file holds the last command written until the next read consumes it.
+
+use crate::{error::Error, io_buffer::IoBufferReader, Result};
+use alloc::vec::Vec;
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The last command written to an open file, until it is read.
+///
+/// [`CommandBuffer::store`] is called by [`FileOperations::write`], and stores a whole write as
+/// a command, replacing any command that was not consumed yet: only the last one is answered.
+/// [`CommandBuffer::take`] is called by [`FileOperations::read`], and consumes the pending
+/// command, if any, so that each command is answered at most once; what a read does when no
+/// command is pending is up to the driver, e.g. return `0` (end of file) or `EINVAL`.
+///
+/// Both never sleep while holding the buffer: concurrent calls, e.g. from several threads sharing
+/// the same open file, fail with `EBUSY` instead of waiting for each other.
+///
+/// [`FileOperations::write`]: crate::file_operations::FileOperations::write
+/// [`FileOperations::read`]: crate::file_operations::FileOperations::read
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::command_buffer::CommandBuffer;
+/// # use kernel::file::File;
+/// # use kernel::file_operations::FileOperations;
+/// # use kernel::io_buffer::{IoBufferReader, IoBufferWriter};
+/// struct Session {
+///     cmd: CommandBuffer,
+/// }
+///
+/// impl Default for Session {
+///     fn default() -> Self {
+///         Self {
+///             cmd: CommandBuffer::new(64),
+///         }
+///     }
+/// }
+///
+/// impl FileOperations for Session {
+///     kernel::declare_file_operations!(read, write);
+///
+///     fn write<T: IoBufferReader>(this: &Self, _: &File, data: &mut T, _: u64) -> Result<usize> {
+///         this.cmd.store(data)
+///     }
+///
+///     fn read<T: IoBufferWriter>(this: &Self, _: &File, data: &mut T, _: u64) -> Result<usize> {
+///         let cmd = match this.cmd.take()? {
+///             Some(cmd) => cmd,
+///             None => return Ok(0),
+///         };
+///         // Echo the command back as its response.
+///         let len = cmd.len().min(data.len());
+///         data.write_slice(&cmd[..len])?;
+///         Ok(len)
+///     }
+/// }
+/// ```
+///
+/// # Invariants
+///
+/// `command` is only accessed by the holder of `busy`, and holds at most `max_len` bytes.
+pub struct CommandBuffer {
+    max_len: usize,
+    command: UnsafeCell<Option<Vec<u8>>>,
+    busy: AtomicBool,
+}
+
+// SAFETY: The command is only accessed by the holder of the `busy` flag, and moved in and out of
+// the buffer, never shared.
+unsafe impl Send for CommandBuffer {}
+
+// SAFETY: The command is only accessed by the holder of the `busy` flag, and moved in and out of
+// the buffer, never shared.
+unsafe impl Sync for CommandBuffer {}
+
+impl CommandBuffer {
+    /// Creates a new buffer, with no pending command, for commands of up to `max_len` bytes.
+    pub const fn new(max_len: usize) -> Self {
+        // INVARIANTS: There is no command, and nobody holds `busy`.
+        Self {
+            max_len,
+            command: UnsafeCell::new(None),
+            busy: AtomicBool::new(false),
+        }
+    }
+
+    /// Runs `f` on the pending command, holding the `busy` flag.
+    fn with_command<U>(&self, f: impl FnOnce(&mut Option<Vec<u8>>) -> U) -> Result<U> {
+        if self.busy.swap(true, Ordering::Acquire) {
+            return Err(Error::EBUSY);
+        }
+        // SAFETY: We hold `busy`, so we have exclusive access to the command by the type
+        // invariants.
+        let ret = f(unsafe { &mut *self.command.get() });
+        self.busy.store(false, Ordering::Release);
+        Ok(ret)
+    }
+
+    /// Stores the whole content of `data` as the pending command, replacing the previous one.
+    ///
+    /// Returns the length of the command, i.e., of `data`, which is what `write` returns. Fails
+    /// with `EINVAL` if the command is longer than the maximum length of the buffer, in which
+    /// case the previous command remains pending. Copies the command before claiming the buffer,
+    /// so `data` may be a userspace buffer.
+    pub fn store<T: IoBufferReader>(&self, data: &mut T) -> Result<usize> {
+        if data.len() > self.max_len {
+            return Err(Error::EINVAL);
+        }
+        let command = data.read_all()?;
+        let len = command.len();
+        // INVARIANTS: `command` is at most `max_len` bytes long, as checked above.
+        self.with_command(|c| *c = Some(command))?;
+        Ok(len)
+    }
+
+    /// Consumes the pending command, if any.
+    ///
+    /// A subsequent call returns `None` unless a new command is stored in the meantime.
+    pub fn take(&self) -> Result<Option<Vec<u8>>> {
+        self.with_command(Option::take)
+    }
+
+    /// Drops the pending command, if any, e.g. when the device is reset.
+    pub fn clear(&self) -> Result {
+        self.take().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A reader of a byte slice, standing in for a userspace buffer.
+    struct SliceReader<'a>(&'a [u8]);
+
+    impl IoBufferReader for SliceReader<'_> {
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        unsafe fn read_raw(&mut self, out: *mut u8, len: usize) -> Result {
+            if len > self.0.len() {
+                return Err(Error::EFAULT);
+            }
+            // SAFETY: The caller guarantees that `out` is valid for writing `len` bytes.
+            unsafe { core::ptr::copy_nonoverlapping(self.0.as_ptr(), out, len) };
+            self.0 = &self.0[len..];
+            Ok(())
+        }
+    }
+
+    fn store(buf: &CommandBuffer, command: &[u8]) -> Result<usize> {
+        buf.store(&mut SliceReader(command))
+    }
+
+    #[test]
+    fn test_store_replaces_pending_command() {
+        let buf = CommandBuffer::new(8);
+        assert!(store(&buf, b"first") == Ok(5));
+        assert!(store(&buf, b"second") == Ok(6));
+        assert!(buf.take() == Ok(Some(b"second".to_vec())));
+    }
+
+    #[test]
+    fn test_take_returns_command_once() {
+        let buf = CommandBuffer::new(8);
+        assert!(buf.take() == Ok(None));
+        store(&buf, b"cmd").unwrap();
+        assert!(buf.take() == Ok(Some(b"cmd".to_vec())));
+        assert!(buf.take() == Ok(None));
+
+        store(&buf, b"cmd").unwrap();
+        buf.clear().unwrap();
+        assert!(buf.take() == Ok(None));
+    }
+
+    #[test]
+    fn test_oversize_store_fails() {
+        let buf = CommandBuffer::new(4);
+        store(&buf, b"ok").unwrap();
+        assert!(store(&buf, b"too long") == Err(Error::EINVAL));
+        // The previous command remains pending.
+        assert!(buf.take() == Ok(Some(b"ok".to_vec())));
+        assert!(store(&buf, b"full") == Ok(4));
+    }
+
+    #[test]
+    fn test_busy_buffer_fails() {
+        let buf = CommandBuffer::new(8);
+        store(&buf, b"cmd").unwrap();
+        // Accesses while another one holds the buffer, e.g. from another thread, do not wait.
+        let nested = buf.with_command(|_| (buf.take(), store(&buf, b"new"), buf.clear()));
+        let (taken, stored, cleared) = nested.unwrap();
+        assert!(taken == Err(Error::EBUSY));
+        assert!(stored == Err(Error::EBUSY));
+        assert!(cleared == Err(Error::EBUSY));
+        // The buffer is released afterwards, with the command untouched.
+        assert!(buf.take() == Ok(Some(b"cmd".to_vec())));
+    }
+}
@@ -0,0 +1,349 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Generic devices that are part of the kernel's driver model.
+//!
+//! C header: [`include/linux/device.h`](../../../../include/linux/device.h)
+
+use crate::{
+    bindings, c_types,
+    dma::{DmaDirection, DmaMapping},
+    error::to_result,
+    revocable::Revocable,
+    Result,
+};
+use alloc::{boxed::Box, sync::Arc};
+use core::{fmt, pin::Pin};
+
+extern "C" {
+    #[allow(improper_ctypes)]
+    fn rust_helper_devm_add_action_or_reset(
+        dev: *mut bindings::device,
+        action: unsafe extern "C" fn(*mut c_types::c_void),
+        data: *mut c_types::c_void,
+    ) -> c_types::c_int;
+}
+
+/// A device of the kernel's driver model, e.g. a platform device.
+///
+/// # Safety
+///
+/// Implementers must ensure that the pointer returned by [`Device::raw_device`] is non-null and
+/// valid for the lifetime of `self`, and that it points to the `struct device` of the object that
+/// `self` represents.
+pub unsafe trait Device {
+    /// Returns the raw `struct device` related to `self`.
+    fn raw_device(&self) -> *mut bindings::device;
+
+    /// Maps `buf` for a single DMA transfer by the device in direction `dir`.
+    ///
+    /// The buffer is unmapped when the returned [`DmaMapping`] is dropped, and must not be
+    /// accessed by the CPU until then. See [`DmaMapping`].
+    fn dma_map_single<'a>(&'a self, buf: &'a mut [u8], dir: DmaDirection) -> Result<DmaMapping<'a>>
+    where
+        Self: Sized,
+    {
+        DmaMapping::new(self, buf, dir)
+    }
+}
+
+/// A reference-counted device.
+///
+/// Holding a [`DeviceRef`] keeps the underlying `struct device` alive (though not necessarily
+/// bound to a driver), so it can be stored, e.g. in driver data, without the risk of dangling.
+///
+/// # Invariants
+///
+/// The pointer `DeviceRef::ptr` is non-null and valid, and we hold a reference to it.
+pub struct DeviceRef {
+    ptr: *mut bindings::device,
+}
+
+// SAFETY: The kernel's reference counting of devices is thread-safe, so it is fine to release the
+// reference from any thread.
+unsafe impl Send for DeviceRef {}
+
+// SAFETY: `DeviceRef` only gives access to the raw pointer, whose users are responsible for
+// following the kernel's locking rules.
+unsafe impl Sync for DeviceRef {}
+
+impl DeviceRef {
+    /// Creates a new reference to the given device.
+    pub fn from_dev(dev: &dyn Device) -> Self {
+        // SAFETY: By the safety requirements of `Device`, `raw_device` returns a valid pointer.
+        unsafe { Self::from_ptr(dev.raw_device()) }
+    }
+
+    /// Creates a new reference to the device pointed to by `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null and valid for the duration of the call.
+    pub(crate) unsafe fn from_ptr(ptr: *mut bindings::device) -> Self {
+        // SAFETY: `ptr` is valid by the safety requirements of this function. `get_device`
+        // increments the reference count and returns the same pointer.
+        let ptr = unsafe { bindings::get_device(ptr) };
+        // INVARIANTS: We hold the reference acquired by `get_device` above.
+        Self { ptr }
+    }
+}
+
+// SAFETY: By the type invariants, `ptr` remains valid for as long as we hold our reference.
+unsafe impl Device for DeviceRef {
+    fn raw_device(&self) -> *mut bindings::device {
+        self.ptr
+    }
+}
+
+impl Clone for DeviceRef {
+    fn clone(&self) -> Self {
+        Self::from_dev(self)
+    }
+}
+
+impl Drop for DeviceRef {
+    fn drop(&mut self) {
+        // SAFETY: By the type invariants, we hold a reference to `ptr`, which we now release.
+        unsafe { bindings::put_device(self.ptr) };
+    }
+}
+
+unsafe extern "C" fn devm_revoke_callback<T>(data: *mut c_types::c_void) {
+    // SAFETY: `data` was returned by `Arc::into_raw` in `devm_revocable::<T>`, and devm runs the
+    // action only once, releasing the reference `data` holds.
+    let revocable = unsafe { Arc::from_raw(data as *const Revocable<T>) };
+    revocable.revoke();
+}
+
+/// Wraps `data` in a [`Revocable`] that is revoked when the driver is unbound from `dev`.
+///
+/// This is how device-managed resources, e.g. a [`Regmap`](crate::regmap::Regmap), are shared
+/// with contexts that may outlive the binding, such as the state of an open file. The revocation
+/// is a devm action, so it runs after
+/// [`PlatformDriver::remove`](crate::platdev::PlatformDriver::remove) returns, or if probing
+/// fails, and before the resources that were allocated with devm before the call are freed.
+/// Accesses through [`Revocable::try_access`] fail from then on, and `data` is dropped. If
+/// registering the action fails, `data` is dropped before this returns.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::{device, platdev::PlatformDevice, regmap::Regmap, revocable::Revocable};
+/// fn share(pdev: &PlatformDevice, regmap: Regmap) -> Result<Arc<Revocable<Regmap>>> {
+///     // Accesses fail with `None` once the driver is unbound, before the regmap is freed.
+///     device::devm_revocable(pdev, regmap)
+/// }
+/// ```
+pub fn devm_revocable<T: Send + Sync + 'static>(
+    dev: &dyn Device,
+    data: T,
+) -> Result<Arc<Revocable<T>>> {
+    let revocable = Arc::try_new(Revocable::new(data))?;
+    let ptr = Arc::into_raw(revocable.clone());
+    // SAFETY: `raw_device()` is valid as guaranteed by the `Device` trait. `ptr` holds a reference
+    // to the revocable until the action runs, which releases it. On failure, the action runs
+    // immediately.
+    to_result(unsafe {
+        rust_helper_devm_add_action_or_reset(
+            dev.raw_device(),
+            devm_revoke_callback::<T>,
+            ptr as *mut c_types::c_void,
+        )
+    })?;
+    Ok(revocable)
+}
+
+/// Allocates `data` on the heap, initialises it in place with `init`, and returns a handle to it
+/// that is revoked when the driver is unbound from `dev`.
+///
+/// The data is pinned, so it can hold members that must be initialised once in place, e.g. a
+/// [`SpinLock`](crate::sync::SpinLock) initialised by `init` with `spinlock_init!`. It is
+/// accessed through the returned [`Revocable`], which [`devm_revocable`] revokes when the driver
+/// is unbound, after [`PlatformDriver::remove`](crate::platdev::PlatformDriver::remove) returns,
+/// or if probing fails: the data is dropped then, and later accesses fail, even from contexts that
+/// outlive the binding. If the allocation fails with `ENOMEM`, `data` is dropped before this
+/// returns.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::{device, platdev::PlatformDevice, revocable::Revocable, sync::SpinLock};
+/// struct State {
+///     count: SpinLock<u32>,
+/// }
+///
+/// fn alloc_state(pdev: &PlatformDevice) -> Result<Arc<Revocable<Pin<Box<State>>>>> {
+///     device::devm_alloc_pinned(
+///         pdev,
+///         State {
+///             // SAFETY: `count` is initialised in the call to `spinlock_init` below.
+///             count: unsafe { SpinLock::new(0) },
+///         },
+///         |state| {
+///             // SAFETY: `count` is pinned when `state` is.
+///             let count = unsafe { state.map_unchecked_mut(|s| &mut s.count) };
+///             kernel::spinlock_init!(count, "State::count");
+///         },
+///     )
+/// }
+/// ```
+pub fn devm_alloc_pinned<T: Send + Sync + 'static>(
+    dev: &dyn Device,
+    data: T,
+    init: impl FnOnce(Pin<&mut T>),
+) -> Result<Arc<Revocable<Pin<Box<T>>>>> {
+    let mut data = Pin::from(Box::try_new(data)?);
+    init(data.as_mut());
+    devm_revocable(dev, data)
+}
+
+/// Prints a message via the kernel's [`_dev_printk`], prefixed by the driver and device names.
+///
+/// Public but hidden since it should only be used from public macros.
+///
+/// # Safety
+///
+/// `level` must be one of the kernel's `KERN_*` constants, and `dev` must be valid.
+///
+/// [`_dev_printk`]: ../../../../include/linux/dev_printk.h
+#[doc(hidden)]
+pub unsafe fn call_dev_printk(
+    level: &[u8; 3],
+    dev: *mut bindings::device,
+    args: fmt::Arguments<'_>,
+) {
+    // SAFETY: `level` is null-terminated and `dev` is valid by the safety requirements of this
+    // function, and the format string makes `_dev_printk` format `args` with
+    // `rust_fmt_argument`.
+    unsafe {
+        bindings::_dev_printk(
+            level.as_ptr() as _,
+            dev,
+            b"%pA\0".as_ptr() as _,
+            &args as *const _ as *const c_types::c_void,
+        );
+    }
+}
+
+/// Performs formatting and forwards the string to [`call_dev_printk`].
+///
+/// Public but hidden since it should only be used from public macros.
+#[doc(hidden)]
+#[cfg(not(testlib))]
+#[macro_export]
+macro_rules! dev_printk (
+    ($level:path, $dev:expr, $($arg:tt)+) => ({
+        use $crate::device::Device as _;
+        // SAFETY: This hidden macro should only be called by the documented device printing
+        // macros, which pass one of the `KERN_*` constants. `raw_device` returns a valid pointer
+        // as guaranteed by the `Device` trait, and `$dev` is borrowed for the whole call.
+        unsafe {
+            $crate::device::call_dev_printk($level, $dev.raw_device(), format_args!($($arg)+));
+        }
+    });
+);
+
+/// Stub for doctests
+#[cfg(testlib)]
+#[macro_export]
+macro_rules! dev_printk (
+    ($level:path, $dev:expr, $($arg:tt)+) => (
+        ()
+    );
+);
+
+/// Prints an error-level message (level 3) in the context of a device.
+///
+/// Use this level for error conditions, e.g. to report why a device failed to probe. The message
+/// is prefixed by the names of the driver and of the device, e.g. `rust_pl061 9030000.pl061: `.
+///
+/// Equivalent to the kernel's `dev_err` macro. `$dev` is anything that implements
+/// [`Device`], e.g. a [`PlatformDevice`](crate::platdev::PlatformDevice).
+///
+/// Mimics the interface of [`std::print!`]. See [`core::fmt`] and
+/// [`alloc::format!`] for information about the formatting syntax.
+///
+/// [`std::print!`]: https://doc.rust-lang.org/std/macro.print.html
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::platdev::PlatformDevice;
+/// fn report(pdev: &PlatformDevice, err: Error) {
+///     dev_err!(pdev, "failed to map registers: {:?}\n", err);
+/// }
+/// ```
+#[macro_export]
+macro_rules! dev_err (
+    ($dev:expr, $($arg:tt)*) => (
+        $crate::dev_printk!($crate::bindings::KERN_ERR, $dev, $($arg)*)
+    )
+);
+
+/// Prints a warning-level message (level 4) in the context of a device.
+///
+/// Use this level for warning conditions.
+///
+/// Equivalent to the kernel's `dev_warn` macro. See [`dev_err!`] for the arguments.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::platdev::PlatformDevice;
+/// fn report(pdev: &PlatformDevice) {
+///     dev_warn!(pdev, "no interrupt, falling back to polling\n");
+/// }
+/// ```
+#[macro_export]
+macro_rules! dev_warn (
+    ($dev:expr, $($arg:tt)*) => (
+        $crate::dev_printk!($crate::bindings::KERN_WARNING, $dev, $($arg)*)
+    )
+);
+
+/// Prints a notice-level message (level 5) in the context of a device.
+///
+/// Use this level for normal but significant conditions.
+///
+/// Equivalent to the kernel's `dev_notice` macro. See [`dev_err!`] for the arguments.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::platdev::PlatformDevice;
+/// fn report(pdev: &PlatformDevice) {
+///     dev_notice!(pdev, "firmware is outdated\n");
+/// }
+/// ```
+#[macro_export]
+macro_rules! dev_notice (
+    ($dev:expr, $($arg:tt)*) => (
+        $crate::dev_printk!($crate::bindings::KERN_NOTICE, $dev, $($arg)*)
+    )
+);
+
+/// Prints an info-level message (level 6) in the context of a device.
+///
+/// Use this level for informational messages.
+///
+/// Equivalent to the kernel's `dev_info` macro. See [`dev_err!`] for the arguments.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::platdev::PlatformDevice;
+/// fn report(pdev: &PlatformDevice, revision: u32) {
+///     dev_info!(pdev, "revision {}\n", revision);
+/// }
+/// ```
+#[macro_export]
+macro_rules! dev_info (
+    ($dev:expr, $($arg:tt)*) => (
+        $crate::dev_printk!($crate::bindings::KERN_INFO, $dev, $($arg)*)
+    )
+);
@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Direct memory access (DMA).
+//!
+//! C header: [`include/linux/dma-mapping.h`](../../../../include/linux/dma-mapping.h)
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/core-api/dma-api.html>
+
+use crate::{bindings, c_types, device::Device, error::Error, Result};
+use core::marker::PhantomData;
+
+extern "C" {
+    #[allow(improper_ctypes)]
+    fn rust_helper_dma_map_single(
+        dev: *mut bindings::device,
+        ptr: *mut c_types::c_void,
+        size: usize,
+        dir: bindings::dma_data_direction,
+    ) -> bindings::dma_addr_t;
+
+    #[allow(improper_ctypes)]
+    fn rust_helper_dma_unmap_single(
+        dev: *mut bindings::device,
+        addr: bindings::dma_addr_t,
+        size: usize,
+        dir: bindings::dma_data_direction,
+    );
+
+    #[allow(improper_ctypes)]
+    fn rust_helper_dma_mapping_error(
+        dev: *mut bindings::device,
+        addr: bindings::dma_addr_t,
+    ) -> c_types::c_int;
+}
+
+/// The direction of a DMA transfer.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DmaDirection {
+    /// The device reads from memory.
+    ToDevice,
+
+    /// The device writes to memory.
+    FromDevice,
+
+    /// The device may both read from and write to memory.
+    Bidirectional,
+}
+
+impl DmaDirection {
+    fn to_raw(self) -> bindings::dma_data_direction {
+        match self {
+            DmaDirection::ToDevice => bindings::dma_data_direction_DMA_TO_DEVICE,
+            DmaDirection::FromDevice => bindings::dma_data_direction_DMA_FROM_DEVICE,
+            DmaDirection::Bidirectional => bindings::dma_data_direction_DMA_BIDIRECTIONAL,
+        }
+    }
+}
+
+/// A streaming DMA mapping of an existing buffer, for a single transfer.
+///
+/// The buffer is mapped so that the device can access it at [`DmaMapping::dma_addr`], and is
+/// unmapped when the [`DmaMapping`] is dropped. Until then, the buffer belongs to the device: the
+/// CPU must not access it, as it may see stale data or have its writes overwritten, depending on
+/// the caches of the architecture. This is enforced by the mapping borrowing the buffer mutably.
+/// Data written by the device can thus only be read after the transfer completes and the mapping
+/// is dropped.
+///
+/// # Invariants
+///
+/// `DmaMapping::dev` is valid, and `DmaMapping::addr` is the address of a mapping of
+/// `DmaMapping::len` bytes in direction `DmaMapping::dir` for that device.
+pub struct DmaMapping<'a> {
+    dev: *mut bindings::device,
+    addr: bindings::dma_addr_t,
+    len: usize,
+    dir: DmaDirection,
+    _p: PhantomData<(&'a dyn Device, &'a mut [u8])>,
+}
+
+impl<'a> DmaMapping<'a> {
+    /// Maps `buf` for a DMA transfer in direction `dir` by the device `dev`.
+    ///
+    /// Fails with `ENOMEM` if the buffer cannot be mapped, e.g. because it lies outside of the
+    /// memory addressable by the device and no bounce buffer is available.
+    pub fn new(dev: &'a dyn Device, buf: &'a mut [u8], dir: DmaDirection) -> Result<Self> {
+        let dev = dev.raw_device();
+        // SAFETY: `dev` is valid by the safety requirements of `Device`, and `buf` is a valid
+        // buffer of `buf.len()` bytes, which is borrowed for as long as it is mapped.
+        let addr = unsafe {
+            rust_helper_dma_map_single(dev, buf.as_mut_ptr() as _, buf.len(), dir.to_raw())
+        };
+        // SAFETY: `dev` is valid, and `addr` was just returned for it by `dma_map_single`.
+        if unsafe { rust_helper_dma_mapping_error(dev, addr) } != 0 {
+            return Err(Error::ENOMEM);
+        }
+        // INVARIANTS: The buffer was mapped above, and `dev` is valid for the lifetime `'a`.
+        Ok(Self {
+            dev,
+            addr,
+            len: buf.len(),
+            dir,
+            _p: PhantomData,
+        })
+    }
+
+    /// Returns the address of the buffer as seen by the device.
+    pub fn dma_addr(&self) -> bindings::dma_addr_t {
+        self.addr
+    }
+
+    /// Returns the length of the mapped buffer, in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the mapped buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Drop for DmaMapping<'_> {
+    fn drop(&mut self) {
+        // SAFETY: By the type invariants, `self.addr` is a mapping with these parameters for the
+        // valid device `self.dev`.
+        unsafe { rust_helper_dma_unmap_single(self.dev, self.addr, self.len, self.dir.to_raw()) };
+    }
+}
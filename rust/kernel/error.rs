@@ -7,7 +7,7 @@
 use crate::str::CStr;
 use crate::{bindings, c_types};
 use alloc::{alloc::AllocError, collections::TryReserveError};
-use core::convert::From;
+use core::convert::{From, Infallible};
 use core::fmt;
 use core::num::TryFromIntError;
 use core::str::{self, Utf8Error};
@@ -17,48 +17,149 @@ use core::str::{self, Utf8Error};
 /// The kernel defines a set of integer generic error codes based on C and
 /// POSIX ones. These codes may have a more specific meaning in some contexts.
 ///
+/// The error constants (e.g. [`Error::EINVAL`]) and [`Error::to_kernel_errno`] can be used in
+/// const contexts, so const functions can return a [`Result`] (and handle one with `match`, since
+/// the `?` operator is not available there) instead of panicking.
+///
+/// When the kernel is built with `CONFIG_RUST_ERROR_CONTEXT`, errors also carry the chain of
+/// contexts attached with [`Error::context`], which their [`Debug`](fmt::Debug) implementation
+/// prints. Errors always compare equal when their error codes are equal, whatever their contexts.
+///
 /// # Invariants
 ///
 /// The value is a valid `errno` (i.e. `>= -MAX_ERRNO && < 0`).
 #[derive(Clone, Copy, PartialEq, Eq)]
-pub struct Error(c_types::c_int);
+pub struct Error(c_types::c_int, Context);
+
+/// The maximum number of contexts an [`Error`] records; further ones are counted but dropped.
+#[cfg(CONFIG_RUST_ERROR_CONTEXT)]
+const CONTEXT_DEPTH: usize = 4;
+
+/// The chain of contexts attached to an [`Error`], innermost first.
+///
+/// # Invariants
+///
+/// The first `min(len, CONTEXT_DEPTH)` entries of `chain` are the contexts attached so far.
+#[cfg(CONFIG_RUST_ERROR_CONTEXT)]
+#[derive(Clone, Copy)]
+struct Context {
+    chain: [&'static str; CONTEXT_DEPTH],
+    len: usize,
+}
+
+#[cfg(CONFIG_RUST_ERROR_CONTEXT)]
+impl Context {
+    const NONE: Self = Context {
+        chain: [""; CONTEXT_DEPTH],
+        len: 0,
+    };
+
+    fn push(&mut self, context: &'static str) {
+        if self.len < CONTEXT_DEPTH {
+            self.chain[self.len] = context;
+        }
+        // INVARIANTS: The context was stored above if there was room for it.
+        self.len += 1;
+    }
+}
+
+// Contexts are diagnostics only: they do not take part in comparisons of errors.
+#[cfg(CONFIG_RUST_ERROR_CONTEXT)]
+impl PartialEq for Context {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(CONFIG_RUST_ERROR_CONTEXT)]
+impl Eq for Context {}
+
+/// Without `CONFIG_RUST_ERROR_CONTEXT`, contexts are not recorded and take no space.
+#[cfg(not(CONFIG_RUST_ERROR_CONTEXT))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Context;
+
+#[cfg(not(CONFIG_RUST_ERROR_CONTEXT))]
+impl Context {
+    const NONE: Self = Context;
+
+    #[inline(always)]
+    fn push(&mut self, _context: &'static str) {}
+}
 
 impl Error {
     /// Invalid argument.
-    pub const EINVAL: Self = Error(-(bindings::EINVAL as i32));
+    pub const EINVAL: Self = Error(-(bindings::EINVAL as i32), Context::NONE);
 
     /// Out of memory.
-    pub const ENOMEM: Self = Error(-(bindings::ENOMEM as i32));
+    pub const ENOMEM: Self = Error(-(bindings::ENOMEM as i32), Context::NONE);
 
     /// Bad address.
-    pub const EFAULT: Self = Error(-(bindings::EFAULT as i32));
+    pub const EFAULT: Self = Error(-(bindings::EFAULT as i32), Context::NONE);
 
     /// Illegal seek.
-    pub const ESPIPE: Self = Error(-(bindings::ESPIPE as i32));
+    pub const ESPIPE: Self = Error(-(bindings::ESPIPE as i32), Context::NONE);
 
     /// Try again.
-    pub const EAGAIN: Self = Error(-(bindings::EAGAIN as i32));
+    pub const EAGAIN: Self = Error(-(bindings::EAGAIN as i32), Context::NONE);
 
     /// Device or resource busy.
-    pub const EBUSY: Self = Error(-(bindings::EBUSY as i32));
+    pub const EBUSY: Self = Error(-(bindings::EBUSY as i32), Context::NONE);
 
     /// Restart the system call.
-    pub const ERESTARTSYS: Self = Error(-(bindings::ERESTARTSYS as i32));
+    pub const ERESTARTSYS: Self = Error(-(bindings::ERESTARTSYS as i32), Context::NONE);
 
     /// Operation not permitted.
-    pub const EPERM: Self = Error(-(bindings::EPERM as i32));
+    pub const EPERM: Self = Error(-(bindings::EPERM as i32), Context::NONE);
+
+    /// Permission denied.
+    pub const EACCES: Self = Error(-(bindings::EACCES as i32), Context::NONE);
 
     /// No such process.
-    pub const ESRCH: Self = Error(-(bindings::ESRCH as i32));
+    pub const ESRCH: Self = Error(-(bindings::ESRCH as i32), Context::NONE);
 
     /// No such file or directory.
-    pub const ENOENT: Self = Error(-(bindings::ENOENT as i32));
+    pub const ENOENT: Self = Error(-(bindings::ENOENT as i32), Context::NONE);
 
     /// Interrupted system call.
-    pub const EINTR: Self = Error(-(bindings::EINTR as i32));
+    pub const EINTR: Self = Error(-(bindings::EINTR as i32), Context::NONE);
 
     /// Bad file number.
-    pub const EBADF: Self = Error(-(bindings::EBADF as i32));
+    pub const EBADF: Self = Error(-(bindings::EBADF as i32), Context::NONE);
+
+    /// I/O error.
+    pub const EIO: Self = Error(-(bindings::EIO as i32), Context::NONE);
+
+    /// No such device.
+    pub const ENODEV: Self = Error(-(bindings::ENODEV as i32), Context::NONE);
+
+    /// Inappropriate ioctl for device.
+    pub const ENOTTY: Self = Error(-(bindings::ENOTTY as i32), Context::NONE);
+
+    /// Protocol error.
+    pub const EPROTO: Self = Error(-(bindings::EPROTO as i32), Context::NONE);
+
+    /// Operation not supported on transport endpoint.
+    ///
+    /// This is the code to return to userspace for unsupported operations, e.g. an ioctl command
+    /// that is valid for the device type but not implemented by the driver.
+    pub const EOPNOTSUPP: Self = Error(-(bindings::EOPNOTSUPP as i32), Context::NONE);
+
+    /// Operation canceled.
+    pub const ECANCELED: Self = Error(-(bindings::ECANCELED as i32), Context::NONE);
+
+    /// Connection timed out.
+    ///
+    /// This is also the code returned when waiting for a condition, e.g. for a device to be
+    /// ready, takes longer than allowed.
+    pub const ETIMEDOUT: Self = Error(-(bindings::ETIMEDOUT as i32), Context::NONE);
+
+    /// Operation is not supported.
+    ///
+    /// This code is internal to the kernel and unknown to userspace, where it shows up as an
+    /// unknown error: it is only returned to kernel APIs that expect it, and [`Error::EOPNOTSUPP`]
+    /// is returned to userspace instead.
+    pub const ENOTSUPP: Self = Error(-(bindings::ENOTSUPP as i32), Context::NONE);
 
     /// Creates an [`Error`] from a kernel error code.
     ///
@@ -76,7 +177,7 @@ impl Error {
 
         // INVARIANT: the check above ensures the type invariant
         // will hold.
-        Error(errno)
+        Error(errno, Context::NONE)
     }
 
     /// Creates an [`Error`] from a kernel error code.
@@ -84,36 +185,156 @@ impl Error {
     /// # Safety
     ///
     /// `errno` must be within error code range (i.e. `>= -MAX_ERRNO && < 0`).
-    pub(crate) unsafe fn from_kernel_errno_unchecked(errno: c_types::c_int) -> Error {
+    pub(crate) const unsafe fn from_kernel_errno_unchecked(errno: c_types::c_int) -> Error {
         // INVARIANT: the contract ensures the type invariant
         // will hold.
-        Error(errno)
+        Error(errno, Context::NONE)
     }
 
     /// Returns the kernel error code.
-    pub fn to_kernel_errno(self) -> c_types::c_int {
+    pub const fn to_kernel_errno(self) -> c_types::c_int {
         self.0
     }
-}
 
-impl fmt::Debug for Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    /// Returns the name of the error code, e.g. `ENOMEM`.
+    ///
+    /// Returns [`None`] if the error code has no name, or if the kernel is built without
+    /// `CONFIG_SYMBOLIC_ERRNAME`.
+    pub fn name(&self) -> Option<&'static CStr> {
         extern "C" {
             fn rust_helper_errname(err: c_types::c_int) -> *const c_types::c_char;
         }
         // SAFETY: FFI call.
         let name = unsafe { rust_helper_errname(-self.0) };
-
         if name.is_null() {
+            return None;
+        }
+        // SAFETY: `'static` string from C, and is not NULL.
+        Some(unsafe { CStr::from_char_ptr(name) })
+    }
+
+    /// Attaches `context` to the error, e.g. the name of the operation that failed, so that
+    /// printing the error tells which layer of a deep call stack produced it.
+    ///
+    /// Contexts are only recorded when the kernel is built with `CONFIG_RUST_ERROR_CONTEXT`,
+    /// which is meant for debugging; otherwise this compiles to nothing. See also
+    /// [`ResultExt::context`].
+    pub fn context(mut self, context: &'static str) -> Self {
+        self.1.push(context);
+        self
+    }
+
+    /// Returns the category of the error.
+    ///
+    /// This allows matching on the intent of an error rather than on specific error codes, e.g.
+    /// both `EPERM` and `EACCES` are [`ErrorKind::PermissionDenied`].
+    pub fn kind(&self) -> ErrorKind {
+        // CAST: `self.0` is a negative `errno` by the type invariants, so its negation is a
+        // positive value that fits in a `u32`.
+        match -self.0 as u32 {
+            bindings::ENOENT | bindings::ENODEV | bindings::ENXIO => ErrorKind::NotFound,
+            bindings::EPERM | bindings::EACCES => ErrorKind::PermissionDenied,
+            bindings::EAGAIN => ErrorKind::WouldBlock,
+            bindings::EINTR | bindings::ERESTARTSYS => ErrorKind::Interrupted,
+            bindings::EINVAL => ErrorKind::InvalidInput,
+            bindings::ENOMEM => ErrorKind::OutOfMemory,
+            bindings::EBUSY => ErrorKind::Busy,
+            bindings::EEXIST => ErrorKind::AlreadyExists,
+            bindings::ETIMEDOUT => ErrorKind::TimedOut,
+            bindings::EFAULT => ErrorKind::BadAddress,
+            bindings::ENOSYS | bindings::ENOTTY | bindings::EOPNOTSUPP | bindings::ENOTSUPP => {
+                ErrorKind::Unsupported
+            }
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// A category of [`Error`]s, as returned by [`Error::kind`].
+///
+/// Equivalent to [`std::io::ErrorKind`].
+///
+/// [`std::io::ErrorKind`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// An entity was not found (`ENOENT`, `ENODEV`, `ENXIO`).
+    NotFound,
+
+    /// The operation lacked the necessary privileges (`EPERM`, `EACCES`).
+    PermissionDenied,
+
+    /// The operation would block, but non-blocking operation was requested (`EAGAIN`, also known
+    /// as `EWOULDBLOCK`).
+    WouldBlock,
+
+    /// The operation was interrupted by a signal (`EINTR`, `ERESTARTSYS`).
+    Interrupted,
+
+    /// An argument was invalid (`EINVAL`).
+    InvalidInput,
+
+    /// Memory could not be allocated (`ENOMEM`).
+    OutOfMemory,
+
+    /// A resource is busy (`EBUSY`).
+    Busy,
+
+    /// An entity already exists (`EEXIST`).
+    AlreadyExists,
+
+    /// The operation timed out (`ETIMEDOUT`).
+    TimedOut,
+
+    /// An address was invalid (`EFAULT`).
+    BadAddress,
+
+    /// The operation is not supported (`ENOSYS`, `ENOTTY`, `EOPNOTSUPP`, `ENOTSUPP`).
+    Unsupported,
+
+    /// Any other error.
+    Other,
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
             // Print out number if no name can be found.
-            return f.debug_tuple("Error").field(&-self.0).finish();
+            None => f.debug_tuple("Error").field(&-self.0).finish()?,
+            Some(cstr) => {
+                // SAFETY: These strings are ASCII-only.
+                let str = unsafe { str::from_utf8_unchecked(cstr) };
+                f.debug_tuple(str).finish()?;
+            }
         }
+        self.1.write_chain(f)
+    }
+}
 
-        // SAFETY: `'static` string from C, and is not NULL.
-        let cstr = unsafe { CStr::from_char_ptr(name) };
-        // SAFETY: These strings are ASCII-only.
-        let str = unsafe { str::from_utf8_unchecked(cstr) };
-        f.debug_tuple(str).finish()
+impl Context {
+    /// Prints the chain of contexts, outermost first, e.g. ` (probe: read_config: parse)`.
+    #[cfg(CONFIG_RUST_ERROR_CONTEXT)]
+    fn write_chain(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.len == 0 {
+            return Ok(());
+        }
+        f.write_str(" (")?;
+        if self.len > CONTEXT_DEPTH {
+            f.write_str("...: ")?;
+        }
+        let recorded = &self.chain[..self.len.min(CONTEXT_DEPTH)];
+        for (i, context) in recorded.iter().rev().enumerate() {
+            if i != 0 {
+                f.write_str(": ")?;
+            }
+            f.write_str(context)?;
+        }
+        f.write_str(")")
+    }
+
+    #[cfg(not(CONFIG_RUST_ERROR_CONTEXT))]
+    #[inline(always)]
+    fn write_chain(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
     }
 }
 
@@ -135,6 +356,14 @@ impl From<TryReserveError> for Error {
     }
 }
 
+// Allows generic code that is parameterised by an error type to be instantiated with
+// `Infallible` while still using `?` on results that carry an `Error`.
+impl From<Infallible> for Error {
+    fn from(e: Infallible) -> Error {
+        match e {}
+    }
+}
+
 /// A [`Result`] with an [`Error`] error type.
 ///
 /// To be used as the return type for functions that may fail.
@@ -157,6 +386,54 @@ impl From<TryReserveError> for Error {
 /// just an [`Error`].
 pub type Result<T = ()> = core::result::Result<T, Error>;
 
+/// Extension methods for [`Result`] that offer panic-free alternatives to `unwrap()`.
+///
+/// Panicking in the kernel oopses the machine. Driver code should propagate errors instead, or
+/// at least fall back to a sane value, which is what the methods in this trait help with.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// fn probe_width(raw: Result<u32>) -> Result<u32> {
+///     // Fall back to a default width rather than `unwrap()`ping.
+///     let width = raw.unwrap_or_errno(32);
+///     // Log the error, then propagate it.
+///     validate(width).ok_or_log("invalid width")
+/// }
+/// ```
+pub trait ResultExt<T> {
+    /// Returns the contained value or `default` if `self` is an error.
+    ///
+    /// Unlike `unwrap()`, this never panics.
+    fn unwrap_or_errno(self, default: T) -> T;
+
+    /// Logs `msg` and the error via [`crate::pr_err`] if `self` is an error, then returns `self`
+    /// unchanged so the error can be propagated with `?`.
+    fn ok_or_log(self, msg: &str) -> Result<T>;
+
+    /// Attaches `context` to the error if `self` is an error, then returns `self` so the error can
+    /// be propagated with `?`. See [`Error::context`].
+    fn context(self, context: &'static str) -> Result<T>;
+}
+
+impl<T> ResultExt<T> for Result<T> {
+    fn unwrap_or_errno(self, default: T) -> T {
+        self.unwrap_or(default)
+    }
+
+    fn ok_or_log(self, msg: &str) -> Result<T> {
+        if let Err(e) = &self {
+            crate::pr_err!("{}: {:?}\n", msg, e);
+        }
+        self
+    }
+
+    fn context(self, context: &'static str) -> Result<T> {
+        self.map_err(|e| e.context(context))
+    }
+}
+
 impl From<AllocError> for Error {
     fn from(_: AllocError) -> Error {
         Error::ENOMEM
@@ -188,6 +465,10 @@ where
 ///
 /// `T` should be convertible to an `i16` via `From<i16>`.
 ///
+/// The block is the body of a closure returning [`crate::Result`], so `?` can be used on results
+/// whose error type converts into [`Error`] via [`From`], not only on results that already use
+/// [`Error`].
+///
 /// # Examples
 ///
 /// ```ignore
@@ -207,12 +488,68 @@ where
 #[macro_export]
 macro_rules! from_kernel_result {
     ($($tt:tt)*) => {{
-        $crate::error::from_kernel_result_helper((|| {
+        $crate::error::from_kernel_result_helper((|| -> $crate::Result<_> {
             $($tt)*
         })())
     }};
 }
 
+/// Converts an integer as returned by a C kernel function to a [`Result`].
+///
+/// Many kernel C API functions return zero on success and a negative `errno` on failure. This
+/// function performs the check and converts the return value to a [`Result`] in an idiomatic
+/// fashion.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::bindings;
+/// fn regmap_write(map: *mut bindings::regmap, reg: u32, val: u32) -> Result {
+///     // SAFETY: FFI call.
+///     to_result(unsafe { bindings::regmap_write(map, reg, val) })
+/// }
+/// ```
+pub(crate) fn to_result(ret: c_types::c_int) -> Result {
+    if ret != 0 {
+        return Err(Error::from_kernel_errno(ret));
+    }
+    Ok(())
+}
+
+/// Converts an integer as returned by a C kernel function that may return a positive value on
+/// success to a [`Result`].
+///
+/// Some kernel C API functions follow a three-way convention:
+///
+/// - a negative value is an `errno`: the call failed;
+/// - zero means that the call succeeded;
+/// - a positive value means that the call succeeded as well, and carries some information, e.g.
+///   `pm_runtime_get_sync` returns `1` if the device was already active, and
+///   `platform_get_irq` returns the number of the interrupt.
+///
+/// This function returns the non-negative value on success, so that callers can tell the last
+/// two cases apart. [`to_result`] must not be used for such functions, as it treats positive
+/// values as errors.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::bindings;
+/// fn get_sync(dev: *mut bindings::device) -> Result<bool> {
+///     // SAFETY: FFI call.
+///     let already_active = to_result_value(unsafe { bindings::pm_runtime_get_sync(dev) })? == 1;
+///     Ok(already_active)
+/// }
+/// ```
+pub(crate) fn to_result_value(ret: c_types::c_int) -> Result<u32> {
+    if ret < 0 {
+        return Err(Error::from_kernel_errno(ret));
+    }
+    Ok(ret as u32)
+}
+
 /// Transform a kernel "error pointer" to a normal pointer.
 ///
 /// Some kernel C API functions return an "error pointer" which optionally
@@ -240,8 +577,6 @@ macro_rules! from_kernel_result {
 ///     }
 /// }
 /// ```
-// TODO: remove `dead_code` marker once an in-kernel client is available.
-#[allow(dead_code)]
 pub(crate) fn from_kernel_err_ptr<T>(ptr: *mut T) -> Result<*mut T> {
     extern "C" {
         #[allow(improper_ctypes)]
@@ -270,3 +605,121 @@ pub(crate) fn from_kernel_err_ptr<T>(ptr: *mut T) -> Result<*mut T> {
     }
     Ok(ptr)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CustomError;
+
+    impl From<CustomError> for Error {
+        fn from(_: CustomError) -> Error {
+            Error::EBUSY
+        }
+    }
+
+    fn custom(fail: bool) -> core::result::Result<i32, CustomError> {
+        if fail {
+            Err(CustomError)
+        } else {
+            Ok(7)
+        }
+    }
+
+    #[test]
+    fn test_context_does_not_affect_equality() {
+        let e = Error::EINVAL.context("inner").context("outer");
+        assert!(e == Error::EINVAL);
+        assert_eq!(e.to_kernel_errno(), Error::EINVAL.to_kernel_errno());
+        assert!(Err::<(), _>(Error::EBUSY).context("op") == Err(Error::EBUSY));
+    }
+
+    /// Formats the chain of contexts of an error, as its `Debug` implementation does after its
+    /// name.
+    struct Chain(Error);
+
+    impl fmt::Debug for Chain {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0 .1.write_chain(f)
+        }
+    }
+
+    #[test]
+    #[cfg(CONFIG_RUST_ERROR_CONTEXT)]
+    fn test_chain_prints_outermost_first() {
+        assert_eq!(alloc::format!("{:?}", Chain(Error::EINVAL)), "");
+        let e = Error::EINVAL
+            .context("parse")
+            .context("read_config")
+            .context("probe");
+        assert_eq!(
+            alloc::format!("{:?}", Chain(e)),
+            " (probe: read_config: parse)"
+        );
+
+        // Only the innermost contexts are recorded.
+        let e = e.context("bind").context("init");
+        assert_eq!(
+            alloc::format!("{:?}", Chain(e)),
+            " (...: bind: probe: read_config: parse)"
+        );
+    }
+
+    #[test]
+    #[cfg(not(CONFIG_RUST_ERROR_CONTEXT))]
+    fn test_chain_is_empty_without_config() {
+        let e = Error::EINVAL.context("parse").context("probe");
+        assert_eq!(alloc::format!("{:?}", Chain(e)), "");
+        assert_eq!(
+            core::mem::size_of::<Error>(),
+            core::mem::size_of::<c_types::c_int>()
+        );
+    }
+
+    #[test]
+    fn test_to_result_value_keeps_positive_values() {
+        assert!(to_result_value(-(bindings::EAGAIN as i32)) == Err(Error::EAGAIN));
+        assert!(to_result_value(0) == Ok(0));
+        assert!(to_result_value(1) == Ok(1));
+    }
+
+    #[test]
+    fn test_errno_round_trip() {
+        for e in &[
+            Error::ENODEV,
+            Error::EPROTO,
+            Error::EOPNOTSUPP,
+            Error::ECANCELED,
+            Error::ETIMEDOUT,
+            Error::ENOTSUPP,
+        ] {
+            assert!(Error::from_kernel_errno(e.to_kernel_errno()) == *e);
+        }
+        assert_eq!(
+            Error::EOPNOTSUPP.to_kernel_errno(),
+            -(bindings::EOPNOTSUPP as i32)
+        );
+        assert_eq!(Error::ENOTSUPP.to_kernel_errno(), -(bindings::ENOTSUPP as i32));
+    }
+
+    #[test]
+    fn test_to_result() {
+        assert!(to_result(0) == Ok(()));
+        assert!(to_result(-(bindings::EBUSY as i32)) == Err(Error::EBUSY));
+    }
+
+    #[test]
+    fn test_from_kernel_result_converts_errors() {
+        let ok: c_types::c_int = crate::from_kernel_result! {
+            let v = custom(false)?;
+            Ok(v)
+        };
+        assert_eq!(ok, 7);
+
+        let err: c_types::c_int = crate::from_kernel_result! {
+            let v = custom(true)?;
+            Ok(v)
+        };
+        assert_eq!(err, Error::EBUSY.to_kernel_errno());
+    }
+}
@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Event file descriptors.
+//!
+//! Allow drivers to notify userspace of events through an eventfd that userspace passes in, e.g.
+//! via an ioctl, instead of having userspace poll the device.
+//!
+//! C header: [`include/linux/eventfd.h`](../../../../include/linux/eventfd.h)
+
+use crate::{bindings, error::from_kernel_err_ptr, Result};
+
+/// A reference to the context of an eventfd.
+///
+/// The reference is released when the [`EventFd`] is dropped, so it is usually stored in the
+/// per-open state of a file and released when the file is closed.
+///
+/// # Invariants
+///
+/// The pointer `EventFd::ctx` is non-null and valid, and we hold a reference to it.
+pub struct EventFd {
+    ctx: *mut bindings::eventfd_ctx,
+}
+
+// SAFETY: `eventfd_signal` and `eventfd_ctx_put` may be called from any thread.
+unsafe impl Send for EventFd {}
+
+// SAFETY: `eventfd_signal` may be called concurrently from multiple threads.
+unsafe impl Sync for EventFd {}
+
+impl EventFd {
+    /// Takes a reference to the eventfd behind the file descriptor `fd` of the current process.
+    ///
+    /// Fails with `EBADF` if `fd` is not an open file descriptor, or `EINVAL` if it is not an
+    /// eventfd.
+    pub fn from_fd(fd: u32) -> Result<Self> {
+        // SAFETY: FFI call, there are no requirements on `fd`.
+        let ctx = from_kernel_err_ptr(unsafe { bindings::eventfd_ctx_fdget(fd as _) })?;
+        // INVARIANTS: `eventfd_ctx_fdget` succeeded, so `ctx` is valid and we own a reference.
+        Ok(Self { ctx })
+    }
+
+    /// Adds `count` to the eventfd counter, waking up userspace waiting on it.
+    ///
+    /// Returns the amount actually added, which may be less than `count` if the counter would
+    /// overflow. May be called from any context, including interrupt handlers.
+    pub fn signal(&self, count: u64) -> u64 {
+        // SAFETY: `self.ctx` is valid by the type invariants.
+        unsafe { bindings::eventfd_signal(self.ctx, count) }
+    }
+}
+
+impl Drop for EventFd {
+    fn drop(&mut self) {
+        // SAFETY: By the type invariants, we hold a reference to `self.ctx`, which we now release.
+        unsafe { bindings::eventfd_ctx_put(self.ctx) };
+    }
+}
@@ -5,9 +5,14 @@
 //! C headers: [`include/linux/fs.h`](../../../../include/linux/fs.h) and
 //! [`include/linux/file.h`](../../../../include/linux/file.h)
 
-use crate::{bindings, error::Error, Result};
+use crate::{bindings, error::Error, str::CStr, Result};
+use alloc::boxed::Box;
 use core::{mem::ManuallyDrop, ops::Deref};
 
+// `bindgen` does not translate these, since they are defined with a `__force` cast.
+const FMODE_READ: u32 = 0x1;
+const FMODE_WRITE: u32 = 0x2;
+
 /// Wraps the kernel's `struct file`.
 ///
 /// # Invariants
@@ -39,11 +44,81 @@ impl File {
         unsafe { (*self.ptr).f_pos as u64 }
     }
 
+    /// Returns whether the file was opened for appending (`O_APPEND`).
+    pub fn is_append(&self) -> bool {
+        // SAFETY: `File::ptr` is guaranteed to be valid by the type invariants.
+        unsafe { (*self.ptr).f_flags & bindings::O_APPEND != 0 }
+    }
+
     /// Returns whether the file is in blocking mode.
     pub fn is_blocking(&self) -> bool {
         // SAFETY: `File::ptr` is guaranteed to be valid by the type invariants.
         unsafe { (*self.ptr).f_flags & bindings::O_NONBLOCK == 0 }
     }
+
+    /// Returns the name of the directory entry the file was opened through, e.g. the name of the
+    /// device node, without the rest of its path.
+    ///
+    /// This tells which node a file corresponds to when a device has several of them, e.g. for
+    /// logging. Since the entry may be renamed while the file is open, the name is a snapshot
+    /// taken when this is called. Anonymous files have a placeholder name, e.g. `[eventfd]`.
+    /// Returns `ENOENT` if the file has no directory entry at all.
+    pub fn name(&self) -> Result<FileName> {
+        // SAFETY: `File::ptr` is guaranteed to be valid by the type invariants.
+        let dentry = unsafe { (*self.ptr).f_path.dentry };
+        if dentry.is_null() {
+            return Err(Error::ENOENT);
+        }
+        let mut snap = Box::try_new(bindings::name_snapshot::default())?;
+        // SAFETY: The file holds a reference to its path, so `dentry` is valid. `snap` is a valid
+        // snapshot, which does not move once taken since it is boxed.
+        unsafe { bindings::take_dentry_name_snapshot(&mut *snap, dentry) };
+        // INVARIANTS: The snapshot was taken above.
+        Ok(FileName { snap })
+    }
+
+    /// Returns the mode the file was opened with (`struct file::f_mode`), i.e., a combination of
+    /// the `FMODE_*` flags.
+    pub fn mode(&self) -> u32 {
+        // SAFETY: `File::ptr` is guaranteed to be valid by the type invariants.
+        unsafe { (*self.ptr).f_mode }
+    }
+
+    /// Returns whether the file was opened for reading (`FMODE_READ`).
+    pub fn is_readable(&self) -> bool {
+        self.mode() & FMODE_READ != 0
+    }
+
+    /// Returns whether the file was opened for writing (`FMODE_WRITE`).
+    pub fn is_writable(&self) -> bool {
+        self.mode() & FMODE_WRITE != 0
+    }
+}
+
+/// A snapshot of the name of the directory entry of a file, as returned by [`File::name`].
+///
+/// # Invariants
+///
+/// `snap` holds a snapshot taken by `take_dentry_name_snapshot`, which is not released yet.
+pub struct FileName {
+    snap: Box<bindings::name_snapshot>,
+}
+
+impl Deref for FileName {
+    type Target = CStr;
+
+    fn deref(&self) -> &CStr {
+        // SAFETY: The snapshot holds a `NUL`-terminated name, either inline or with a reference to
+        // an external name, which remains valid until the snapshot is released.
+        unsafe { CStr::from_char_ptr(self.snap.name.name as _) }
+    }
+}
+
+impl Drop for FileName {
+    fn drop(&mut self) {
+        // SAFETY: The snapshot was taken and not released yet, by the type invariants.
+        unsafe { bindings::release_dentry_name_snapshot(&mut *self.snap) };
+    }
 }
 
 impl Drop for File {
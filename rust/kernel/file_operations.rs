@@ -16,11 +16,20 @@ use crate::{
     from_kernel_result,
     io_buffer::{IoBufferReader, IoBufferWriter},
     iov_iter::IovIter,
+    pages::Pages,
     sync::CondVar,
     types::PointerWrapper,
-    user_ptr::{UserSlicePtr, UserSlicePtrReader, UserSlicePtrWriter},
+    user_ptr::{UserPtr, UserSlicePtr, UserSlicePtrReader, UserSlicePtrWriter},
 };
 
+extern "C" {
+    #[allow(improper_ctypes)]
+    fn rust_helper_spin_lock(lock: *mut bindings::spinlock_t);
+
+    #[allow(improper_ctypes)]
+    fn rust_helper_spin_unlock(lock: *mut bindings::spinlock_t);
+}
+
 /// Wraps the kernel's `struct poll_table_struct`.
 ///
 /// # Invariants
@@ -84,8 +93,20 @@ unsafe extern "C" fn open_callback<A: FileOpenAdapter, T: FileOpener<A::Arg>>(
     file: *mut bindings::file,
 ) -> c_types::c_int {
     from_kernel_result! {
+        // SAFETY: `open_callback` is only installed by registrations whose adapter is `A`, so
+        // this is being called for a file that was registered by `A`.
         let arg = unsafe { A::convert(inode, file) };
+        if T::TO_USE.nonseekable {
+            // SAFETY: `inode` and `file` are valid for the duration of this call.
+            unsafe { bindings::nonseekable_open(inode, file) };
+        }
         let ptr = T::open(unsafe { &*arg })?.into_pointer();
+        // SAFETY: `file` is valid for the duration of this call. `private_data` is only set once
+        // `T::open` has succeeded and nothing can fail afterwards, so on error there is nothing to
+        // free: the kernel does not call `release` for files whose `open` failed. Conversely, once
+        // `open` has returned successfully, the kernel calls `release` exactly once, even if it
+        // fails to complete the open itself afterwards. No other callback can run before `open`
+        // returns, since the file is not visible to userspace yet.
         unsafe { (*file).private_data = ptr as *mut c_types::c_void };
         Ok(0)
     }
@@ -106,8 +127,9 @@ unsafe extern "C" fn read_callback<T: FileOperations>(
         let f = unsafe { T::Wrapper::borrow((*file).private_data) };
         // No `FMODE_UNSIGNED_OFFSET` support, so `offset` must be in [0, 2^63).
         // See discussion in https://github.com/fishinabarrel/linux-kernel-module-rust/pull/113
-        let read = T::read(&f, unsafe { &FileRef::from_ptr(file) }, &mut data, unsafe { *offset }.try_into()?)?;
-        unsafe { (*offset) += bindings::loff_t::try_from(read).unwrap() };
+        let mut pos = unsafe { *offset }.try_into()?;
+        let read = T::read_with_pos(&f, unsafe { &FileRef::from_ptr(file) }, &mut data, &mut pos)?;
+        unsafe { (*offset) = pos.try_into()? };
         Ok(read as _)
     }
 }
@@ -125,12 +147,40 @@ unsafe extern "C" fn read_iter_callback<T: FileOperations>(
         // callback, which the C API guarantees that will be called only when all references to
         // `file` have been released, so we know it can't be called while this function is running.
         let f = unsafe { T::Wrapper::borrow((*file).private_data) };
-        let read = T::read(&f, unsafe { &FileRef::from_ptr(file) }, &mut iter, offset.try_into()?)?;
-        unsafe { (*iocb).ki_pos += bindings::loff_t::try_from(read).unwrap() };
+        let mut pos = offset.try_into()?;
+        let read = T::read_with_pos(&f, unsafe { &FileRef::from_ptr(file) }, &mut iter, &mut pos)?;
+        unsafe { (*iocb).ki_pos = pos.try_into()? };
         Ok(read as _)
     }
 }
 
+/// Returns the position `len` bytes after `offset`.
+///
+/// Fails with `EINVAL` if it does not fit in a `loff_t`, since there is no `FMODE_UNSIGNED_OFFSET`
+/// support.
+fn advance(offset: u64, len: usize) -> Result<u64> {
+    let pos = offset.checked_add(len as u64).ok_or(Error::EINVAL)?;
+    bindings::loff_t::try_from(pos)?;
+    Ok(pos)
+}
+
+/// Returns the position at which a write requested at `offset` must take place.
+///
+/// This is `offset` itself, except for files opened with `O_APPEND` that implement
+/// [`FileOperations::seek`], which are written at the position returned by seeking to
+/// [`SeekFrom::End`].
+fn write_pos<T: FileOperations>(
+    f: &<<T::Wrapper as PointerWrapper>::Borrowed as Deref>::Target,
+    file: &File,
+    offset: u64,
+) -> Result<u64> {
+    if T::TO_USE.seek && file.is_append() {
+        T::seek(f, file, SeekFrom::End(0))
+    } else {
+        Ok(offset)
+    }
+}
+
 unsafe extern "C" fn write_callback<T: FileOperations>(
     file: *mut bindings::file,
     buf: *const c_types::c_char,
@@ -144,10 +194,12 @@ unsafe extern "C" fn write_callback<T: FileOperations>(
         // callback, which the C API guarantees that will be called only when all references to
         // `file` have been released, so we know it can't be called while this function is running.
         let f = unsafe { T::Wrapper::borrow((*file).private_data) };
+        let file = unsafe { &FileRef::from_ptr(file) };
         // No `FMODE_UNSIGNED_OFFSET` support, so `offset` must be in [0, 2^63).
         // See discussion in https://github.com/fishinabarrel/linux-kernel-module-rust/pull/113
-        let written = T::write(&f, unsafe { &FileRef::from_ptr(file) }, &mut data, unsafe { *offset }.try_into()?)?;
-        unsafe { (*offset) += bindings::loff_t::try_from(written).unwrap() };
+        let mut pos = write_pos::<T>(&f, file, unsafe { *offset }.try_into()?)?;
+        let written = T::write_with_pos(&f, file, &mut data, &mut pos)?;
+        unsafe { (*offset) = pos.try_into()? };
         Ok(written as _)
     }
 }
@@ -165,8 +217,10 @@ unsafe extern "C" fn write_iter_callback<T: FileOperations>(
         // callback, which the C API guarantees that will be called only when all references to
         // `file` have been released, so we know it can't be called while this function is running.
         let f = unsafe { T::Wrapper::borrow((*file).private_data) };
-        let written = T::write(&f, unsafe { &FileRef::from_ptr(file) }, &mut iter, offset.try_into()?)?;
-        unsafe { (*iocb).ki_pos += bindings::loff_t::try_from(written).unwrap() };
+        let file = unsafe { &FileRef::from_ptr(file) };
+        let mut pos = write_pos::<T>(&f, file, offset.try_into()?)?;
+        let written = T::write_with_pos(&f, file, &mut iter, &mut pos)?;
+        unsafe { (*iocb).ki_pos = pos.try_into()? };
         Ok(written as _)
     }
 }
@@ -175,7 +229,13 @@ unsafe extern "C" fn release_callback<T: FileOperations>(
     _inode: *mut bindings::inode,
     file: *mut bindings::file,
 ) -> c_types::c_int {
+    // SAFETY: `file` is valid for the duration of this call. The kernel calls `release` once, when
+    // the last reference to the file is dropped, so no other callback is running or can start
+    // running on this file. The pointer is cleared so that it does not dangle once freed.
     let ptr = mem::replace(unsafe { &mut (*file).private_data }, ptr::null_mut());
+    // SAFETY: `ptr` was returned by `T::Wrapper::into_pointer` in `open_callback`, which succeeded
+    // since `release` is only called for successfully opened files, and this is the only place
+    // where it is converted back.
     T::release(unsafe { T::Wrapper::from_pointer(ptr as _) }, unsafe {
         &FileRef::from_ptr(file)
     });
@@ -199,8 +259,12 @@ unsafe extern "C" fn llseek_callback<T: FileOperations>(
         // callback, which the C API guarantees that will be called only when all references to
         // `file` have been released, so we know it can't be called while this function is running.
         let f = unsafe { T::Wrapper::borrow((*file).private_data) };
-        let off = T::seek(&f, unsafe { &FileRef::from_ptr(file) }, off)?;
-        Ok(off as bindings::loff_t)
+        let off = T::seek(&f, unsafe { &FileRef::from_ptr(file) }, off)?.try_into()?;
+        // The VFS only returns the new position to userspace, it is up to `llseek` to store it.
+        // SAFETY: `file` is valid for the duration of this call. Concurrent updates of `f_pos` are
+        // serialised by the VFS with `f_pos_lock` for files that can seek.
+        unsafe { (*file).f_pos = off };
+        Ok(off)
     }
 }
 
@@ -248,6 +312,11 @@ unsafe extern "C" fn mmap_callback<T: FileOperations>(
         // callback, which the C API guarantees that will be called only when all references to
         // `file` have been released, so we know it can't be called while this function is running.
         let f = unsafe { T::Wrapper::borrow((*file).private_data) };
+        if T::TO_USE.fault {
+            // SAFETY: `vma` is valid for the duration of this call, and maps `file`, whose
+            // operations were built for `T`.
+            unsafe { (*vma).vm_ops = VmOperationsVtable::<T>::build() };
+        }
         T::mmap(&f, unsafe { &FileRef::from_ptr(file) }, unsafe { &mut *vma })?;
         Ok(0)
     }
@@ -290,6 +359,116 @@ unsafe extern "C" fn poll_callback<T: FileOperations>(
     }
 }
 
+type CompatIoctlFn = unsafe extern "C" fn(
+    *mut bindings::file,
+    c_types::c_uint,
+    c_types::c_ulong,
+) -> c_types::c_long;
+
+/// Used as the `compat_ioctl` callback of files that implement [`FileOperations::ioctl`] but not
+/// [`FileOperations::compat_ioctl`]: `compat_ptr_ioctl` converts the argument with `compat_ptr`
+/// and forwards the call to `unlocked_ioctl`.
+#[cfg(CONFIG_COMPAT)]
+const COMPAT_PTR_IOCTL: Option<CompatIoctlFn> = Some(bindings::compat_ptr_ioctl);
+
+#[cfg(not(CONFIG_COMPAT))]
+const COMPAT_PTR_IOCTL: Option<CompatIoctlFn> = None;
+
+/// Wraps the kernel's `struct vm_fault`.
+///
+/// # Invariants
+///
+/// The pointer `VmFault::ptr` is non-null and valid.
+pub struct VmFault {
+    ptr: *mut bindings::vm_fault,
+}
+
+impl VmFault {
+    /// Constructs a new `struct vm_fault` wrapper.
+    ///
+    /// # Safety
+    ///
+    /// The pointer `ptr` must be non-null and valid for the lifetime of the object.
+    unsafe fn from_ptr(ptr: *mut bindings::vm_fault) -> Self {
+        // INVARIANTS: The safety requirements of this function ensure that the invariants hold.
+        Self { ptr }
+    }
+
+    /// Returns the faulting user virtual address, rounded down to a page boundary.
+    pub fn address(&self) -> usize {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        unsafe { (*self.ptr).address as _ }
+    }
+
+    /// Returns the offset in the file of the faulting page, in pages.
+    ///
+    /// This includes the offset passed to `mmap`, so it can be used directly to index a buffer
+    /// that backs the whole file.
+    pub fn pgoff(&self) -> usize {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        unsafe { (*self.ptr).pgoff as _ }
+    }
+}
+
+unsafe extern "C" fn fault_callback<T: FileOperations>(
+    vmf: *mut bindings::vm_fault,
+) -> bindings::vm_fault_t {
+    // SAFETY: `vmf` is valid for the duration of this call, and so is the area it refers to.
+    let file = unsafe { (*(*vmf).vma).vm_file };
+    // SAFETY: `private_data` was initialised by `open_callback` with a value returned by
+    // `T::Wrapper::into_pointer`. `T::Wrapper::from_pointer` is only called by the `release`
+    // callback, which cannot run while this function is running because the area holds a
+    // reference to `file`.
+    let f = unsafe { T::Wrapper::borrow((*file).private_data) };
+    match T::fault(&f, unsafe { &VmFault::from_ptr(vmf) }) {
+        Ok(page) => {
+            // SAFETY: `vmf` is valid. The reference owned by `page` is handed over to the memory
+            // management code, which releases it when the page is unmapped (or right away if it
+            // fails to map it).
+            unsafe { (*vmf).page = page.into_raw() };
+            0
+        }
+        Err(e) if e == Error::ENOMEM => bindings::vm_fault_reason_VM_FAULT_OOM as _,
+        Err(_) => bindings::vm_fault_reason_VM_FAULT_SIGBUS as _,
+    }
+}
+
+struct VmOperationsVtable<T>(marker::PhantomData<T>);
+
+impl<T: FileOperations> VmOperationsVtable<T> {
+    const VTABLE: bindings::vm_operations_struct = bindings::vm_operations_struct {
+        open: None,
+        close: None,
+        may_split: None,
+        mremap: None,
+        fault: if T::TO_USE.fault {
+            Some(fault_callback::<T>)
+        } else {
+            None
+        },
+        huge_fault: None,
+        map_pages: None,
+        pagesize: None,
+        page_mkwrite: None,
+        pfn_mkwrite: None,
+        access: None,
+        name: None,
+        #[cfg(CONFIG_NUMA)]
+        set_policy: None,
+        #[cfg(CONFIG_NUMA)]
+        get_policy: None,
+        find_special_page: None,
+    };
+
+    /// Builds an instance of [`struct vm_operations_struct`].
+    ///
+    /// The operations must only be installed in areas that map a file whose operations were
+    /// built by `FileOperationsVtable<_, T>`.
+    const fn build() -> &'static bindings::vm_operations_struct {
+        &Self::VTABLE
+    }
+}
+
 pub(crate) struct FileOperationsVtable<A, T>(marker::PhantomData<A>, marker::PhantomData<T>);
 
 impl<A: FileOpenAdapter, T: FileOpener<A::Arg>> FileOperationsVtable<A, T> {
@@ -301,12 +480,14 @@ impl<A: FileOpenAdapter, T: FileOpener<A::Arg>> FileOperationsVtable<A, T> {
         } else {
             None
         },
-        write: if T::TO_USE.write {
+        write: if T::TO_USE.write && !T::TO_USE.read_only {
             Some(write_callback::<T>)
         } else {
             None
         },
-        llseek: if T::TO_USE.seek {
+        llseek: if T::TO_USE.nonseekable {
+            Some(bindings::no_llseek)
+        } else if T::TO_USE.seek {
             Some(llseek_callback::<T>)
         } else {
             None
@@ -315,6 +496,8 @@ impl<A: FileOpenAdapter, T: FileOpener<A::Arg>> FileOperationsVtable<A, T> {
         check_flags: None,
         compat_ioctl: if T::TO_USE.compat_ioctl {
             Some(compat_ioctl_callback::<T>)
+        } else if T::TO_USE.ioctl {
+            COMPAT_PTR_IOCTL
         } else {
             None
         },
@@ -362,7 +545,7 @@ impl<A: FileOpenAdapter, T: FileOpener<A::Arg>> FileOperationsVtable<A, T> {
         } else {
             None
         },
-        write_iter: if T::TO_USE.write_iter {
+        write_iter: if T::TO_USE.write_iter && !T::TO_USE.read_only {
             Some(write_iter_callback::<T>)
         } else {
             None
@@ -400,6 +583,8 @@ pub struct ToUse {
     pub ioctl: bool,
 
     /// The `compat_ioctl` field of [`struct file_operations`].
+    ///
+    /// If `false` but [`ToUse::ioctl`] is `true`, the field is set to `compat_ptr_ioctl`.
     pub compat_ioctl: bool,
 
     /// The `fsync` field of [`struct file_operations`].
@@ -410,6 +595,28 @@ pub struct ToUse {
 
     /// The `poll` field of [`struct file_operations`].
     pub poll: bool,
+
+    /// The `fault` field of the [`struct vm_operations_struct`] installed in areas mapped by
+    /// [`FileOperations::mmap`].
+    ///
+    /// Only has an effect if [`ToUse::mmap`] is `true` too.
+    pub fault: bool,
+
+    /// Whether the file is a stream that does not support seeking, e.g. a device that produces
+    /// data as it goes.
+    ///
+    /// If `true`, `open` calls `nonseekable_open` so that `pread(2)` and `pwrite(2)` fail, and the
+    /// `llseek` field of [`struct file_operations`] is set to `no_llseek` so that `lseek(2)` fails,
+    /// all with `ESPIPE`. This takes precedence over [`ToUse::seek`].
+    pub nonseekable: bool,
+
+    /// Whether the file is read-only.
+    ///
+    /// If `true`, the `write` and `write_iter` fields of [`struct file_operations`] are left null
+    /// even if [`ToUse::write`] or [`ToUse::write_iter`] are `true`, so that `write(2)` and
+    /// related calls fail with `EINVAL` without reaching the driver, whatever mode the file was
+    /// opened with. Drivers that only need to check the mode can use [`File::is_writable`] instead.
+    pub read_only: bool,
 }
 
 /// A constant version where all values are to set to `false`, that is, all supported fields will
@@ -425,16 +632,24 @@ pub const USE_NONE: ToUse = ToUse {
     fsync: false,
     mmap: false,
     poll: false,
+    fault: false,
+    nonseekable: false,
+    read_only: false,
 };
 
 /// Defines the [`FileOperations::TO_USE`] field based on a list of fields to be populated.
+///
+/// Besides the names of callbacks, the list may contain the flags of [`ToUse`] that change how
+/// the file behaves, e.g. `declare_file_operations!(read, nonseekable)`. A flag overrides the
+/// callbacks it conflicts with, e.g. `declare_file_operations!(read, write, read_only)` declares a
+/// file that cannot be written to, whatever [`FileOperations::write`] does.
 #[macro_export]
 macro_rules! declare_file_operations {
     () => {
         const TO_USE: $crate::file_operations::ToUse = $crate::file_operations::USE_NONE;
     };
     ($($i:ident),+) => {
-        const TO_USE: kernel::file_operations::ToUse =
+        const TO_USE: $crate::file_operations::ToUse =
             $crate::file_operations::ToUse {
                 $($i: true),+ ,
                 ..$crate::file_operations::USE_NONE
@@ -535,6 +750,72 @@ impl IoctlCommand {
         }
     }
 
+    /// Returns a pointer to the value of type `T` that the argument of the command points to.
+    ///
+    /// This suits commands whose argument is a fixed-size structure, e.g. read with
+    /// [`UserPtr::read`] and then written back with [`UserPtr::write`] for a command that both
+    /// takes and returns a value. It can only be called once, since the argument is only accessed
+    /// once, and fails with `EINVAL` if the size encoded in the command is not that of `T`, or if
+    /// the argument was already taken, e.g. by [`IoctlCommand::dispatch`].
+    pub fn user_ptr<T>(&mut self) -> Result<UserPtr<T>> {
+        let size = (self.cmd >> bindings::_IOC_SIZESHIFT) & bindings::_IOC_SIZEMASK;
+        if size as usize != mem::size_of::<T>() {
+            return Err(Error::EINVAL);
+        }
+        self.user_slice.take().ok_or(Error::EINVAL)?;
+        // SAFETY: The user slice of the argument was taken above, so this is the only instance
+        // accessing the argument, and TOCTOU issues are not possible.
+        Ok(unsafe { UserPtr::new(self.arg as _) })
+    }
+
+    /// Handles the file-control ioctls `FIONBIO` and `FIOASYNC`, which set or clear the
+    /// `O_NONBLOCK` and `FASYNC` flags of the file, like the VFS does.
+    ///
+    /// Returns [`None`] if the command is neither of them, in which case the driver handles it.
+    /// When issued by userspace, these commands are handled by the VFS and never reach
+    /// [`FileOperations::ioctl`]; they do when in-kernel callers issue them with `vfs_ioctl`,
+    /// which calls the driver directly, so drivers that support this call this first. Since files
+    /// implemented in Rust do not support `fasync`, enabling `FASYNC` fails with `ENOTTY`.
+    pub fn handle_common_fcntl_ioctl(&mut self, file: &File) -> Option<Result<i32>> {
+        let flag = match self.cmd {
+            bindings::FIONBIO => bindings::O_NONBLOCK,
+            bindings::FIOASYNC => bindings::FASYNC,
+            _ => return None,
+        };
+        Some(self.set_file_flag(file, flag))
+    }
+
+    /// Sets or clears `flag` in the flags of `file`, depending on the `int` the argument of the
+    /// command points to.
+    fn set_file_flag(&mut self, file: &File, flag: u32) -> Result<i32> {
+        // The argument of these commands is not encoded in the command, so `self.user_slice` is
+        // empty; it is taken so that only one user slice is ever created for the argument.
+        self.user_slice.take().ok_or(Error::EINVAL)?;
+        // SAFETY: We only create one instance of the user slice per ioctl call, as ensured above,
+        // so TOCTOU issues are not possible.
+        let data = unsafe { UserSlicePtr::new(self.arg as _, mem::size_of::<i32>()) };
+        let on = data.reader().read::<i32>()? != 0;
+
+        // SAFETY: `file.ptr` is valid by the type invariants of `File`.
+        let flags = unsafe { (*file.ptr).f_flags };
+        if flag == bindings::FASYNC && on != (flags & flag != 0) {
+            // Like the VFS does for files without an `fasync` operation.
+            return Err(Error::ENOTTY);
+        }
+        // SAFETY: `file.ptr` is valid, and `f_flags` is protected by `f_lock`.
+        unsafe {
+            let lock = &mut (*file.ptr).f_lock;
+            rust_helper_spin_lock(lock);
+            if on {
+                (*file.ptr).f_flags |= flag;
+            } else {
+                (*file.ptr).f_flags &= !flag;
+            }
+            rust_helper_spin_unlock(lock);
+        }
+        Ok(0)
+    }
+
     /// Returns the raw 32-bit value of the command and the ptr-sized argument.
     pub fn raw(&self) -> (u32, usize) {
         (self.cmd, self.arg)
@@ -570,6 +851,9 @@ pub trait FileOpenAdapter {
 pub trait FileOpener<T: ?Sized>: FileOperations {
     /// Creates a new instance of this file.
     ///
+    /// On failure, the error is returned verbatim by `open(2)`, e.g. `ENODEV` if the device is
+    /// gone, and no other callback is called for the file.
+    ///
     /// Corresponds to the `open` function pointer in `struct file_operations`.
     fn open(context: &T) -> Result<Self::Wrapper>;
 }
@@ -587,6 +871,35 @@ impl<T: FileOperations<Wrapper = Box<T>> + Default> FileOpener<()> for T {
 /// File descriptors may be used from multiple threads/processes concurrently, so your type must be
 /// [`Sync`]. It must also be [`Send`] because [`FileOperations::release`] will be called from the
 /// thread that decrements that associated file's refcount to zero.
+///
+/// # Lifecycle
+///
+/// [`FileOpener::open`] is called for every `open(2)` of the device and returns the object that
+/// represents the open file. It is stored in [`struct file::private_data`] only if `open`
+/// succeeds; on failure, no other callback is called for the file. The object is then borrowed by
+/// all other callbacks, and only moved out to call [`FileOperations::release`], exactly once,
+/// after the last reference to the file is dropped. For example, a file that is shared by several
+/// processes, duplicated with `dup(2)` or mapped into memory is only released after it has been
+/// closed and unmapped everywhere, and concurrent last `close(2)`s on it result in a single call
+/// to `release`.
+///
+/// # Concurrency
+///
+/// The callbacks installed in [`struct file_operations`] do not take any lock before calling into
+/// the implementation: neither the big kernel lock nor a per-file or per-device lock. They only
+/// borrow the object stored in [`struct file::private_data`], so, for example, several tasks
+/// calling `read(2)` on the same file (or on different files of the same device) run
+/// [`FileOperations::read`] in parallel. Implementations that need mutual exclusion must provide
+/// it themselves, e.g. by wrapping their mutable state in a [`crate::sync::Mutex`].
+///
+/// # Device removal
+///
+/// Open files may outlive the device they were opened for: unregistering the device does not
+/// close them, and their callbacks may still be called after the driver is unbound from the
+/// device, once its devm-managed resources are freed. Resources shared with open files are thus
+/// wrapped in a [`crate::revocable::Revocable`] by [`crate::device::devm_revocable`], which
+/// revokes it when the driver is unbound, and callbacks return `ENODEV` when they fail to access
+/// them.
 pub trait FileOperations: Send + Sync + Sized {
     /// The methods to use to populate [`struct file_operations`].
     const TO_USE: ToUse;
@@ -604,6 +917,14 @@ pub trait FileOperations: Send + Sync + Sized {
 
     /// Reads data from this file to the caller's buffer.
     ///
+    /// May be called concurrently from multiple tasks, see the [`FileOperations`] documentation.
+    ///
+    /// `offset` is the position to read from: the current file position for `read(2)`, or the
+    /// position given by userspace for `pread(2)`. The implementation must not assume that reads
+    /// are sequential. After `n` bytes are returned, the file position is advanced by `n` for
+    /// `read(2)`, and left unchanged for `pread(2)`; [`FileOperations::read_with_pos`] allows
+    /// setting it explicitly instead.
+    ///
     /// Corresponds to the `read` and `read_iter` function pointers in `struct file_operations`.
     fn read<T: IoBufferWriter>(
         _this: &<<Self::Wrapper as PointerWrapper>::Borrowed as Deref>::Target,
@@ -614,8 +935,32 @@ pub trait FileOperations: Send + Sync + Sized {
         Err(Error::EINVAL)
     }
 
+    /// Reads data from this file to the caller's buffer, and updates the position.
+    ///
+    /// `offset` is the position to read from, as for [`FileOperations::read`], and is updated to
+    /// the new position when this returns successfully. The default implementation calls
+    /// [`FileOperations::read`] and advances `offset` by the number of bytes read, so that
+    /// consecutive calls to `read(2)` see increasing offsets. Implementations whose position is
+    /// not a byte count, e.g. devices that return whole records but count positions in records,
+    /// or that skip data, override this instead, and still declare `read`.
+    fn read_with_pos<T: IoBufferWriter>(
+        this: &<<Self::Wrapper as PointerWrapper>::Borrowed as Deref>::Target,
+        file: &File,
+        data: &mut T,
+        offset: &mut u64,
+    ) -> Result<usize> {
+        let read = Self::read(this, file, data, *offset)?;
+        *offset = advance(*offset, read)?;
+        Ok(read)
+    }
+
     /// Writes data from the caller's buffer to this file.
     ///
+    /// `offset` is the position to write at, with the same semantics as for
+    /// [`FileOperations::read`]. If the file was opened with `O_APPEND` and the implementation
+    /// declares `seek`, `offset` is instead the position returned by seeking to
+    /// [`SeekFrom::End`], and the file position is updated to the end of the written data.
+    ///
     /// Corresponds to the `write` and `write_iter` function pointers in `struct file_operations`.
     fn write<T: IoBufferReader>(
         _this: &<<Self::Wrapper as PointerWrapper>::Borrowed as Deref>::Target,
@@ -626,8 +971,26 @@ pub trait FileOperations: Send + Sync + Sized {
         Err(Error::EINVAL)
     }
 
+    /// Writes data from the caller's buffer to this file, and updates the position.
+    ///
+    /// This is to [`FileOperations::write`] what [`FileOperations::read_with_pos`] is to
+    /// [`FileOperations::read`].
+    fn write_with_pos<T: IoBufferReader>(
+        this: &<<Self::Wrapper as PointerWrapper>::Borrowed as Deref>::Target,
+        file: &File,
+        data: &mut T,
+        offset: &mut u64,
+    ) -> Result<usize> {
+        let written = Self::write(this, file, data, *offset)?;
+        *offset = advance(*offset, written)?;
+        Ok(written)
+    }
+
     /// Changes the position of the file.
     ///
+    /// The new position, which is returned, becomes the file position used by subsequent calls to
+    /// `read(2)` and `write(2)`.
+    ///
     /// Corresponds to the `llseek` function pointer in `struct file_operations`.
     fn seek(
         _this: &<<Self::Wrapper as PointerWrapper>::Borrowed as Deref>::Target,
@@ -639,6 +1002,10 @@ pub trait FileOperations: Send + Sync + Sized {
 
     /// Performs IO control operations that are specific to the file.
     ///
+    /// Commands common to all files, e.g. `FIONBIO`, `FIOASYNC` and `FIOCLEX`, are handled by the
+    /// VFS when issued by userspace and do not reach the driver. See
+    /// [`IoctlCommand::handle_common_fcntl_ioctl`] for those issued from within the kernel.
+    ///
     /// Corresponds to the `unlocked_ioctl` function pointer in `struct file_operations`.
     fn ioctl(
         _this: &<<Self::Wrapper as PointerWrapper>::Borrowed as Deref>::Target,
@@ -650,6 +1017,11 @@ pub trait FileOperations: Send + Sync + Sized {
 
     /// Performs 32-bit IO control operations on that are specific to the file on 64-bit kernels.
     ///
+    /// Only needs to be implemented (and declared) if the argument layout of some commands
+    /// differs between 32-bit and 64-bit userspace. Files that only declare `ioctl` get the
+    /// kernel's `compat_ptr_ioctl` instead, which converts the argument with `compat_ptr` and
+    /// calls [`FileOperations::ioctl`].
+    ///
     /// Corresponds to the `compat_ioctl` function pointer in `struct file_operations`.
     fn compat_ioctl(
         _this: &<<Self::Wrapper as PointerWrapper>::Borrowed as Deref>::Target,
@@ -684,6 +1056,26 @@ pub trait FileOperations: Send + Sync + Sized {
         Err(Error::EINVAL)
     }
 
+    /// Provides the page that backs a faulting address in an area mapped by
+    /// [`FileOperations::mmap`].
+    ///
+    /// Called the first time each page of the area is accessed, which allows large or sparse
+    /// mappings to be populated on demand. The reference owned by the returned [`Pages`] is handed
+    /// over to the page tables, which release it when the page is unmapped; to keep the page
+    /// around as well, e.g. in a buffer shared by all mappings of the file, return a clone of it.
+    /// On failure, the faulting process gets a `SIGBUS`, or the OOM killer is invoked if the error
+    /// is `ENOMEM`.
+    ///
+    /// Corresponds to the `fault` function pointer in `struct vm_operations_struct`. The operations
+    /// are installed in the area right before [`FileOperations::mmap`] is called, so `mmap` must
+    /// be declared and implemented as well.
+    fn fault(
+        _this: &<<Self::Wrapper as PointerWrapper>::Borrowed as Deref>::Target,
+        _vmf: &VmFault,
+    ) -> Result<Pages<0>> {
+        Err(Error::EINVAL)
+    }
+
     /// Checks the state of the file and optionally registers for notification when the state
     /// changes.
     ///
@@ -696,3 +1088,285 @@ pub trait FileOperations: Send + Sync + Sized {
         Ok(bindings::POLLIN | bindings::POLLOUT | bindings::POLLRDNORM | bindings::POLLWRNORM)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use alloc::vec::Vec;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    /// A buffer of `cap` bytes that collects what is written to it.
+    struct VecWriter {
+        buf: Vec<u8>,
+        cap: usize,
+    }
+
+    impl VecWriter {
+        fn new(cap: usize) -> Self {
+            Self {
+                buf: Vec::new(),
+                cap,
+            }
+        }
+    }
+
+    impl IoBufferWriter for VecWriter {
+        fn len(&self) -> usize {
+            self.cap - self.buf.len()
+        }
+
+        fn clear(&mut self, len: usize) -> Result {
+            self.write_slice(&alloc::vec![0; len])
+        }
+
+        unsafe fn write_raw(&mut self, data: *const u8, len: usize) -> Result {
+            if len > self.len() {
+                return Err(Error::EFAULT);
+            }
+            // SAFETY: The caller guarantees that `data` is valid for `len` bytes.
+            self.buf
+                .extend_from_slice(unsafe { core::slice::from_raw_parts(data, len) });
+            Ok(())
+        }
+    }
+
+    /// A device that returns the bytes `0, 1, 2, ...` at the corresponding offsets.
+    struct Counter;
+
+    impl FileOperations for Counter {
+        const TO_USE: ToUse = ToUse {
+            read: true,
+            ..USE_NONE
+        };
+
+        fn read<T: IoBufferWriter>(
+            _this: &Self,
+            _file: &File,
+            data: &mut T,
+            offset: u64,
+        ) -> Result<usize> {
+            let len = data.len().min(3);
+            for i in 0..len {
+                data.write(&((offset as usize + i) as u8))?;
+            }
+            Ok(len)
+        }
+    }
+
+    /// A device that returns 4-byte records, and whose position counts records rather than bytes.
+    struct Records;
+
+    impl FileOperations for Records {
+        const TO_USE: ToUse = ToUse {
+            read: true,
+            ..USE_NONE
+        };
+
+        fn read_with_pos<T: IoBufferWriter>(
+            _this: &Self,
+            _file: &File,
+            data: &mut T,
+            offset: &mut u64,
+        ) -> Result<usize> {
+            data.write(&(*offset as u32))?;
+            *offset += 1;
+            Ok(4)
+        }
+    }
+
+    fn raw_file() -> bindings::file {
+        // SAFETY: `struct file` is a plain C struct, for which all zeroes is a valid value.
+        unsafe { core::mem::zeroed() }
+    }
+
+    #[test]
+    fn test_sequential_reads_advance_position() {
+        let mut raw = raw_file();
+        // SAFETY: `raw` is a valid file, which outlives `file`.
+        let file = unsafe { FileRef::from_ptr(&mut raw) };
+        let mut pos = 0;
+        let mut out = VecWriter::new(8);
+        for &expected in &[3, 6, 8] {
+            let mut data = VecWriter::new(out.len());
+            let read = Counter::read_with_pos(&Counter, &file, &mut data, &mut pos).unwrap();
+            out.write_slice(&data.buf).unwrap();
+            assert_eq!(read, data.buf.len());
+            assert_eq!(pos, expected);
+        }
+        assert_eq!(out.buf, [0, 1, 2, 3, 4, 5, 6, 7]);
+
+        // Nothing left to read: the position no longer moves.
+        let read = Counter::read_with_pos(&Counter, &file, &mut out, &mut pos).unwrap();
+        assert_eq!((read, pos), (0, 8));
+    }
+
+    #[test]
+    fn test_read_with_pos_sets_position() {
+        let mut raw = raw_file();
+        // SAFETY: `raw` is a valid file, which outlives `file`.
+        let file = unsafe { FileRef::from_ptr(&mut raw) };
+        let mut pos = 0;
+        let mut data = VecWriter::new(8);
+        Records::read_with_pos(&Records, &file, &mut data, &mut pos).unwrap();
+        Records::read_with_pos(&Records, &file, &mut data, &mut pos).unwrap();
+        assert_eq!(pos, 2);
+        assert_eq!(data.buf, [0, 0, 0, 0, 1, 0, 0, 0]);
+    }
+
+    /// An adapter whose argument is the errno that `open` fails with, or `0` to succeed.
+    struct ErrnoAdapter<const ERRNO: u32>;
+
+    impl<const ERRNO: u32> FileOpenAdapter for ErrnoAdapter<ERRNO> {
+        type Arg = u32;
+
+        unsafe fn convert(_inode: *mut bindings::inode, _file: *mut bindings::file) -> *const u32 {
+            &ERRNO
+        }
+    }
+
+    /// A device whose `open` fails with the errno it is given, if not `0`.
+    struct Opener;
+
+    impl FileOperations for Opener {
+        const TO_USE: ToUse = USE_NONE;
+    }
+
+    impl FileOpener<u32> for Opener {
+        fn open(errno: &u32) -> Result<Box<Self>> {
+            if *errno != 0 {
+                return Err(Error::from_kernel_errno(-(*errno as i32)));
+            }
+            Ok(Box::try_new(Opener)?)
+        }
+    }
+
+    #[test]
+    fn test_open_error_is_returned_verbatim() {
+        let mut file = raw_file();
+        // SAFETY: The adapter does not use the inode, and `Opener` does not ask for
+        // `nonseekable_open`, so only `file` is accessed.
+        let ret = unsafe {
+            open_callback::<ErrnoAdapter<{ bindings::ENODEV }>, Opener>(ptr::null_mut(), &mut file)
+        };
+        assert_eq!(ret, -(bindings::ENODEV as i32));
+        // No state is left behind for `release` to free.
+        assert!(file.private_data.is_null());
+    }
+
+    #[test]
+    fn test_open_success_sets_private_data() {
+        let mut file = raw_file();
+        // SAFETY: As above.
+        let ret = unsafe { open_callback::<ErrnoAdapter<0>, Opener>(ptr::null_mut(), &mut file) };
+        assert_eq!(ret, 0);
+        assert!(!file.private_data.is_null());
+        // SAFETY: `private_data` was set from `Box::<Opener>::into_pointer`, and is freed once.
+        drop(unsafe { Box::<Opener>::from_pointer(file.private_data) });
+    }
+
+    /// A device that declares several operations at once.
+    struct ReadPoll;
+
+    impl FileOperations for ReadPoll {
+        crate::declare_file_operations!(read, poll);
+    }
+
+    impl FileOpener<u32> for ReadPoll {
+        fn open(_: &u32) -> Result<Box<Self>> {
+            Ok(Box::try_new(ReadPoll)?)
+        }
+    }
+
+    #[test]
+    fn test_declared_operations_are_all_installed() {
+        // SAFETY: The vtable is only inspected, never used to open a file.
+        let fops = unsafe { FileOperationsVtable::<ErrnoAdapter<0>, ReadPoll>::build() };
+        assert!(fops.read.is_some());
+        assert!(fops.poll.is_some());
+        assert!(fops.write.is_none());
+        assert!(fops.unlocked_ioctl.is_none());
+    }
+
+    /// A device whose reads only complete once `READERS` of them are in progress at once.
+    #[derive(Default)]
+    struct Rendezvous {
+        inside: AtomicUsize,
+    }
+
+    impl Rendezvous {
+        const READERS: usize = 4;
+    }
+
+    impl FileOperations for Rendezvous {
+        const TO_USE: ToUse = ToUse {
+            read: true,
+            ..USE_NONE
+        };
+
+        fn read<T: IoBufferWriter>(
+            this: &Self,
+            _file: &File,
+            _data: &mut T,
+            _offset: u64,
+        ) -> Result<usize> {
+            this.inside.fetch_add(1, Ordering::SeqCst);
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while this.inside.load(Ordering::SeqCst) < Self::READERS {
+                if Instant::now() > deadline {
+                    return Err(Error::ETIMEDOUT);
+                }
+                thread::yield_now();
+            }
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_concurrent_reads_are_not_serialised() {
+        let mut raw = raw_file();
+        raw.private_data = Box::try_new(Rendezvous::default()).unwrap().into_pointer() as _;
+        // CAST: Raw pointers are not `Send`; the file outlives the readers, which are joined below.
+        let file = &mut raw as *mut bindings::file as usize;
+
+        let readers: Vec<_> = (0..Rendezvous::READERS)
+            .map(|_| {
+                thread::spawn(move || {
+                    let mut offset: bindings::loff_t = 0;
+                    // SAFETY: `file` is valid and its `private_data` was set from
+                    // `Box::<Rendezvous>::into_pointer`. The buffer is empty, so it is never
+                    // dereferenced.
+                    unsafe {
+                        read_callback::<Rendezvous>(
+                            file as *mut bindings::file,
+                            ptr::null_mut(),
+                            0,
+                            &mut offset,
+                        )
+                    }
+                })
+            })
+            .collect();
+        // Each reader only returns once all of them are inside `read` at the same time, and times
+        // out otherwise.
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 0);
+        }
+
+        // SAFETY: `private_data` was set from `Box::<Rendezvous>::into_pointer` above, and the
+        // readers are done with it.
+        drop(unsafe { Box::<Rendezvous>::from_pointer(raw.private_data) });
+    }
+
+    #[test]
+    fn test_advance_rejects_overflow() {
+        assert_eq!(advance(4, 4), Ok(8));
+        assert!(advance(i64::MAX as u64, 1).is_err());
+        assert!(advance(u64::MAX, 1).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! High-resolution timers.
+//!
+//! Unlike regular timers, whose expiry times are counted in jiffies, high-resolution timers have
+//! nanosecond precision (subject to the resolution of the underlying clock event device), which
+//! makes them suitable for precise periodic work, e.g. sampling audio or sensor data.
+//!
+//! C header: [`include/linux/hrtimer.h`](../../../../include/linux/hrtimer.h)
+
+use crate::{bindings, types::Opaque, Result};
+use alloc::boxed::Box;
+use core::{ops::Deref, pin::Pin};
+
+extern "C" {
+    #[allow(improper_ctypes)]
+    fn rust_helper_hrtimer_forward_now(
+        timer: *mut bindings::hrtimer,
+        interval: bindings::ktime_t,
+    ) -> u64;
+}
+
+/// What happens to a timer once its callback returns.
+///
+/// Equivalent to `enum hrtimer_restart`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HrTimerRestart {
+    /// The timer is not restarted (`HRTIMER_NORESTART`).
+    NoRestart,
+
+    /// The timer is restarted (`HRTIMER_RESTART`), at the expiry time set by the callback with
+    /// [`HrTimer::forward_now`].
+    Restart,
+}
+
+/// The callback of a high-resolution timer.
+pub trait HrTimerCallback: Sized + Sync {
+    /// Called when `timer` expires.
+    ///
+    /// On most kernels, this runs in hardirq context, with interrupts disabled: it must not sleep
+    /// (so no mutexes, no `GFP_KERNEL` allocations and no access to userspace memory), it should
+    /// be short, and any data it shares with process context must be protected by a lock that
+    /// disables interrupts. On `PREEMPT_RT` kernels, it runs in softirq context instead.
+    ///
+    /// To run periodically, it calls [`HrTimer::forward_now`] and returns
+    /// [`HrTimerRestart::Restart`].
+    fn run(timer: &HrTimer<Self>) -> HrTimerRestart;
+}
+
+/// A high-resolution timer, holding data of type `T`, whose callback is [`T::run`].
+///
+/// The timer uses the monotonic clock, and expiry times are relative to the time the timer is
+/// started. It dereferences to its data, which the callback can thus access.
+///
+/// [`T::run`]: HrTimerCallback::run
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::hrtimer::{HrTimer, HrTimerCallback, HrTimerRestart};
+/// # use core::sync::atomic::{AtomicU32, Ordering};
+/// const PERIOD_NS: i64 = 125_000;
+///
+/// struct Sampler {
+///     ticks: AtomicU32,
+/// }
+///
+/// impl HrTimerCallback for Sampler {
+///     fn run(timer: &HrTimer<Self>) -> HrTimerRestart {
+///         timer.ticks.fetch_add(1, Ordering::Relaxed);
+///         timer.forward_now(PERIOD_NS);
+///         HrTimerRestart::Restart
+///     }
+/// }
+///
+/// fn start_sampling() -> Result<Pin<Box<HrTimer<Sampler>>>> {
+///     let timer = HrTimer::try_new(Sampler {
+///         ticks: AtomicU32::new(0),
+///     })?;
+///     timer.start(PERIOD_NS);
+///     Ok(timer)
+/// }
+/// ```
+///
+/// # Invariants
+///
+/// `timer` is initialised, with `timer_callback::<T>` as its function.
+pub struct HrTimer<T: HrTimerCallback> {
+    timer: Opaque<bindings::hrtimer>,
+    data: T,
+}
+
+// SAFETY: The timer may be started, cancelled and dropped from any thread, and the callback, which
+// may run on any CPU, only gets a shared reference to the data, so the data must be `Send`.
+unsafe impl<T: HrTimerCallback + Send> Send for HrTimer<T> {}
+
+// SAFETY: All methods taking a shared reference are safe to call concurrently, since the C side
+// serialises accesses to the timer, and the data is `Sync` by the bounds of `HrTimerCallback`.
+unsafe impl<T: HrTimerCallback> Sync for HrTimer<T> {}
+
+impl<T: HrTimerCallback> HrTimer<T> {
+    /// Allocates and initialises a new timer holding `data`.
+    ///
+    /// The timer is not started: see [`HrTimer::start`].
+    pub fn try_new(data: T) -> Result<Pin<Box<Self>>> {
+        let timer = Pin::from(Box::try_new(Self {
+            timer: Opaque::uninit(),
+            data,
+        })?);
+        // SAFETY: `timer.timer` is pinned, so it does not move after being initialised here.
+        unsafe {
+            bindings::hrtimer_init(
+                timer.timer.get(),
+                bindings::CLOCK_MONOTONIC as _,
+                bindings::hrtimer_mode_HRTIMER_MODE_REL,
+            );
+            (*timer.timer.get()).function = Some(timer_callback::<T>);
+        }
+        // INVARIANTS: The timer was initialised above.
+        Ok(timer)
+    }
+
+    /// Starts the timer, so that it expires once `expires` nanoseconds have elapsed.
+    ///
+    /// If the timer is already active, it is restarted with the new expiry time.
+    pub fn start(&self, expires: i64) {
+        // SAFETY: `self.timer` is initialised by the type invariants.
+        unsafe {
+            bindings::hrtimer_start_range_ns(
+                self.timer.get(),
+                expires,
+                0,
+                bindings::hrtimer_mode_HRTIMER_MODE_REL,
+            )
+        };
+    }
+
+    /// Cancels the timer, waiting for its callback to complete if it is running.
+    ///
+    /// Returns whether the timer was active. Must not be called from the callback, since it would
+    /// wait forever for itself to complete; the callback returns [`HrTimerRestart::NoRestart`]
+    /// instead.
+    pub fn cancel(&self) -> bool {
+        // SAFETY: `self.timer` is initialised by the type invariants.
+        unsafe { bindings::hrtimer_cancel(self.timer.get()) != 0 }
+    }
+
+    /// Moves the expiry time forward by whole multiples of `interval` nanoseconds, so that it is
+    /// in the future.
+    ///
+    /// Returns the number of intervals that were skipped, which is greater than one if the timer
+    /// expired late and periods were missed. Only meant to be called from the callback, before it
+    /// returns [`HrTimerRestart::Restart`], which keeps periodic timers from drifting.
+    pub fn forward_now(&self, interval: i64) -> u64 {
+        // SAFETY: `self.timer` is initialised by the type invariants.
+        unsafe { rust_helper_hrtimer_forward_now(self.timer.get(), interval) }
+    }
+}
+
+impl<T: HrTimerCallback> Deref for HrTimer<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T: HrTimerCallback> Drop for HrTimer<T> {
+    fn drop(&mut self) {
+        // The callback must not run once the data is dropped.
+        self.cancel();
+    }
+}
+
+unsafe extern "C" fn timer_callback<T: HrTimerCallback>(
+    ptr: *mut bindings::hrtimer,
+) -> bindings::hrtimer_restart {
+    // CAST: `Opaque` is `repr(transparent)` over the C struct.
+    let timer = ptr as *const Opaque<bindings::hrtimer>;
+    // The callback is only installed by `HrTimer::<T>::try_new`, so `ptr` points to the `timer`
+    // field of an `HrTimer<T>`, which is alive since it cancels the timer when dropped.
+    let this = crate::container_of!(timer, HrTimer<T>, timer);
+    // SAFETY: See above.
+    let this = unsafe { &*this };
+    match T::run(this) {
+        HrTimerRestart::NoRestart => bindings::hrtimer_restart_HRTIMER_NORESTART,
+        HrTimerRestart::Restart => bindings::hrtimer_restart_HRTIMER_RESTART,
+    }
+}
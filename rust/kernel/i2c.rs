@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! I2C devices.
+//!
+//! C header: [`include/linux/i2c.h`](../../../../include/linux/i2c.h)
+
+use crate::{bindings, device::Device};
+
+/// An I2C device, i.e., a chip on an I2C bus, as bound to its driver.
+///
+/// # Invariants
+///
+/// The field `ptr` is non-null and valid for the lifetime of the object.
+pub struct I2cClient {
+    ptr: *mut bindings::i2c_client,
+}
+
+impl I2cClient {
+    /// Creates a new [`I2cClient`] from a raw pointer.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null and valid. It must remain valid for the lifetime of the returned
+    /// instance, e.g. because it is the client passed to the `probe` callback of an I2C driver and
+    /// the instance does not outlive the callback.
+    pub unsafe fn from_ptr(ptr: *mut bindings::i2c_client) -> Self {
+        // INVARIANTS: The safety requirements of the function ensure the lifetime invariant.
+        Self { ptr }
+    }
+
+    /// Returns the raw `struct i2c_client` pointer.
+    pub(crate) fn to_ptr(&self) -> *mut bindings::i2c_client {
+        self.ptr
+    }
+
+    /// Returns the address of the device on its bus.
+    pub fn addr(&self) -> u16 {
+        // SAFETY: By the type invariants, we know that `self.ptr` is non-null and valid.
+        unsafe { (*self.ptr).addr }
+    }
+}
+
+// SAFETY: The device returned by `raw_device` is the raw I2C device.
+unsafe impl Device for I2cClient {
+    fn raw_device(&self) -> *mut bindings::device {
+        // SAFETY: By the type invariants, we know that `self.ptr` is non-null and valid.
+        unsafe { &mut (*self.ptr).dev }
+    }
+}
@@ -0,0 +1,243 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Input devices.
+//!
+//! Drivers for keys, buttons, touchscreens and the like report the events they detect through an
+//! input device, which the input core forwards to userspace via `/dev/input/event*`.
+//!
+//! C header: [`include/linux/input.h`](../../../../include/linux/input.h)
+
+use crate::{
+    bindings, c_types,
+    device::{Device, DeviceRef},
+    error::{to_result, Error},
+    str::CStr,
+    Result,
+};
+use core::mem::{size_of, ManuallyDrop};
+
+/// Synchronisation events, which separate groups of events (`EV_SYN`).
+pub const EV_SYN: u32 = bindings::EV_SYN;
+
+/// Key and button events (`EV_KEY`).
+pub const EV_KEY: u32 = bindings::EV_KEY;
+
+/// Relative axis events, e.g. of a mouse (`EV_REL`).
+pub const EV_REL: u32 = bindings::EV_REL;
+
+/// Absolute axis events, e.g. of a touchscreen (`EV_ABS`).
+pub const EV_ABS: u32 = bindings::EV_ABS;
+
+const BITS_PER_LONG: usize = size_of::<c_types::c_ulong>() * 8;
+
+/// Sets bit `bit` of the kernel bitmap `bitmap`, which has `count` valid bits.
+fn set_bit(bitmap: &mut [c_types::c_ulong], count: u32, bit: u32) -> Result {
+    if bit >= count {
+        return Err(Error::EINVAL);
+    }
+    let bit = bit as usize;
+    bitmap[bit / BITS_PER_LONG] |= 1 << (bit % BITS_PER_LONG);
+    Ok(())
+}
+
+/// An input device that is not registered yet.
+///
+/// The capabilities of the device, i.e., the events it may report, are declared with
+/// [`InputDevice::set_evbit`] and the related methods, and can no longer be changed once the
+/// device is registered with [`InputDevice::register`].
+///
+/// The device is freed when it is dropped, unless it was registered, in which case it is owned by
+/// the [`Registration`] returned by [`InputDevice::register`].
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::{c_str, input, platdev::PlatformDevice};
+/// fn probe_button(pdev: &mut PlatformDevice) -> Result<input::Registration> {
+///     let mut idev = input::InputDevice::new(pdev)?;
+///     idev.set_name(c_str!("rust-button"));
+///     idev.set_keybit(bindings::KEY_POWER)?;
+///     idev.register()
+/// }
+///
+/// // From the interrupt handler.
+/// fn on_button(reg: &input::Registration, pressed: bool) {
+///     reg.report_key(bindings::KEY_POWER, pressed);
+///     reg.sync();
+/// }
+/// ```
+///
+/// # Invariants
+///
+/// The pointer `InputDevice::ptr` is non-null and valid, and points to an input device that is
+/// owned by the [`InputDevice`], has `dev` as its parent and is not registered yet.
+pub struct InputDevice {
+    dev: DeviceRef,
+    ptr: *mut bindings::input_dev,
+}
+
+// SAFETY: The input device is only accessed through exclusive references until it is registered,
+// so it can be set up from any thread.
+unsafe impl Send for InputDevice {}
+
+impl InputDevice {
+    /// Allocates a new input device, with `dev` as its parent.
+    pub fn new(dev: &dyn Device) -> Result<Self> {
+        // SAFETY: FFI call with no additional safety requirements.
+        let ptr = unsafe { bindings::input_allocate_device() };
+        if ptr.is_null() {
+            return Err(Error::ENOMEM);
+        }
+        let dev = DeviceRef::from_dev(dev);
+        // SAFETY: `ptr` was just allocated, so it is valid, and the parent is kept alive by the
+        // reference held in `dev`.
+        unsafe { (*ptr).dev.parent = dev.raw_device() };
+        // INVARIANTS: `ptr` is non-null and was just allocated, so it is valid and not registered,
+        // and its parent was set above.
+        Ok(Self { dev, ptr })
+    }
+
+    /// Sets the name of the device, as shown to userspace.
+    pub fn set_name(&mut self, name: &'static CStr) {
+        // SAFETY: `self.ptr` is valid by the type invariants, and `name` outlives the device.
+        unsafe { (*self.ptr).name = name.as_char_ptr() };
+    }
+
+    /// Declares that the device reports events of type `ev`, e.g. [`EV_KEY`].
+    ///
+    /// Returns `EINVAL` if `ev` is not a valid event type.
+    pub fn set_evbit(&mut self, ev: u32) -> Result {
+        // SAFETY: `self.ptr` is valid by the type invariants, and the device is not registered,
+        // so the input core does not access its capabilities concurrently.
+        let evbit = unsafe { &mut (*self.ptr).evbit };
+        set_bit(evbit, bindings::EV_CNT, ev)
+    }
+
+    /// Declares that the device reports the key or button `code`, e.g. `KEY_POWER`.
+    ///
+    /// This also declares that the device reports [`EV_KEY`] events. Returns `EINVAL` if `code`
+    /// is not a valid key code.
+    pub fn set_keybit(&mut self, code: u32) -> Result {
+        // SAFETY: `self.ptr` is valid by the type invariants, and the device is not registered,
+        // so the input core does not access its capabilities concurrently.
+        let keybit = unsafe { &mut (*self.ptr).keybit };
+        set_bit(keybit, bindings::KEY_CNT, code)?;
+        self.set_evbit(EV_KEY)
+    }
+
+    /// Declares that the device reports the absolute axis `axis`, e.g. `ABS_X`, with values
+    /// ranging from `min` to `max`.
+    ///
+    /// `fuzz` is the noise the input core filters out, and `flat` the size of the dead zone
+    /// around the center of the axis. This also declares that the device reports [`EV_ABS`]
+    /// events. Returns `EINVAL` if `axis` is not a valid axis.
+    pub fn set_abs_params(
+        &mut self,
+        axis: u32,
+        min: i32,
+        max: i32,
+        fuzz: i32,
+        flat: i32,
+    ) -> Result {
+        if axis >= bindings::ABS_CNT {
+            return Err(Error::EINVAL);
+        }
+        // SAFETY: `self.ptr` is valid by the type invariants, and `axis` is a valid axis.
+        unsafe { bindings::input_set_abs_params(self.ptr, axis, min, max, fuzz, flat) };
+        // `input_set_abs_params` returns silently if it fails to allocate the axis information.
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        if unsafe { (*self.ptr).absinfo }.is_null() {
+            return Err(Error::ENOMEM);
+        }
+        Ok(())
+    }
+
+    /// Registers the input device with the input core, which makes it visible to userspace.
+    ///
+    /// The device remains registered until the returned [`Registration`] is dropped. If the
+    /// registration fails, the device is freed.
+    pub fn register(self) -> Result<Registration> {
+        // SAFETY: `self.ptr` is valid and not registered by the type invariants.
+        to_result(unsafe { bindings::input_register_device(self.ptr) })?;
+        // The device is now owned by the registration, so it must not be freed on drop.
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is not used nor dropped afterwards, so `dev` is moved out of it only
+        // once.
+        let dev = unsafe { core::ptr::read(&this.dev) };
+        // INVARIANTS: The device was registered above, with `dev` as its parent.
+        Ok(Registration {
+            _dev: dev,
+            ptr: this.ptr,
+        })
+    }
+}
+
+impl Drop for InputDevice {
+    fn drop(&mut self) {
+        // SAFETY: `self.ptr` is owned and not registered by the type invariants, so it can be
+        // freed.
+        unsafe { bindings::input_free_device(self.ptr) };
+    }
+}
+
+/// A registered input device, through which events are reported.
+///
+/// Events are reported in groups, e.g. the state of all the keys that changed, each group being
+/// terminated by a call to [`Registration::sync`]. All the reporting methods may be called from
+/// any context, including interrupt handlers.
+///
+/// The device is unregistered, and then freed, when the [`Registration`] is dropped. It holds a
+/// reference to the parent device, so it remains valid even if it outlives the binding of the
+/// driver to the device, e.g. when it is shared with a timer or a workqueue.
+///
+/// # Invariants
+///
+/// The pointer `Registration::ptr` is non-null and valid, and points to an input device that is
+/// owned by the [`Registration`] and registered.
+pub struct Registration {
+    _dev: DeviceRef,
+    ptr: *mut bindings::input_dev,
+}
+
+// SAFETY: The input core serialises event reporting with the `event_lock` spinlock of the device,
+// so events can be reported from any thread.
+unsafe impl Send for Registration {}
+
+// SAFETY: The input core serialises event reporting with the `event_lock` spinlock of the device,
+// so events can be reported concurrently from several threads.
+unsafe impl Sync for Registration {}
+
+impl Registration {
+    /// Reports an event of type `ev` with the given `code` and `value`.
+    ///
+    /// Events of types or codes that were not declared before registration are ignored.
+    pub fn report(&self, ev: u32, code: u32, value: i32) {
+        // SAFETY: `self.ptr` is valid and registered by the type invariants.
+        unsafe { bindings::input_event(self.ptr, ev, code, value) };
+    }
+
+    /// Reports whether the key or button `code` is pressed.
+    pub fn report_key(&self, code: u32, pressed: bool) {
+        self.report(EV_KEY, code, pressed as i32);
+    }
+
+    /// Reports the new `value` of the absolute axis `axis`.
+    pub fn report_abs(&self, axis: u32, value: i32) {
+        self.report(EV_ABS, axis, value);
+    }
+
+    /// Terminates a group of events, which userspace then processes as a whole.
+    pub fn sync(&self) {
+        self.report(EV_SYN, bindings::SYN_REPORT, 0);
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        // SAFETY: `self.ptr` is owned and registered by the type invariants, and is only
+        // unregistered here. Unregistering drops the last reference to a device that is not
+        // device-managed, which frees it, so `input_free_device` must not be called.
+        unsafe { bindings::input_unregister_device(self.ptr) };
+    }
+}
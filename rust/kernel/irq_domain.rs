@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Interrupt domains.
+//!
+//! Allow drivers of interrupt controllers (e.g. GPIO controllers or PMICs that demultiplex a
+//! single parent interrupt) to map their hardware interrupt numbers to Linux interrupts.
+//!
+//! C header: [`include/linux/irqdomain.h`](../../../../include/linux/irqdomain.h)
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/core-api/irq/irq-domain.html>
+
+use crate::{
+    bindings, c_types,
+    device::Device,
+    error::{to_result, Error, Result},
+    from_kernel_result,
+};
+use alloc::boxed::Box;
+
+extern "C" {
+    #[allow(improper_ctypes)]
+    fn rust_helper_irq_domain_add_linear(
+        of_node: *mut bindings::device_node,
+        size: c_types::c_uint,
+        ops: *const bindings::irq_domain_ops,
+        host_data: *mut c_types::c_void,
+    ) -> *mut bindings::irq_domain;
+
+    #[allow(improper_ctypes)]
+    fn rust_helper_irq_create_mapping(
+        domain: *mut bindings::irq_domain,
+        hwirq: bindings::irq_hw_number_t,
+    ) -> c_types::c_uint;
+}
+
+/// Trait for implementers of interrupt domains.
+pub trait IrqDomainOps: Send + Sync {
+    /// Called when the Linux interrupt `virq` is associated with the hardware interrupt `hwirq`.
+    ///
+    /// Implementers typically set up the irq chip and flow handler of `virq` here.
+    ///
+    /// Corresponds to the `map` function pointer in `struct irq_domain_ops`.
+    fn map(&self, virq: u32, hwirq: usize) -> Result;
+
+    /// Called when the Linux interrupt `virq` is disposed of.
+    ///
+    /// Corresponds to the `unmap` function pointer in `struct irq_domain_ops`.
+    fn unmap(&self, _virq: u32) {}
+}
+
+unsafe extern "C" fn map_callback<T: IrqDomainOps>(
+    domain: *mut bindings::irq_domain,
+    virq: c_types::c_uint,
+    hwirq: bindings::irq_hw_number_t,
+) -> c_types::c_int {
+    from_kernel_result! {
+        // SAFETY: `host_data` was set to a valid `T` in `IrqDomain::new_linear`, which outlives
+        // the domain.
+        let data = unsafe { &*((*domain).host_data as *const T) };
+        data.map(virq, hwirq as _)?;
+        Ok(0)
+    }
+}
+
+unsafe extern "C" fn unmap_callback<T: IrqDomainOps>(
+    domain: *mut bindings::irq_domain,
+    virq: c_types::c_uint,
+) {
+    // SAFETY: `host_data` was set to a valid `T` in `IrqDomain::new_linear`, which outlives the
+    // domain.
+    let data = unsafe { &*((*domain).host_data as *const T) };
+    data.unmap(virq);
+}
+
+/// A linear interrupt domain.
+///
+/// The domain is removed when the [`IrqDomain`] is dropped, so it is usually stored in the driver
+/// data of the interrupt controller's device.
+///
+/// # Invariants
+///
+/// The pointer `IrqDomain::ptr` is non-null and valid. Its `host_data` points to `IrqDomain::data`
+/// and its `ops` to `IrqDomain::_ops`. The domain covers the hardware interrupts `0..size`.
+pub struct IrqDomain<T: IrqDomainOps> {
+    ptr: *mut bindings::irq_domain,
+    size: u32,
+    _ops: Box<bindings::irq_domain_ops>,
+    data: Box<T>,
+}
+
+impl<T: IrqDomainOps> IrqDomain<T> {
+    /// Creates a linear domain of `size` interrupts for the devicetree node of `dev`.
+    ///
+    /// Hardware interrupts are translated from one- or two-cell interrupt specifiers.
+    pub fn new_linear(dev: &dyn Device, size: u32, data: T) -> Result<Self> {
+        let ops = Box::try_new(bindings::irq_domain_ops {
+            map: Some(map_callback::<T>),
+            unmap: Some(unmap_callback::<T>),
+            xlate: Some(bindings::irq_domain_xlate_onetwocell),
+            ..Default::default()
+        })?;
+        let data = Box::try_new(data)?;
+
+        // SAFETY: `dev.raw_device()` is valid by the safety requirements of `Device`. `ops` and
+        // `data` are heap-allocated, so they do not move, and they are only freed after the
+        // domain is removed in `drop`.
+        let ptr = unsafe {
+            rust_helper_irq_domain_add_linear(
+                (*dev.raw_device()).of_node,
+                size,
+                &*ops,
+                &*data as *const T as *mut c_types::c_void,
+            )
+        };
+        if ptr.is_null() {
+            return Err(Error::ENOMEM);
+        }
+
+        // INVARIANTS: `ptr` is non-null, and `host_data`, `ops` and `size` were set above.
+        Ok(Self {
+            ptr,
+            size,
+            _ops: ops,
+            data,
+        })
+    }
+
+    /// Returns the data associated with the domain on creation.
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    /// Maps the hardware interrupt `hwirq`, returning the corresponding Linux interrupt.
+    pub fn create_mapping(&self, hwirq: usize) -> Result<u32> {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        let virq = unsafe { rust_helper_irq_create_mapping(self.ptr, hwirq as _) };
+        if virq == 0 {
+            return Err(Error::EINVAL);
+        }
+        Ok(virq)
+    }
+
+    /// Returns the Linux interrupt mapped to the hardware interrupt `hwirq`, if any.
+    pub fn find_mapping(&self, hwirq: usize) -> Option<u32> {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        let virq = unsafe { bindings::irq_find_mapping(self.ptr, hwirq as _) };
+        if virq == 0 {
+            None
+        } else {
+            Some(virq)
+        }
+    }
+
+    /// Runs the handler of the Linux interrupt mapped to the hardware interrupt `hwirq`.
+    ///
+    /// To be called from the (chained) handler of the parent interrupt when the controller
+    /// reports that `hwirq` is pending.
+    pub fn generic_handle(&self, hwirq: usize) -> Result {
+        let virq = self.find_mapping(hwirq).ok_or(Error::EINVAL)?;
+        // SAFETY: FFI call; `virq` is a valid interrupt number, it was just looked up.
+        to_result(unsafe { bindings::generic_handle_irq(virq) })
+    }
+}
+
+impl<T: IrqDomainOps> Drop for IrqDomain<T> {
+    fn drop(&mut self) {
+        // The domain must be empty before it is removed, so dispose of all the mappings created
+        // by `create_mapping`. This also calls `unmap` for each of them.
+        for hwirq in 0..self.size {
+            if let Some(virq) = self.find_mapping(hwirq as _) {
+                // SAFETY: FFI call; `virq` is a valid interrupt number, it was just looked up.
+                unsafe { bindings::irq_dispose_mapping(virq) };
+            }
+        }
+
+        // SAFETY: `self.ptr` is valid by the type invariants. `self._ops` and `self.data` are only
+        // freed after this call has removed the domain.
+        unsafe { bindings::irq_domain_remove(self.ptr) };
+    }
+}
+
+// SAFETY: The domain itself is only accessed through the thread-safe irq domain API, and `T` is
+// `Send`.
+unsafe impl<T: IrqDomainOps> Send for IrqDomain<T> {}
+
+// SAFETY: The domain itself is only accessed through the thread-safe irq domain API, and `T` is
+// `Sync`.
+unsafe impl<T: IrqDomainOps> Sync for IrqDomain<T> {}
@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! LED class devices.
+//!
+//! Drivers of LED controllers register each LED with the LED class, which exposes it to
+//! userspace under `/sys/class/leds` and lets triggers, e.g. disk activity or heartbeat, drive
+//! it.
+//!
+//! C header: [`include/linux/leds.h`](../../../../include/linux/leds.h)
+
+use crate::{
+    bindings,
+    device::{Device, DeviceRef},
+    error::to_result,
+    str::CStr,
+    types::Opaque,
+    Result,
+};
+use alloc::boxed::Box;
+use core::pin::Pin;
+
+/// The operations of an LED.
+pub trait LedOps: Sync {
+    /// Whether [`LedOps::brightness_get`] is implemented.
+    ///
+    /// If not, the LED class reports the brightness last set instead of reading it back from the
+    /// hardware.
+    const HAS_BRIGHTNESS_GET: bool = false;
+
+    /// Sets the brightness of the LED, from `0` (off) to the maximum brightness it was registered
+    /// with.
+    ///
+    /// May be called from atomic context, e.g. by triggers running in timer callbacks, so it must
+    /// not sleep.
+    fn brightness_set(&self, value: u8);
+
+    /// Returns the current brightness of the LED, as read back from the hardware.
+    ///
+    /// Only called if [`LedOps::HAS_BRIGHTNESS_GET`] is `true`.
+    fn brightness_get(&self) -> u8 {
+        0
+    }
+}
+
+unsafe extern "C" fn brightness_set_callback<T: LedOps>(
+    cdev: *mut bindings::led_classdev,
+    brightness: bindings::led_brightness,
+) {
+    // SAFETY: The callback is only installed by `LedClassDev::<T>::register`, which
+    // registers the `cdev` field of a `Led<T>`, alive since the `LedClassDev<T>` owning it
+    // unregisters it when dropped.
+    let led = unsafe { Led::<T>::from_cdev(cdev) };
+    // The LED class caps the brightness to the maximum brightness, which fits in a `u8`.
+    led.ops.brightness_set(brightness as u8);
+}
+
+unsafe extern "C" fn brightness_get_callback<T: LedOps>(
+    cdev: *mut bindings::led_classdev,
+) -> bindings::led_brightness {
+    // SAFETY: The callback is only installed by `LedClassDev::<T>::register`, which
+    // registers the `cdev` field of a `Led<T>`, alive since the `LedClassDev<T>` owning it
+    // unregisters it when dropped.
+    let led = unsafe { Led::<T>::from_cdev(cdev) };
+    led.ops.brightness_get().into()
+}
+
+/// The parts of a [`LedClassDev`] that the LED class refers to, which must not move.
+struct Led<T: LedOps> {
+    cdev: Opaque<bindings::led_classdev>,
+    ops: T,
+}
+
+impl<T: LedOps> Led<T> {
+    /// Returns the [`Led`] whose `cdev` field `cdev` points to.
+    ///
+    /// # Safety
+    ///
+    /// `cdev` must point to the `cdev` field of a `Led<T>` that outlives the returned reference.
+    unsafe fn from_cdev<'a>(cdev: *mut bindings::led_classdev) -> &'a Self {
+        // CAST: `Opaque` is `repr(transparent)` over the C struct.
+        let cdev = cdev as *const Opaque<bindings::led_classdev>;
+        let led = crate::container_of!(cdev, Self, cdev);
+        // SAFETY: By the safety requirements of this function, `led` is valid and outlives `'a`.
+        unsafe { &*led }
+    }
+}
+
+/// An LED registered with the LED class, whose operations are implemented by `T`.
+///
+/// The registration is owned by the [`LedClassDev`], and is undone when it is dropped. It holds a
+/// reference to the parent device, so it remains valid even if it outlives the binding of the
+/// driver to the device, though drivers usually keep it in their driver data.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::{c_str, leds, platdev::PlatformDevice};
+/// # use core::sync::atomic::{AtomicU8, Ordering};
+/// struct StatusLed {
+///     // Stands in for the hardware register.
+///     level: AtomicU8,
+/// }
+///
+/// impl leds::LedOps for StatusLed {
+///     fn brightness_set(&self, value: u8) {
+///         self.level.store(value, Ordering::Relaxed);
+///     }
+/// }
+///
+/// fn probe_led(pdev: &PlatformDevice) -> Result<leds::LedClassDev<StatusLed>> {
+///     let led = StatusLed {
+///         level: AtomicU8::new(0),
+///     };
+///     leds::LedClassDev::register(pdev, c_str!("rust:green:status"), 255, led)
+/// }
+/// ```
+///
+/// # Invariants
+///
+/// `led.cdev` is registered with `dev` as its parent by `led_classdev_register_ext`.
+pub struct LedClassDev<T: LedOps> {
+    _dev: DeviceRef,
+    led: Pin<Box<Led<T>>>,
+}
+
+// SAFETY: The LED can be unregistered from any thread, and the LED class calls its operations
+// from any thread, so they must be `Send`; they are `Sync` by the bounds of `LedOps`.
+unsafe impl<T: LedOps + Send> Send for LedClassDev<T> {}
+
+// SAFETY: `LedClassDev` only gives shared access to the operations, which are `Sync`.
+unsafe impl<T: LedOps> Sync for LedClassDev<T> {}
+
+impl<T: LedOps> LedClassDev<T> {
+    /// Registers an LED named `name`, whose brightness ranges from `0` to `max_brightness`, with
+    /// `dev` as its parent.
+    ///
+    /// LED names follow the `devicename:color:function` convention, e.g. `rust:green:status`.
+    pub fn register(
+        dev: &dyn Device,
+        name: &'static CStr,
+        max_brightness: u8,
+        ops: T,
+    ) -> Result<Self> {
+        let led = Pin::from(Box::try_new(Led {
+            cdev: Opaque::new(bindings::led_classdev {
+                name: name.as_char_ptr(),
+                max_brightness: max_brightness.into(),
+                brightness_set: Some(brightness_set_callback::<T>),
+                brightness_get: if T::HAS_BRIGHTNESS_GET {
+                    Some(brightness_get_callback::<T>)
+                } else {
+                    None
+                },
+                ..Default::default()
+            }),
+            ops,
+        })?);
+        // SAFETY: `raw_device()` is valid as guaranteed by the `Device` trait, `led.cdev` is
+        // pinned, so it does not move while registered, and `name` has static lifetime.
+        to_result(unsafe {
+            bindings::led_classdev_register_ext(
+                dev.raw_device(),
+                led.cdev.get(),
+                core::ptr::null_mut(),
+            )
+        })?;
+        // INVARIANTS: The LED was registered above.
+        Ok(Self {
+            _dev: DeviceRef::from_dev(dev),
+            led,
+        })
+    }
+
+    /// Returns the operations of the LED.
+    pub fn ops(&self) -> &T {
+        &self.led.ops
+    }
+}
+
+impl<T: LedOps> Drop for LedClassDev<T> {
+    fn drop(&mut self) {
+        // SAFETY: `self.led.cdev` was registered by the type invariants, and is only unregistered
+        // here. Once this returns, the LED class no longer calls the operations, which can then be
+        // dropped.
+        unsafe { bindings::led_classdev_unregister(self.led.cdev.get()) };
+    }
+}
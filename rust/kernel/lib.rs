@@ -37,15 +37,27 @@ mod allocator;
 #[doc(hidden)]
 pub mod bindings;
 
+pub mod barrier;
 pub mod buffer;
 pub mod c_types;
 pub mod chrdev;
+pub mod command_buffer;
+pub mod device;
+pub mod dma;
 mod error;
+#[cfg(CONFIG_EVENTFD)]
+pub mod eventfd;
 pub mod file;
 pub mod file_operations;
+pub mod hrtimer;
+#[cfg(CONFIG_I2C)]
+pub mod i2c;
+#[cfg(CONFIG_INPUT)]
+pub mod input;
 pub mod miscdev;
 pub mod pages;
 pub mod security;
+pub mod simple_attr;
 pub mod str;
 pub mod task;
 pub mod traits;
@@ -61,24 +73,40 @@ mod build_assert;
 pub mod prelude;
 pub mod print;
 pub mod random;
+#[cfg(CONFIG_REGMAP)]
+pub mod regmap;
+pub mod revocable;
+pub mod ring_buffer;
 mod static_assert;
 pub mod sync;
 
 #[cfg(CONFIG_SYSCTL)]
 pub mod sysctl;
 
+#[cfg(CONFIG_THERMAL_OF)]
+pub mod thermal;
+
 pub mod io_buffer;
 pub mod iov_iter;
+#[cfg(CONFIG_IRQ_DOMAIN)]
+pub mod irq_domain;
+#[cfg(CONFIG_LEDS_CLASS)]
+pub mod leds;
 pub mod of;
+#[cfg(CONFIG_PM_OPP)]
+pub mod opp;
+#[cfg(CONFIG_PINCTRL)]
+pub mod pinctrl;
 pub mod platdev;
 mod types;
 pub mod user_ptr;
+pub mod wait;
 
 #[doc(hidden)]
 pub use build_error::build_error;
 
-pub use crate::error::{Error, Result};
-pub use crate::types::{Mode, ScopeGuard};
+pub use crate::error::{Error, ErrorKind, Result, ResultExt};
+pub use crate::types::{Mode, Opaque, ScopeGuard};
 
 /// Page size defined in terms of the `PAGE_SHIFT` macro from C.
 ///
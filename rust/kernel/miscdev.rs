@@ -7,7 +7,7 @@
 //! Reference: <https://www.kernel.org/doc/html/latest/driver-api/misc_devices.html>
 
 use crate::bindings;
-use crate::error::{Error, Result};
+use crate::error::{to_result, Error, Result};
 use crate::file_operations::{FileOpenAdapter, FileOpener, FileOperationsVtable};
 use crate::str::CStr;
 use alloc::boxed::Box;
@@ -72,10 +72,7 @@ impl<T: Sync> Registration<T> {
         this.mdev.name = name.as_char_ptr();
         this.mdev.minor = minor.unwrap_or(bindings::MISC_DYNAMIC_MINOR as i32);
 
-        let ret = unsafe { bindings::misc_register(&mut this.mdev) };
-        if ret < 0 {
-            return Err(Error::from_kernel_errno(ret));
-        }
+        to_result(unsafe { bindings::misc_register(&mut this.mdev) })?;
         this.registered = true;
         Ok(())
     }
@@ -4,11 +4,40 @@
 //!
 //! C header: [`include/linux/of_*.h`](../../../../include/linux/of_*.h)
 
-use crate::{bindings, c_types, str::CStr};
+use crate::{bindings, c_types, error::Error, str::CStr, Result};
 
+use core::marker::PhantomData;
 use core::ops::Deref;
 use core::ptr;
 
+extern "C" {
+    #[allow(improper_ctypes)]
+    fn rust_helper_of_node_get(node: *mut bindings::device_node) -> *mut bindings::device_node;
+
+    #[allow(improper_ctypes)]
+    fn rust_helper_of_node_put(node: *mut bindings::device_node);
+}
+
+/// Returns whether the machine is compatible with `compatible`, i.e., whether `compatible` is
+/// listed in the `compatible` property of the root node of the devicetree.
+///
+/// This allows drivers to apply board- or SoC-specific quirks, e.g. for a device integrated in
+/// several SoCs that only differ in details the device's own compatible string does not
+/// capture. Always returns `false` when the kernel is built without devicetree support.
+pub fn machine_is_compatible(compatible: &CStr) -> bool {
+    #[cfg(CONFIG_OF)]
+    {
+        // SAFETY: `compatible` is `NUL`-terminated.
+        unsafe { bindings::of_machine_is_compatible(compatible.as_char_ptr()) != 0 }
+    }
+
+    #[cfg(not(CONFIG_OF))]
+    {
+        let _ = compatible;
+        false
+    }
+}
+
 /// A kernel Open Firmware / devicetree match table.
 ///
 /// Can only exist as an `&OfMatchTable` reference (akin to `&str` or
@@ -44,19 +73,84 @@ pub struct ConstOfMatchTable<const N: usize> {
 
 impl<const N: usize> ConstOfMatchTable<N> {
     /// Creates a new Open Firmware Match Table from a list of compatible strings.
+    ///
+    /// Fails to build if one of the compatible strings does not fit in a `struct of_device_id`.
+    /// See [`Self::try_new_const()`] for a fallible version.
     pub const fn new_const(compatibles: [&'static CStr; N]) -> Self {
+        match Self::try_new_const(compatibles) {
+            Ok(table) => table,
+            Err(_) => panic!("compatible string too long for `struct of_device_id`"),
+        }
+    }
+
+    /// Creates a new Open Firmware Match Table from a list of compatible strings.
+    ///
+    /// Returns `EINVAL` if one of the compatible strings, including its `NUL` terminator, is longer
+    /// than the `compatible` field of `struct of_device_id`.
+    pub const fn try_new_const(compatibles: [&'static CStr; N]) -> Result<Self> {
         let mut table = [Self::zeroed_of_device_id(); N];
         let mut i = 0;
         while i < N {
-            table[i] = Self::new_of_device_id(compatibles[i]);
+            table[i] = match Self::new_of_device_id(compatibles[i], ptr::null()) {
+                Ok(id) => id,
+                Err(e) => return Err(e),
+            };
             i += 1;
         }
-        Self {
+        Ok(Self {
             table,
             // INVARIANTS: we zero the sentinel here, and never change it
             // anywhere. Therefore it always contains zeroes.
             sentinel: Self::zeroed_of_device_id(),
+        })
+    }
+
+    /// Creates a new Open Firmware Match Table from a list of compatible strings, each paired
+    /// with the match data of the hardware variant it identifies.
+    ///
+    /// The data of the entry that matched a device is stored in the `data` field of its
+    /// `struct of_device_id` and can be retrieved in probe, e.g. with
+    /// [`crate::platdev::PlatformDevice::of_match_data`]. This allows a single driver to handle
+    /// several variants of the same hardware. Retrieving it requires `unsafe` code though, see
+    /// [`ConstOfMatchDataTable`] for a table whose match data can be retrieved safely.
+    ///
+    /// Fails to build if one of the compatible strings does not fit in a `struct of_device_id`.
+    /// See [`Self::try_new_const_with_data()`] for a fallible version.
+    pub const fn new_const_with_data<T: Sync>(
+        entries: [(&'static CStr, Option<&'static T>); N],
+    ) -> Self {
+        match Self::try_new_const_with_data(entries) {
+            Ok(table) => table,
+            Err(_) => panic!("compatible string too long for `struct of_device_id`"),
+        }
+    }
+
+    /// Creates a new Open Firmware Match Table from a list of compatible strings, each paired
+    /// with its match data.
+    ///
+    /// See [`Self::new_const_with_data()`] and [`Self::try_new_const()`].
+    pub const fn try_new_const_with_data<T: Sync>(
+        entries: [(&'static CStr, Option<&'static T>); N],
+    ) -> Result<Self> {
+        let mut table = [Self::zeroed_of_device_id(); N];
+        let mut i = 0;
+        while i < N {
+            let data = match entries[i].1 {
+                Some(data) => data as *const T as *const c_types::c_void,
+                None => ptr::null(),
+            };
+            table[i] = match Self::new_of_device_id(entries[i].0, data) {
+                Ok(id) => id,
+                Err(e) => return Err(e),
+            };
+            i += 1;
         }
+        Ok(Self {
+            table,
+            // INVARIANTS: we zero the sentinel here, and never change it
+            // anywhere. Therefore it always contains zeroes.
+            sentinel: Self::zeroed_of_device_id(),
+        })
     }
 
     const fn zeroed_of_device_id() -> bindings::of_device_id {
@@ -68,17 +162,22 @@ impl<const N: usize> ConstOfMatchTable<N> {
         }
     }
 
-    const fn new_of_device_id(compatible: &'static CStr) -> bindings::of_device_id {
+    const fn new_of_device_id(
+        compatible: &'static CStr,
+        data: *const c_types::c_void,
+    ) -> Result<bindings::of_device_id> {
         let mut id = Self::zeroed_of_device_id();
+        id.data = data;
         let compatible = compatible.as_bytes_with_nul();
+        if compatible.len() > id.compatible.len() {
+            return Err(Error::EINVAL);
+        }
         let mut i = 0;
         while i < compatible.len() {
-            // If `compatible` does not fit in `id.compatible`, an
-            // "index out of bounds" build time error will be triggered.
             id.compatible[i] = compatible[i] as c_types::c_char;
             i += 1;
         }
-        id
+        Ok(id)
     }
 }
 
@@ -99,3 +198,158 @@ impl<const N: usize> Deref for ConstOfMatchTable<N> {
         unsafe { &*head }
     }
 }
+
+/// A kernel Open Firmware / devicetree match table, the entries of which all have match data of
+/// type `T`.
+///
+/// Can only exist as an `&OfMatchDataTable<T>` reference, see [`OfMatchTable`]. Registering a
+/// driver with such a table allows it to retrieve the match data safely, e.g. with
+/// [`crate::platdev::PlatformDevice::match_data`].
+///
+/// # Invariants
+///
+/// The `data` field of each entry of the table points to a `T` with static lifetime.
+#[repr(transparent)]
+pub struct OfMatchDataTable<T: Sync + 'static> {
+    _p: PhantomData<&'static T>,
+    table: OfMatchTable,
+}
+
+impl<T: Sync + 'static> Deref for OfMatchDataTable<T> {
+    type Target = OfMatchTable;
+
+    fn deref(&self) -> &OfMatchTable {
+        &self.table
+    }
+}
+
+/// An Open Firmware Match Table, the entries of which all have match data of type `T`, that can
+/// be constructed at build time.
+///
+/// # Invariants
+///
+/// The `data` field of each entry of `table` points to a `T` with static lifetime.
+pub struct ConstOfMatchDataTable<T: Sync + 'static, const N: usize> {
+    table: ConstOfMatchTable<N>,
+    _p: PhantomData<&'static T>,
+}
+
+impl<T: Sync + 'static, const N: usize> ConstOfMatchDataTable<T, N> {
+    /// Creates a new Open Firmware Match Table from a list of compatible strings, each paired
+    /// with the match data of the hardware variant it identifies.
+    ///
+    /// Unlike with [`ConstOfMatchTable::new_const_with_data`], every entry has data, and the data
+    /// of all the entries has the same type, so it can be retrieved without `unsafe` code.
+    ///
+    /// Fails to build if one of the compatible strings does not fit in a `struct of_device_id`.
+    /// See [`Self::try_new_const()`] for a fallible version.
+    pub const fn new_const(entries: [(&'static CStr, &'static T); N]) -> Self {
+        match Self::try_new_const(entries) {
+            Ok(table) => table,
+            Err(_) => panic!("compatible string too long for `struct of_device_id`"),
+        }
+    }
+
+    /// Creates a new Open Firmware Match Table from a list of compatible strings, each paired
+    /// with its match data.
+    ///
+    /// See [`Self::new_const()`] and [`ConstOfMatchTable::try_new_const()`].
+    pub const fn try_new_const(entries: [(&'static CStr, &'static T); N]) -> Result<Self> {
+        let mut table = [ConstOfMatchTable::<N>::zeroed_of_device_id(); N];
+        let mut i = 0;
+        while i < N {
+            let data = entries[i].1 as *const T as *const c_types::c_void;
+            table[i] = match ConstOfMatchTable::<N>::new_of_device_id(entries[i].0, data) {
+                Ok(id) => id,
+                Err(e) => return Err(e),
+            };
+            i += 1;
+        }
+        // INVARIANTS: The data of each entry was set to a `&'static T` above.
+        Ok(Self {
+            table: ConstOfMatchTable {
+                table,
+                // INVARIANTS: we zero the sentinel here, and never change it
+                // anywhere. Therefore it always contains zeroes.
+                sentinel: ConstOfMatchTable::<N>::zeroed_of_device_id(),
+            },
+            _p: PhantomData,
+        })
+    }
+}
+
+impl<T: Sync + 'static, const N: usize> Deref for ConstOfMatchDataTable<T, N> {
+    type Target = OfMatchDataTable<T>;
+
+    fn deref(&self) -> &OfMatchDataTable<T> {
+        let head = &*self.table as *const OfMatchTable as *const OfMatchDataTable<T>;
+
+        // SAFETY: `OfMatchDataTable<T>` is `repr(transparent)` over `OfMatchTable`, and the
+        // reference has the lifetime of `self`, see the `Deref` implementation of
+        // `ConstOfMatchTable`.
+        // INVARIANTS: The entries of the table all have data of type `T`, as per the type
+        // invariants.
+        unsafe { &*head }
+    }
+}
+
+/// A devicetree node.
+///
+/// Holds a reference to the node, so it remains valid independently of the device it was obtained
+/// from.
+///
+/// # Invariants
+///
+/// The pointer `DeviceNode::ptr` is non-null and valid, and we hold a reference to it.
+pub struct DeviceNode {
+    ptr: *mut bindings::device_node,
+}
+
+// SAFETY: Reference counting of devicetree nodes is thread-safe, so it is fine to release the
+// reference from any thread.
+unsafe impl Send for DeviceNode {}
+
+// SAFETY: `DeviceNode` only gives access to the raw pointer, whose users are responsible for
+// following the kernel's locking rules.
+unsafe impl Sync for DeviceNode {}
+
+impl DeviceNode {
+    /// Creates a new reference to the node pointed to by `ptr`, or returns [`None`] if it is null.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be either null or valid for the duration of the call.
+    pub(crate) unsafe fn get(ptr: *mut bindings::device_node) -> Option<Self> {
+        if ptr.is_null() {
+            return None;
+        }
+        // SAFETY: `ptr` is non-null and valid by the safety requirements of this function.
+        let ptr = unsafe { rust_helper_of_node_get(ptr) };
+        // INVARIANTS: We hold the reference acquired above.
+        Some(Self { ptr })
+    }
+
+    /// Returns the raw `struct device_node` pointer, e.g. to be passed to OF functions that are
+    /// not wrapped yet.
+    ///
+    /// The pointer is valid for as long as `self` is alive.
+    pub fn as_ptr(&self) -> *mut bindings::device_node {
+        self.ptr
+    }
+}
+
+impl Clone for DeviceNode {
+    fn clone(&self) -> Self {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        let ptr = unsafe { rust_helper_of_node_get(self.ptr) };
+        // INVARIANTS: We hold the reference acquired above.
+        Self { ptr }
+    }
+}
+
+impl Drop for DeviceNode {
+    fn drop(&mut self) {
+        // SAFETY: By the type invariants, we hold a reference to `ptr`, which we now release.
+        unsafe { rust_helper_of_node_put(self.ptr) };
+    }
+}
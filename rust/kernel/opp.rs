@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Operating performance points (OPPs).
+//!
+//! The OPPs of a device are the pairs of frequency and voltage it can run at, typically described
+//! by an `operating-points-v2` table in the devicetree. Drivers that scale the clock of their
+//! device with the load pick one of them, and let the OPP core program the clock and the
+//! regulator accordingly.
+//!
+//! C header: [`include/linux/pm_opp.h`](../../../../include/linux/pm_opp.h)
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/power/opp.html>
+
+use crate::{
+    bindings,
+    device::{Device, DeviceRef},
+    error::{from_kernel_err_ptr, to_result, to_result_value, Result},
+};
+
+/// The OPP table of a device, as parsed from the devicetree.
+///
+/// The table is device-managed: it is removed when the driver is unbound from the device. The
+/// [`OppTable`] holds a reference to the device, so using it after that is safe, though the OPP
+/// core then fails the calls because the device no longer has a table.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::platdev::PlatformDevice;
+/// fn set_max_rate(pdev: &PlatformDevice, max_hz: u64) -> Result {
+///     let opps = pdev.devm_pm_opp_of_add_table()?;
+///     let rate = opps.find_freq_floor(max_hz)?;
+///     opps.set_rate(rate)
+/// }
+/// ```
+///
+/// # Invariants
+///
+/// An OPP table was added for `dev` by `devm_pm_opp_of_add_table`.
+pub struct OppTable {
+    dev: DeviceRef,
+}
+
+impl OppTable {
+    /// Adds the OPP table of `dev`, as described by its devicetree node, and returns it.
+    ///
+    /// Fails with `ENODEV` if the device has no OPP table in the devicetree.
+    pub fn devm_of_add(dev: &dyn Device) -> Result<Self> {
+        // SAFETY: `dev.raw_device()` is valid by the safety requirements of `Device`.
+        to_result(unsafe { bindings::devm_pm_opp_of_add_table(dev.raw_device()) })?;
+        // INVARIANTS: The table was added above.
+        Ok(Self {
+            dev: DeviceRef::from_dev(dev),
+        })
+    }
+
+    /// Returns the number of available OPPs.
+    pub fn count(&self) -> Result<u32> {
+        // SAFETY: `raw_device()` is valid by the invariants of `DeviceRef`.
+        to_result_value(unsafe { bindings::dev_pm_opp_get_opp_count(self.dev.raw_device()) })
+    }
+
+    /// Returns the frequency, in Hz, of the available OPP with the lowest frequency that is
+    /// greater than or equal to `freq`.
+    ///
+    /// Fails with `ERANGE` if there is no such OPP.
+    pub fn find_freq_ceil(&self, freq: u64) -> Result<u64> {
+        let mut freq = freq as _;
+        // SAFETY: `raw_device()` is valid by the invariants of `DeviceRef`, and `freq` is a valid
+        // location for the frequency.
+        let opp = from_kernel_err_ptr(unsafe {
+            bindings::dev_pm_opp_find_freq_ceil(self.dev.raw_device(), &mut freq)
+        })?;
+        // SAFETY: `opp` was returned with a reference held by `dev_pm_opp_find_freq_ceil`.
+        unsafe { bindings::dev_pm_opp_put(opp) };
+        Ok(freq as _)
+    }
+
+    /// Returns the frequency, in Hz, of the available OPP with the highest frequency that is
+    /// lower than or equal to `freq`.
+    ///
+    /// Fails with `ERANGE` if there is no such OPP.
+    pub fn find_freq_floor(&self, freq: u64) -> Result<u64> {
+        let mut freq = freq as _;
+        // SAFETY: `raw_device()` is valid by the invariants of `DeviceRef`, and `freq` is a valid
+        // location for the frequency.
+        let opp = from_kernel_err_ptr(unsafe {
+            bindings::dev_pm_opp_find_freq_floor(self.dev.raw_device(), &mut freq)
+        })?;
+        // SAFETY: `opp` was returned with a reference held by `dev_pm_opp_find_freq_floor`.
+        unsafe { bindings::dev_pm_opp_put(opp) };
+        Ok(freq as _)
+    }
+
+    /// Switches the device to the OPP for frequency `freq`, in Hz.
+    ///
+    /// The OPP core rounds `freq` to a frequency supported by the clock, then sets the clock and,
+    /// if the device has one, the voltage of its regulator, in the order that keeps the device
+    /// within its operating conditions. May sleep.
+    pub fn set_rate(&self, freq: u64) -> Result {
+        // SAFETY: `raw_device()` is valid by the invariants of `DeviceRef`.
+        to_result(unsafe { bindings::dev_pm_opp_set_rate(self.dev.raw_device(), freq as _) })
+    }
+}
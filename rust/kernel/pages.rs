@@ -5,10 +5,10 @@
 //! TODO: This module is a work in progress.
 
 use crate::{
-    bindings, c_types, io_buffer::IoBufferReader, user_ptr::UserSlicePtrReader, Error, Result,
-    PAGE_SIZE,
+    bindings, c_types, error::to_result, io_buffer::IoBufferReader, user_ptr::UserSlicePtrReader,
+    Error, Result, PAGE_SIZE,
 };
-use core::{marker::PhantomData, ptr};
+use core::{marker::PhantomData, mem, ptr};
 
 extern "C" {
     #[allow(improper_ctypes)]
@@ -22,6 +22,9 @@ extern "C" {
 
     #[allow(improper_ctypes)]
     fn rust_helper_kunmap(page: *mut bindings::page);
+
+    #[allow(improper_ctypes)]
+    fn rust_helper_get_page(page: *mut bindings::page);
 }
 
 /// A set of physical pages.
@@ -64,12 +67,7 @@ impl<const ORDER: u32> Pages<ORDER> {
 
         // SAFETY: We check above that the allocation is of order 0. The range of `address` is
         // already checked by `vm_insert_page`.
-        let ret = unsafe { bindings::vm_insert_page(vma, address as _, self.pages) };
-        if ret != 0 {
-            Err(Error::from_kernel_errno(ret))
-        } else {
-            Ok(())
-        }
+        to_result(unsafe { bindings::vm_insert_page(vma, address as _, self.pages) })
     }
 
     /// Copies data from the given [`UserSlicePtrReader`] into the pages.
@@ -154,6 +152,26 @@ impl<const ORDER: u32> Pages<ORDER> {
     }
 }
 
+impl Pages<0> {
+    /// Consumes the page, returning the raw `struct page` pointer without dropping the reference
+    /// to it. The caller becomes responsible for releasing the reference.
+    pub(crate) fn into_raw(self) -> *mut bindings::page {
+        let pages = self.pages;
+        mem::forget(self);
+        pages
+    }
+}
+
+/// Takes an additional reference to the page, which is freed once all references are dropped.
+impl Clone for Pages<0> {
+    fn clone(&self) -> Self {
+        // SAFETY: `self.pages` is valid by the type invariants.
+        unsafe { rust_helper_get_page(self.pages) };
+        // INVARIANTS: We hold the reference acquired above.
+        Self { pages: self.pages }
+    }
+}
+
 impl<const ORDER: u32> Drop for Pages<ORDER> {
     fn drop(&mut self) {
         // SAFETY: By the type invariants, we know the pages are allocated with the given order.
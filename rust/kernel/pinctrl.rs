@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Pin control.
+//!
+//! Allow drivers to select the mux and configuration of their pins, e.g. to route the pins of an
+//! SoC to a peripheral, or to put them in a low-power configuration while the device sleeps.
+//!
+//! C header: [`include/linux/pinctrl/consumer.h`](../../../../include/linux/pinctrl/consumer.h)
+//!
+//! Reference: <https://www.kernel.org/doc/html/latest/driver-api/pin-control.html>
+
+use crate::{
+    bindings, c_str,
+    device::Device,
+    error::{from_kernel_err_ptr, to_result, Result},
+    str::CStr,
+};
+use core::marker::PhantomData;
+
+/// The name of the state that the driver core selects before probing the device.
+pub const STATE_DEFAULT: &CStr = c_str!("default");
+
+/// The name of the state that the driver core selects, if present, before probing the device
+/// instead of [`STATE_DEFAULT`], which is then selected after a successful probe.
+pub const STATE_INIT: &CStr = c_str!("init");
+
+/// The name of the state to select when the device is idle.
+pub const STATE_IDLE: &CStr = c_str!("idle");
+
+/// The name of the state to select when the device is suspended.
+pub const STATE_SLEEP: &CStr = c_str!("sleep");
+
+/// The pin control handle of a device.
+///
+/// The handle is device-managed: it is released when the driver is unbound from the device. It
+/// therefore borrows the device for `'a`, during which the driver is bound to it, e.g. the
+/// [`PlatformDevice`](crate::platdev::PlatformDevice) passed to a driver callback, so it cannot
+/// be used after that, e.g. from the state of an open file. Selecting a state may sleep, so the
+/// handle cannot be shared through a [`Revocable`](crate::revocable::Revocable) either.
+///
+/// # Invariants
+///
+/// The pointer `PinCtrl::ptr` is non-null and valid for `'a`.
+pub struct PinCtrl<'a> {
+    ptr: *mut bindings::pinctrl,
+    _dev: PhantomData<&'a ()>,
+}
+
+// SAFETY: The pin control API serialises accesses to the handle internally.
+unsafe impl Send for PinCtrl<'_> {}
+
+// SAFETY: The pin control API serialises accesses to the handle internally.
+unsafe impl Sync for PinCtrl<'_> {}
+
+impl<'a> PinCtrl<'a> {
+    /// Gets the device-managed pin control handle of `dev`.
+    ///
+    /// Fails with `EPROBE_DEFER` if the pin controller has not been probed yet, in which case the
+    /// error should be returned from the probe function so it is retried later. Drivers get the
+    /// handle safely through e.g.
+    /// [`PlatformDevice::devm_pinctrl_get`](crate::platdev::PlatformDevice::devm_pinctrl_get).
+    ///
+    /// # Safety
+    ///
+    /// The driver must remain bound to `dev` for the whole lifetime `'a`.
+    pub unsafe fn devm_get(dev: &'a dyn Device) -> Result<Self> {
+        // SAFETY: `dev.raw_device()` is valid by the safety requirements of `Device`.
+        let ptr = from_kernel_err_ptr(unsafe { bindings::devm_pinctrl_get(dev.raw_device()) })?;
+        // INVARIANTS: `devm_pinctrl_get` succeeded, so `ptr` is valid, and it is only released
+        // when the driver is unbound from `dev`, which does not happen during `'a` by the safety
+        // requirements of this function.
+        Ok(Self {
+            ptr,
+            _dev: PhantomData,
+        })
+    }
+
+    /// Looks up the state `name`, e.g. [`STATE_DEFAULT`] or [`STATE_SLEEP`], as named in the
+    /// `pinctrl-names` property of the devicetree node of the device.
+    pub fn lookup_state(&self, name: &CStr) -> Result<PinCtrlState<'_, 'a>> {
+        // SAFETY: `self.ptr` is valid by the type invariants, and `name` is `NUL`-terminated.
+        let ptr = from_kernel_err_ptr(unsafe {
+            bindings::pinctrl_lookup_state(self.ptr, name.as_char_ptr())
+        })?;
+        // INVARIANTS: `pinctrl_lookup_state` succeeded, so `ptr` is a valid state of `self`.
+        Ok(PinCtrlState {
+            ptr,
+            _pinctrl: PhantomData,
+        })
+    }
+
+    /// Muxes and configures the pins of the device as described by `state`.
+    pub fn select_state(&self, state: &PinCtrlState<'_, 'a>) -> Result {
+        // SAFETY: `self.ptr` is valid by the type invariants, and `state.ptr` is a valid state of
+        // `self` by the invariants of `PinCtrlState`.
+        to_result(unsafe { bindings::pinctrl_select_state(self.ptr, state.ptr) })
+    }
+
+    /// Looks up the state `name` and selects it.
+    ///
+    /// See [`PinCtrl::lookup_state`] and [`PinCtrl::select_state`].
+    pub fn select_state_by_name(&self, name: &CStr) -> Result {
+        self.select_state(&self.lookup_state(name)?)
+    }
+}
+
+/// A state of the pins of a device, as returned by [`PinCtrl::lookup_state`].
+///
+/// # Invariants
+///
+/// The pointer `PinCtrlState::ptr` is non-null and is a valid state of the [`PinCtrl`] it
+/// borrows.
+pub struct PinCtrlState<'a, 'b> {
+    ptr: *mut bindings::pinctrl_state,
+    _pinctrl: PhantomData<&'a PinCtrl<'b>>,
+}
@@ -8,25 +8,69 @@
 
 use crate::{
     bindings, c_types,
-    error::{Error, Result},
+    device::{Device, DeviceRef},
+    error::{to_result, to_result_value, Error, Result},
     from_kernel_result,
-    of::OfMatchTable,
+    of::{DeviceNode, OfMatchDataTable, OfMatchTable},
     str::CStr,
-    types::PointerWrapper,
+    types::{Opaque, PointerWrapper},
 };
 use alloc::boxed::Box;
-use core::{marker::PhantomPinned, pin::Pin};
+#[cfg(CONFIG_NVMEM)]
+use alloc::vec::Vec;
+use core::{
+    any::TypeId,
+    ops::Deref,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[cfg(CONFIG_NVMEM)]
+use crate::{error::from_kernel_err_ptr, ScopeGuard};
+#[cfg(CONFIG_PM_OPP)]
+use crate::opp::OppTable;
+#[cfg(CONFIG_PINCTRL)]
+use crate::pinctrl::PinCtrl;
 
 /// A registration of a platform device.
-#[derive(Default)]
+///
+/// The driver remains registered with the kernel for as long as the [`Registration`] is alive,
+/// whether or not a matching device is present when it is created. [`PlatformDriver::probe`] is
+/// called for matching devices that already exist, and later for every matching device added
+/// afterwards, e.g. when a devicetree overlay is applied at runtime. Modules should therefore
+/// keep the registration in their [`crate::KernelModule`] instance so it is only dropped, and the
+/// driver unregistered, when the module is unloaded.
+///
+/// The registration keeps track of how many times the driver was bound to, and unbound from, a
+/// device, e.g. so that tests can check that a device was bound and unbound cleanly. Besides
+/// device addition and removal, binding and unbinding can be triggered from userspace by writing
+/// the name of a device to the `bind` and `unbind` files of the driver in
+/// `/sys/bus/platform/drivers/<name>/`; these go through the same [`PlatformDriver::probe`] and
+/// [`PlatformDriver::remove`] callbacks and are counted likewise.
 pub struct Registration {
     registered: bool,
-    pdrv: bindings::platform_driver,
-    _pin: PhantomPinned,
+    probe_count: AtomicUsize,
+    remove_count: AtomicUsize,
+    match_data_type: Option<TypeId>,
+    pdrv: Opaque<bindings::platform_driver>,
+}
+
+impl Default for Registration {
+    fn default() -> Self {
+        Self {
+            registered: false,
+            probe_count: AtomicUsize::new(0),
+            remove_count: AtomicUsize::new(0),
+            match_data_type: None,
+            pdrv: Opaque::new(bindings::platform_driver::default()),
+        }
+    }
 }
 
 // SAFETY: `Registration` does not expose any of its state across threads
-// (it is fine for multiple threads to have a shared reference to it).
+// (it is fine for multiple threads to have a shared reference to it), other
+// than its counters, which are atomic, and the type of its match data, which is only written
+// before the driver is registered.
 unsafe impl Sync for Registration {}
 
 extern "C" {
@@ -42,18 +86,53 @@ extern "C" {
     );
 }
 
+/// Returns the registration of the driver that `pdev` is being bound to, or is bound to.
+///
+/// # Safety
+///
+/// `pdev` must be valid, and must be in a probe, remove or shutdown callback of a driver registered
+/// through [`Registration`].
+unsafe fn registration_of<'a>(pdev: *mut bindings::platform_device) -> &'a Registration {
+    // SAFETY: `pdev` is valid by the safety requirements.
+    let drv = unsafe { (*pdev).dev.driver };
+    // The driver core sets `dev.driver` before calling `probe` and only clears it after `remove`
+    // returns. As per the safety requirements, it points to the `driver` field of the `pdrv` field
+    // of a `Registration`.
+    let pdrv = crate::container_of!(drv, bindings::platform_driver, driver);
+    let reg = crate::container_of!(pdrv, Registration, pdrv);
+    // SAFETY: `reg` points to a `Registration`, which remains alive until the driver is
+    // unregistered, hence until after all devices are unbound.
+    unsafe { &*reg }
+}
+
+/// Converts the return value of `platform_get_irq` and `platform_get_irq_byname`.
+///
+/// `0` is never a valid interrupt number, but older kernels returned it for interrupts that
+/// failed to be mapped, so it is an error too.
+fn irq_result(ret: c_types::c_int) -> Result<u32> {
+    match to_result_value(ret)? {
+        0 => Err(Error::EINVAL),
+        irq => Ok(irq),
+    }
+}
+
 extern "C" fn probe_callback<P: PlatformDriver>(
     pdev: *mut bindings::platform_device,
 ) -> c_types::c_int {
     from_kernel_result! {
-        // SAFETY: `pdev` is guaranteed to be a valid, non-null pointer.
-        let device_id = unsafe { (*pdev).id };
-        let drv_data = P::probe(device_id)?;
+        // SAFETY: `pdev` is guaranteed to be a valid, non-null pointer, which remains valid
+        // for the duration of this call.
+        let mut dev = unsafe { PlatformDevice::from_ptr(pdev) };
+        let match_data = dev.match_data::<P::MatchData>();
+        let drv_data = P::probe(&mut dev, match_data)?;
         let drv_data = drv_data.into_pointer() as *mut c_types::c_void;
         // SAFETY: `pdev` is guaranteed to be a valid, non-null pointer.
         unsafe {
             rust_helper_platform_set_drvdata(pdev, drv_data);
         }
+        // SAFETY: `pdev` is valid and being bound to a driver registered by `Registration`.
+        let reg = unsafe { registration_of(pdev) };
+        reg.probe_count.fetch_add(1, Ordering::Relaxed);
         Ok(0)
     }
 }
@@ -62,8 +141,9 @@ extern "C" fn remove_callback<P: PlatformDriver>(
     pdev: *mut bindings::platform_device,
 ) -> c_types::c_int {
     from_kernel_result! {
-        // SAFETY: `pdev` is guaranteed to be a valid, non-null pointer.
-        let device_id = unsafe { (*pdev).id };
+        // SAFETY: `pdev` is guaranteed to be a valid, non-null pointer, which remains valid
+        // for the duration of this call.
+        let mut dev = unsafe { PlatformDevice::from_ptr(pdev) };
         // SAFETY: `pdev` is guaranteed to be a valid, non-null pointer.
         let ptr = unsafe { rust_helper_platform_get_drvdata(pdev) };
         // SAFETY:
@@ -73,16 +153,35 @@ extern "C" fn remove_callback<P: PlatformDriver>(
         //     `remove` is the canonical kernel location to free driver data. so OK
         //     to convert the pointer back to a Rust structure here.
         let drv_data = unsafe { P::DrvData::from_pointer(ptr) };
-        P::remove(device_id, drv_data)?;
+        let ret = P::remove(&mut dev, drv_data);
+        // SAFETY: `pdev` is valid and bound to a driver registered by `Registration`.
+        let reg = unsafe { registration_of(pdev) };
+        // The driver core unbinds the device even if `remove` fails.
+        reg.remove_count.fetch_add(1, Ordering::Relaxed);
+        ret?;
         Ok(0)
     }
 }
 
+extern "C" fn shutdown_callback<P: PlatformDriver>(pdev: *mut bindings::platform_device) {
+    // SAFETY: `pdev` is guaranteed to be a valid, non-null pointer, which remains valid
+    // for the duration of this call.
+    let mut dev = unsafe { PlatformDevice::from_ptr(pdev) };
+    // SAFETY: `pdev` is guaranteed to be a valid, non-null pointer.
+    let ptr = unsafe { rust_helper_platform_get_drvdata(pdev) };
+    // SAFETY: `shutdown` is only called for bound devices, so `ptr` was returned by
+    // `P::DrvData::into_pointer` in `probe`. `P::DrvData::from_pointer` is only called in
+    // `remove`, which the kernel does not run concurrently with `shutdown`.
+    let drv_data = unsafe { P::DrvData::borrow(ptr) };
+    P::shutdown(&mut dev, &drv_data);
+}
+
 impl Registration {
     fn register<P: PlatformDriver>(
         self: Pin<&mut Self>,
         name: &'static CStr,
         of_match_table: Option<&'static OfMatchTable>,
+        match_data_type: Option<TypeId>,
         module: &'static crate::ThisModule,
     ) -> Result {
         // SAFETY: We must ensure that we never move out of `this`.
@@ -91,24 +190,28 @@ impl Registration {
             // Already registered.
             return Err(Error::EINVAL);
         }
-        this.pdrv.driver.name = name.as_char_ptr();
-        if let Some(tbl) = of_match_table {
-            this.pdrv.driver.of_match_table = tbl.as_ptr();
+        this.match_data_type = match_data_type;
+        let pdrv = this.pdrv.get();
+        // SAFETY: `pdrv` is valid, and not registered yet, so the kernel does not access it
+        // concurrently.
+        unsafe {
+            (*pdrv).driver.name = name.as_char_ptr();
+            if let Some(tbl) = of_match_table {
+                (*pdrv).driver.of_match_table = tbl.as_ptr();
+            }
+            (*pdrv).probe = Some(probe_callback::<P>);
+            (*pdrv).remove = Some(remove_callback::<P>);
+            (*pdrv).shutdown = Some(shutdown_callback::<P>);
         }
-        this.pdrv.probe = Some(probe_callback::<P>);
-        this.pdrv.remove = Some(remove_callback::<P>);
         // SAFETY:
         //   - `this.pdrv` lives at least until the call to `platform_driver_unregister()` returns.
         //   - `name` pointer has static lifetime.
         //   - `module.0` lives at least as long as the module.
-        //   - `probe()` and `remove()` are static functions.
+        //   - `probe()`, `remove()` and `shutdown()` are static functions.
         //   - `of_match_table` is either a raw pointer with static lifetime,
         //      as guaranteed by the [`of::OfMatchTable::as_ptr()`] return type,
         //      or null.
-        let ret = unsafe { bindings::__platform_driver_register(&mut this.pdrv, module.0) };
-        if ret < 0 {
-            return Err(Error::from_kernel_errno(ret));
-        }
+        to_result(unsafe { bindings::__platform_driver_register(pdrv, module.0) })?;
         this.registered = true;
         Ok(())
     }
@@ -122,9 +225,45 @@ impl Registration {
         module: &'static crate::ThisModule,
     ) -> Result<Pin<Box<Self>>> {
         let mut r = Pin::from(Box::try_new(Self::default())?);
-        r.as_mut().register::<P>(name, of_match_tbl, module)?;
+        r.as_mut().register::<P>(name, of_match_tbl, None, module)?;
+        Ok(r)
+    }
+
+    /// Registers a platform device with an OF match table the entries of which all have match
+    /// data of type [`PlatformDriver::MatchData`].
+    ///
+    /// The data of the entry that matched a device is passed to [`PlatformDriver::probe`], and
+    /// can also be retrieved with [`PlatformDevice::match_data`].
+    ///
+    /// Returns a pinned heap-allocated representation of the registration.
+    pub fn new_pinned_with_data<P: PlatformDriver>(
+        name: &'static CStr,
+        of_match_tbl: &'static OfMatchDataTable<P::MatchData>,
+        module: &'static crate::ThisModule,
+    ) -> Result<Pin<Box<Self>>> {
+        let mut r = Pin::from(Box::try_new(Self::default())?);
+        let match_data_type = Some(TypeId::of::<P::MatchData>());
+        r.as_mut()
+            .register::<P>(name, Some(&**of_match_tbl), match_data_type, module)?;
         Ok(r)
     }
+
+    /// Returns the number of times a device was successfully bound to the driver.
+    pub fn probe_count(&self) -> usize {
+        self.probe_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of times a device was unbound from the driver.
+    pub fn remove_count(&self) -> usize {
+        self.remove_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the driver is currently bound to at least one device.
+    ///
+    /// This is only a snapshot: devices may be bound or unbound concurrently, e.g. through sysfs.
+    pub fn is_bound(&self) -> bool {
+        self.probe_count() > self.remove_count()
+    }
 }
 
 impl Drop for Registration {
@@ -133,8 +272,317 @@ impl Drop for Registration {
             // SAFETY: if `registered` is true, then `self.pdev` was registered
             // previously, which means `platform_driver_unregister` is always
             // safe to call.
-            unsafe { bindings::platform_driver_unregister(&mut self.pdrv) }
+            unsafe { bindings::platform_driver_unregister(self.pdrv.get()) }
+        }
+    }
+}
+
+/// A registration of a platform device that is not described by firmware.
+///
+/// The device is added when the registration is created, and drivers whose name matches the name
+/// of the device are then probed for it, including drivers registered earlier. The device is
+/// unbound from its driver and removed when the registration is dropped.
+///
+/// # Invariants
+///
+/// The pointer `DeviceRegistration::ptr` is non-null and points to a device added with
+/// `platform_device_add`.
+pub struct DeviceRegistration {
+    ptr: *mut bindings::platform_device,
+}
+
+// SAFETY: The registration only allows the device to be removed, which can be done from any
+// thread.
+unsafe impl Send for DeviceRegistration {}
+
+// SAFETY: The registration does not expose any of its state through shared references.
+unsafe impl Sync for DeviceRegistration {}
+
+impl DeviceRegistration {
+    /// Adds a platform device called `name`, without an id.
+    ///
+    /// Unless the matching driver probes asynchronously, it is bound to the device before this
+    /// returns.
+    pub fn new(name: &CStr) -> Result<Self> {
+        // SAFETY: `name` is a valid NUL-terminated string, which `platform_device_alloc` copies.
+        let ptr = unsafe {
+            bindings::platform_device_alloc(name.as_char_ptr(), bindings::PLATFORM_DEVID_NONE)
+        };
+        if ptr.is_null() {
+            return Err(Error::ENOMEM);
+        }
+        // SAFETY: `ptr` was just allocated, and not added yet.
+        if let Err(e) = to_result(unsafe { bindings::platform_device_add(ptr) }) {
+            // SAFETY: `ptr` was allocated above and failed to be added, so we own the only
+            // reference to it.
+            unsafe { bindings::platform_device_put(ptr) };
+            return Err(e);
+        }
+        // INVARIANTS: `ptr` was added above.
+        Ok(Self { ptr })
+    }
+}
+
+impl Drop for DeviceRegistration {
+    fn drop(&mut self) {
+        // SAFETY: `self.ptr` was added by `new` by the type invariants, and is only removed here.
+        unsafe { bindings::platform_device_unregister(self.ptr) };
+    }
+}
+
+/// A platform device.
+///
+/// # Invariants
+///
+/// The field `ptr` is non-null and valid for the lifetime of the object.
+pub struct PlatformDevice {
+    ptr: *mut bindings::platform_device,
+}
+
+impl PlatformDevice {
+    /// Creates a new [`PlatformDevice`] from a raw pointer.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null and valid. It must remain valid for the lifetime of the returned
+    /// instance.
+    unsafe fn from_ptr(ptr: *mut bindings::platform_device) -> Self {
+        // INVARIANTS: The safety requirements of the function ensure the lifetime invariant.
+        Self { ptr }
+    }
+
+    /// Returns the raw `struct platform_device` pointer.
+    pub(crate) fn to_ptr(&self) -> *mut bindings::platform_device {
+        self.ptr
+    }
+
+    /// Returns the id of the platform device.
+    pub fn id(&self) -> i32 {
+        // SAFETY: By the type invariants, we know that `self.ptr` is non-null and valid.
+        unsafe { (*self.ptr).id }
+    }
+
+    /// Returns the interrupt `index` of the device, as a Linux virtual interrupt number, e.g. to
+    /// request it.
+    ///
+    /// Fails with `ENXIO` if the device has no such interrupt, and with `EPROBE_DEFER` if its
+    /// interrupt controller is not probed yet, which should be returned by
+    /// [`PlatformDriver::probe`] so that it is retried later.
+    pub fn irq(&self, index: u32) -> Result<u32> {
+        // SAFETY: By the type invariants, we know that `self.ptr` is non-null and valid.
+        irq_result(unsafe { bindings::platform_get_irq(self.ptr, index) })
+    }
+
+    /// Returns the interrupt of the device named `name`, e.g. in the `interrupt-names` property of
+    /// its devicetree node.
+    ///
+    /// See [`PlatformDevice::irq`].
+    pub fn irq_by_name(&self, name: &CStr) -> Result<u32> {
+        // SAFETY: By the type invariants, we know that `self.ptr` is non-null and valid, and
+        // `name` is `NUL`-terminated.
+        irq_result(unsafe { bindings::platform_get_irq_byname(self.ptr, name.as_char_ptr()) })
+    }
+
+    /// Reads the `u32` value of the device property `name`.
+    ///
+    /// Uses the firmware-agnostic (`fwnode`) property API, so it works for devices described by
+    /// both devicetree and ACPI.
+    pub fn property_read_u32(&self, name: &CStr) -> Result<u32> {
+        let mut val = 0;
+        // SAFETY: `raw_device()` is valid as guaranteed by the type invariants, `name` is
+        // `NUL`-terminated and `val` is a valid location for a single `u32`.
+        to_result(unsafe {
+            bindings::device_property_read_u32_array(
+                self.raw_device(),
+                name.as_char_ptr(),
+                &mut val,
+                1,
+            )
+        })?;
+        Ok(val)
+    }
+
+    /// Reads the string value of the device property `name`.
+    ///
+    /// See [`PlatformDevice::property_read_u32`].
+    pub fn property_read_string(&self, name: &CStr) -> Result<&CStr> {
+        let mut val: *const c_types::c_char = core::ptr::null();
+        // SAFETY: `raw_device()` is valid as guaranteed by the type invariants, `name` is
+        // `NUL`-terminated and `val` is a valid location for a single pointer.
+        to_result(unsafe {
+            bindings::device_property_read_string(self.raw_device(), name.as_char_ptr(), &mut val)
+        })?;
+        // SAFETY: On success, `val` points to a `NUL`-terminated string owned by the firmware node
+        // of the device, which lives at least as long as the device is bound.
+        Ok(unsafe { CStr::from_char_ptr(val) })
+    }
+
+    /// Returns whether the device has the property `name`.
+    ///
+    /// Boolean properties are true when they are present. See
+    /// [`PlatformDevice::property_read_u32`].
+    pub fn property_read_bool(&self, name: &CStr) -> bool {
+        // SAFETY: `raw_device()` is valid as guaranteed by the type invariants and `name` is
+        // `NUL`-terminated.
+        unsafe { bindings::device_property_present(self.raw_device(), name.as_char_ptr()) }
+    }
+
+    /// Reads the nvmem cell `name` of the device, e.g. factory calibration data stored in an
+    /// EEPROM or in fuses.
+    ///
+    /// The cell is looked up by name, e.g. in the `nvmem-cell-names` devicetree property of the
+    /// device. It is released before this returns, and the returned bytes are owned by the caller.
+    #[cfg(CONFIG_NVMEM)]
+    pub fn nvmem_cell_read(&self, name: &CStr) -> Result<Vec<u8>> {
+        // SAFETY: `raw_device()` is valid as guaranteed by the type invariants and `name` is
+        // `NUL`-terminated.
+        let cell = from_kernel_err_ptr(unsafe {
+            bindings::nvmem_cell_get(self.raw_device(), name.as_char_ptr())
+        })?;
+        let _put = ScopeGuard::new(|| {
+            // SAFETY: `cell` was returned by `nvmem_cell_get` above, and is released only once.
+            unsafe { bindings::nvmem_cell_put(cell) };
+        });
+
+        let mut len = 0;
+        // SAFETY: `cell` is valid until it is released, and `len` is a valid location.
+        let buf = from_kernel_err_ptr(unsafe { bindings::nvmem_cell_read(cell, &mut len) })?;
+        let _free = ScopeGuard::new(|| {
+            // SAFETY: `buf` was allocated by `nvmem_cell_read` with `kmalloc`, and is freed only
+            // once.
+            unsafe { bindings::kfree(buf) };
+        });
+
+        let mut data = Vec::new();
+        data.try_reserve_exact(len as usize)?;
+        // SAFETY: `nvmem_cell_read` returned a buffer of `len` bytes.
+        let bytes = unsafe { core::slice::from_raw_parts(buf as *const u8, len as _) };
+        data.extend_from_slice(bytes);
+        Ok(data)
+    }
+
+    /// Returns the entry of the driver's OF match table that matched the device, if any.
+    #[cfg(CONFIG_OF)]
+    fn matched_of_device_id(&self) -> Option<&bindings::of_device_id> {
+        let dev = self.raw_device();
+        // SAFETY: `dev` is valid as guaranteed by the type invariants. If non-null, its driver
+        // remains valid while the device is bound.
+        let driver = unsafe { (*dev).driver };
+        if driver.is_null() {
+            return None;
         }
+        // SAFETY: `driver` is non-null and valid, and its `of_match_table` is either null or a
+        // sentinel-terminated array with static lifetime, as set up by `Registration::register`.
+        let id = unsafe { bindings::of_match_device((*driver).of_match_table, dev) };
+        // SAFETY: If non-null, `id` points into the `of_match_table` of the driver, which has
+        // static lifetime.
+        unsafe { id.as_ref() }
+    }
+
+    #[cfg(not(CONFIG_OF))]
+    fn matched_of_device_id(&self) -> Option<&bindings::of_device_id> {
+        None
+    }
+
+    /// Returns the compatible string of the OF match table entry that matched the device.
+    ///
+    /// This tells which of the compatible strings the driver declared bound it to the device.
+    /// Returns [`None`] if the device was not matched via the OF match table, e.g. because it is
+    /// not described by a devicetree.
+    pub fn matched_compatible(&self) -> Option<&CStr> {
+        let id = self.matched_of_device_id()?;
+        // SAFETY: The `compatible` field of `of_device_id` is `NUL`-terminated, as guaranteed by
+        // the `ConstOfMatchTable` constructor, and lives as long as `id`.
+        Some(unsafe { CStr::from_char_ptr(id.compatible.as_ptr()) })
+    }
+
+    /// Returns the match data of the OF match table entry that matched the device.
+    ///
+    /// Returns [`None`] if the device was not matched via the OF match table, or if the matching
+    /// entry has no data. See [`crate::of::ConstOfMatchTable::new_const_with_data`].
+    ///
+    /// # Safety
+    ///
+    /// All the entries of the OF match table of the driver that have data must have data of type
+    /// `T`, e.g. because the table was created by
+    /// [`crate::of::ConstOfMatchTable::new_const_with_data::<T>`].
+    pub unsafe fn of_match_data<T: Sync>(&self) -> Option<&'static T> {
+        let id = self.matched_of_device_id()?;
+        // SAFETY: If non-null, `data` points to a `T` with static lifetime, as per the safety
+        // requirements of this function.
+        unsafe { (id.data as *const T).as_ref() }
+    }
+
+    /// Returns the match data of the OF match table entry that matched the device.
+    ///
+    /// Returns [`None`] if the device was not matched via the OF match table, or if the driver was
+    /// not registered with an OF match table with data of type `T`, see
+    /// [`Registration::new_pinned_with_data`]. This is the data passed to
+    /// [`PlatformDriver::probe`], for use in the other callbacks of the driver.
+    pub fn match_data<T: Sync + 'static>(&self) -> Option<&'static T> {
+        // SAFETY: `self.ptr` is valid by the type invariants, and a `PlatformDevice` only exists
+        // in the callbacks of a driver registered through `Registration`.
+        let reg = unsafe { registration_of(self.ptr) };
+        if reg.match_data_type != Some(TypeId::of::<T>()) {
+            return None;
+        }
+        // SAFETY: The driver was registered by `Registration::new_pinned_with_data::<P>` with
+        // `P::MatchData` being `T`, so all the entries of its OF match table have data of type
+        // `T`, as per the type invariants of `OfMatchDataTable<T>`.
+        unsafe { self.of_match_data() }
+    }
+
+    /// Gets the device-managed pin control handle of the device.
+    ///
+    /// The driver core already selects the [`crate::pinctrl::STATE_DEFAULT`] state, if the
+    /// device has one, before calling [`PlatformDriver::probe`]. The handle is needed to select
+    /// other states, e.g. [`crate::pinctrl::STATE_IDLE`]. It borrows the device, so it can only be
+    /// used during the driver callback it was obtained in. See [`PinCtrl::devm_get`].
+    #[cfg(CONFIG_PINCTRL)]
+    pub fn devm_pinctrl_get(&self) -> Result<PinCtrl<'_>> {
+        // SAFETY: A `PlatformDevice` only exists in the callbacks of a driver while it is bound
+        // to the device, and the handle borrows it.
+        unsafe { PinCtrl::devm_get(self) }
+    }
+
+    /// Adds the OPP table of the device, as described by the `operating-points-v2` property of
+    /// its devicetree node, and returns it.
+    ///
+    /// The table is removed when the driver is unbound from the device. See
+    /// [`OppTable::devm_of_add`].
+    #[cfg(CONFIG_PM_OPP)]
+    pub fn devm_pm_opp_of_add_table(&self) -> Result<OppTable> {
+        OppTable::devm_of_add(self)
+    }
+
+    /// Returns the devicetree node of the device, or [`None`] if the device is not described by
+    /// a devicetree.
+    ///
+    /// See [`PlatformDriver::probe`] for how drivers can support devices without a node.
+    ///
+    /// The returned [`DeviceNode`] holds a reference to the node, so it remains valid after the
+    /// device is unbound or removed.
+    pub fn of_node(&self) -> Option<DeviceNode> {
+        // SAFETY: `raw_device()` is valid as guaranteed by the type invariants, and its `of_node`
+        // is either null or valid while the device exists.
+        unsafe { DeviceNode::get((*self.raw_device()).of_node) }
+    }
+
+    /// Takes a reference to the underlying `struct device`.
+    ///
+    /// Unlike the [`PlatformDevice`] itself, which is only valid during the driver callbacks, the
+    /// returned [`DeviceRef`] can be stored in the driver data: it keeps the device from being
+    /// freed until it is dropped.
+    pub fn get(&self) -> DeviceRef {
+        DeviceRef::from_dev(self)
+    }
+}
+
+// SAFETY: The device returned by `raw_device` is the raw platform device.
+unsafe impl Device for PlatformDevice {
+    fn raw_device(&self) -> *mut bindings::device {
+        // SAFETY: By the type invariants, we know that `self.ptr` is non-null and valid.
+        unsafe { &mut (*self.ptr).dev }
     }
 }
 
@@ -152,15 +600,98 @@ pub trait PlatformDriver {
     /// driver writers to use pinned or self-referential data structures.
     type DrvData: PointerWrapper;
 
+    /// The type of the data of the entries of the OF match table, e.g. an enum of the variants of
+    /// the device, which tells how to initialise each of them.
+    ///
+    /// Only drivers registered with [`Registration::new_pinned_with_data`] receive match data.
+    type MatchData: Sync + 'static = ();
+
     /// Platform driver probe.
     ///
     /// Called when a new platform device is added or discovered.
     /// Implementers should attempt to initialize the device here.
-    fn probe(device_id: i32) -> Result<Self::DrvData>;
+    ///
+    /// `match_data` is the data of the entry of the OF match table that matched the device, or
+    /// [`None`] if the device was not matched by the table, or if the driver was not registered
+    /// with a table with data, see [`Registration::new_pinned_with_data`].
+    ///
+    /// # Devices without a devicetree node
+    ///
+    /// Devices are not necessarily described by a devicetree: on legacy or minimal platforms, the
+    /// platform code may register them by name, in which case the driver is bound to them because
+    /// the name of the device matches the name of the driver. Such devices have no devicetree
+    /// node, so [`PlatformDevice::of_node`] returns [`None`], and neither do they match the OF
+    /// match table, so `match_data` is [`None`] as well. Drivers that support them fall back to
+    /// compiled-in defaults in that case, while still rejecting devices that have a node but no
+    /// match data, which indicates a bug in the driver's table:
+    ///
+    /// ```ignore
+    /// # use kernel::prelude::*;
+    /// # use kernel::platdev::PlatformDevice;
+    /// struct Config {
+    ///     fifo_depth: u32,
+    /// }
+    ///
+    /// const DEFAULT_CONFIG: Config = Config { fifo_depth: 16 };
+    ///
+    /// fn config(
+    ///     pdev: &PlatformDevice,
+    ///     match_data: Option<&'static Config>,
+    /// ) -> Result<&'static Config> {
+    ///     match match_data {
+    ///         Some(cfg) => Ok(cfg),
+    ///         None if pdev.of_node().is_none() => Ok(&DEFAULT_CONFIG),
+    ///         None => Err(Error::EINVAL),
+    ///     }
+    /// }
+    /// ```
+    fn probe(
+        pdev: &mut PlatformDevice,
+        match_data: Option<&'static Self::MatchData>,
+    ) -> Result<Self::DrvData>;
 
     /// Platform driver remove.
     ///
     /// Called when a platform device is removed.
     /// Implementers should prepare the device for complete removal here.
-    fn remove(device_id: i32, drv_data: Self::DrvData) -> Result;
+    fn remove(pdev: &mut PlatformDevice, drv_data: Self::DrvData) -> Result;
+
+    /// Platform driver shutdown.
+    ///
+    /// Called when the system is shut down or rebooted, in which case [`PlatformDriver::remove`]
+    /// may not be called at all. Implementers should quiesce the device here, e.g. stop DMA and
+    /// disable interrupts. The default implementation does nothing.
+    fn shutdown(
+        _pdev: &mut PlatformDevice,
+        _drv_data: &<<Self::DrvData as PointerWrapper>::Borrowed as Deref>::Target,
+    ) {
+    }
+}
+
+/// Trait for implementers of platform drivers that have no driver data.
+///
+/// Some drivers only initialise their device in [`SimplePlatformDriver::probe`], e.g. by setting
+/// up a clock divider once, and have nothing to release when the device is removed. Implementing
+/// this trait instead of [`PlatformDriver`] spares them from declaring driver data and a `remove`
+/// callback: they implement [`PlatformDriver`] with `()` as [`PlatformDriver::DrvData`] and a
+/// `remove` callback that does nothing, so they are registered with [`Registration::new_pinned`]
+/// like any other driver.
+pub trait SimplePlatformDriver {
+    /// Platform driver probe.
+    ///
+    /// Called when a new platform device is added or discovered.
+    /// Implementers should initialize the device here.
+    fn probe(pdev: &mut PlatformDevice) -> Result;
+}
+
+impl<T: SimplePlatformDriver> PlatformDriver for T {
+    type DrvData = ();
+
+    fn probe(pdev: &mut PlatformDevice, _match_data: Option<&'static ()>) -> Result {
+        <T as SimplePlatformDriver>::probe(pdev)
+    }
+
+    fn remove(_pdev: &mut PlatformDevice, _drv_data: ()) -> Result {
+        Ok(())
+    }
 }
@@ -19,10 +19,12 @@ pub use macros::{module, module_misc_device};
 
 pub use super::build_assert;
 
-pub use super::{pr_alert, pr_crit, pr_emerg, pr_err, pr_info, pr_notice, pr_warn};
+pub use super::{dev_err, dev_info, dev_notice, dev_warn};
+
+pub use super::{pr_alert, pr_crit, pr_emerg, pr_err, pr_err_errno, pr_info, pr_notice, pr_warn};
 
 pub use super::static_assert;
 
-pub use super::{Error, KernelModule, Result};
+pub use super::{Error, KernelModule, Result, ResultExt};
 
 pub use crate::traits::TryPin;
@@ -11,6 +11,7 @@ use core::fmt;
 
 use crate::bindings;
 use crate::c_types::{c_char, c_void};
+use crate::error::Error;
 
 // Called from `vsprintf` with format specifier `%pA`.
 #[no_mangle]
@@ -159,6 +160,42 @@ pub fn call_printk_cont(args: fmt::Arguments<'_>) {
     }
 }
 
+/// Prints an error-level message followed by the name of `err` via the kernel's [`printk`],
+/// without going through [`core::fmt`].
+///
+/// Public but hidden since it should only be used from public macros.
+///
+/// # Safety
+///
+/// The module name and `msg` must be null-terminated.
+///
+/// [`printk`]: ../../../../include/linux/printk.h
+#[doc(hidden)]
+pub unsafe fn call_printk_errno(module_name: &[u8], msg: &[u8], err: Error) {
+    // The format strings start with `KERN_ERR`.
+    match err.name() {
+        // SAFETY: All the strings are null-terminated, as per the safety requirements of this
+        // function and the return type of `Error::name`.
+        Some(name) => unsafe {
+            bindings::printk(
+                b"\x013%s: %s: %s\n\0".as_ptr() as _,
+                module_name.as_ptr(),
+                msg.as_ptr(),
+                name.as_char_ptr(),
+            );
+        },
+        // SAFETY: As above.
+        None => unsafe {
+            bindings::printk(
+                b"\x013%s: %s: error %d\n\0".as_ptr() as _,
+                module_name.as_ptr(),
+                msg.as_ptr(),
+                err.to_kernel_errno(),
+            );
+        },
+    }
+}
+
 /// Performs formatting and forwards the string to [`call_printk`].
 ///
 /// Public but hidden since it should only be used from public macros.
@@ -308,6 +345,46 @@ macro_rules! pr_err (
     )
 );
 
+/// Prints an error-level message (level 3) followed by the name of an error.
+///
+/// Prints `$msg`, then the name of the [`Error`] `$err`, e.g. `failed: ENOMEM`, or its number if
+/// it has no name. Unlike `pr_err!("failed: {:?}\n", err)`, this does not use the formatting
+/// machinery of [`core::fmt`], which keeps the many log sites of error paths small. `$msg` is a
+/// string literal, without a trailing newline, which is added.
+///
+/// [`Error`]: crate::Error
+///
+/// # Examples
+///
+/// ```
+/// # use kernel::prelude::*;
+/// pr_err_errno!(Error::ENOMEM, "failed");
+/// ```
+#[cfg(not(testlib))]
+#[macro_export]
+macro_rules! pr_err_errno (
+    ($err:expr, $msg:literal) => (
+        // SAFETY: All `__LOG_PREFIX`s are null-terminated, see `print_macro`, and so is the
+        // message.
+        unsafe {
+            $crate::print::call_printk_errno(
+                crate::__LOG_PREFIX,
+                concat!($msg, "\0").as_bytes(),
+                $err,
+            );
+        }
+    )
+);
+
+/// Stub for doctests
+#[cfg(testlib)]
+#[macro_export]
+macro_rules! pr_err_errno (
+    ($err:expr, $msg:literal) => (
+        ()
+    );
+);
+
 /// Prints a warning-level message (level 4).
 ///
 /// Use this level for warning conditions.
@@ -6,17 +6,14 @@
 
 use core::convert::TryInto;
 
-use crate::{bindings, c_types, error};
+use crate::{bindings, c_types, error, error::to_result};
 
 /// Fills a byte slice with random bytes generated from the kernel's CSPRNG.
 ///
 /// Ensures that the CSPRNG has been seeded before generating any random bytes,
 /// and will block until it is ready.
 pub fn getrandom(dest: &mut [u8]) -> error::Result {
-    let res = unsafe { bindings::wait_for_random_bytes() };
-    if res != 0 {
-        return Err(error::Error::from_kernel_errno(res));
-    }
+    to_result(unsafe { bindings::wait_for_random_bytes() })?;
 
     unsafe {
         bindings::get_random_bytes(
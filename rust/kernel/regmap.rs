@@ -0,0 +1,1389 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Register map access API.
+//!
+//! C header: [`include/linux/regmap.h`](../../../../include/linux/regmap.h)
+
+use crate::{
+    bindings, c_types,
+    device::{Device, DeviceRef},
+    error::{from_kernel_err_ptr, to_result, to_result_value, Error},
+    from_kernel_result, Result,
+};
+use alloc::vec::Vec;
+use core::{
+    convert::{TryFrom, TryInto},
+    ptr,
+};
+
+#[cfg(CONFIG_REGMAP_I2C)]
+use crate::i2c::I2cClient;
+#[cfg(CONFIG_REGMAP_MMIO)]
+use crate::{platdev::PlatformDevice, str::CStr};
+
+extern "C" {
+    #[allow(improper_ctypes)]
+    fn rust_helper_devm_regmap_init(
+        dev: *mut bindings::device,
+        bus: *const bindings::regmap_bus,
+        bus_context: *mut c_types::c_void,
+        config: *const bindings::regmap_config,
+    ) -> *mut bindings::regmap;
+
+    fn rust_helper_usleep_range(min: c_types::c_ulong, max: c_types::c_ulong);
+
+    // Declared in `drivers/base/regmap/internal.h` rather than in the public header.
+    fn regmap_readable(map: *mut bindings::regmap, reg: c_types::c_uint) -> bool;
+    fn regmap_volatile(map: *mut bindings::regmap, reg: c_types::c_uint) -> bool;
+}
+
+#[cfg(CONFIG_REGMAP_MMIO)]
+extern "C" {
+    #[allow(improper_ctypes)]
+    fn rust_helper_devm_regmap_init_mmio(
+        dev: *mut bindings::device,
+        regs: *mut c_types::c_void,
+        config: *const bindings::regmap_config,
+    ) -> *mut bindings::regmap;
+
+    #[allow(improper_ctypes)]
+    fn rust_helper_devm_regmap_init_mmio_clk(
+        dev: *mut bindings::device,
+        clk_id: *const c_types::c_char,
+        regs: *mut c_types::c_void,
+        config: *const bindings::regmap_config,
+    ) -> *mut bindings::regmap;
+}
+
+#[cfg(CONFIG_REGMAP_I2C)]
+extern "C" {
+    #[allow(improper_ctypes)]
+    fn rust_helper_devm_regmap_init_i2c(
+        client: *mut bindings::i2c_client,
+        config: *const bindings::regmap_config,
+    ) -> *mut bindings::regmap;
+}
+
+/// A lock that serialises accesses to a register map, in place of the regmap's own lock.
+///
+/// The regmap calls [`RegmapLock::lock`] before, and [`RegmapLock::unlock`] after, every access to
+/// the device or to its internal state, so the lock can be shared with other subsystems, e.g. a
+/// lock of the parent device that must also be held while accessing the bus. Since regmap
+/// operations are called with the lock held, the lock must not be held by the caller of a regmap
+/// operation, or the operation deadlocks. If [`RegmapLock::lock`] sleeps, the regmap must only be
+/// used from process context.
+///
+/// # Safety
+///
+/// Implementers must ensure that at most one thread is between a call to [`RegmapLock::lock`] and
+/// the matching call to [`RegmapLock::unlock`] at any time, since [`Regmap`] relies on this to be
+/// usable concurrently from several threads.
+pub unsafe trait RegmapLock: Sync {
+    /// Acquires the lock, waiting for it to be released if it is held.
+    fn lock(&self);
+
+    /// Releases the lock, which was acquired by the current thread with [`RegmapLock::lock`].
+    fn unlock(&self);
+}
+
+unsafe extern "C" fn lock_callback<L: RegmapLock>(arg: *mut c_types::c_void) {
+    // SAFETY: `arg` was set to a `&'static L` by `RegmapConfig::custom_lock::<L>`.
+    let lock = unsafe { &*(arg as *const L) };
+    lock.lock();
+}
+
+unsafe extern "C" fn unlock_callback<L: RegmapLock>(arg: *mut c_types::c_void) {
+    // SAFETY: `arg` was set to a `&'static L` by `RegmapConfig::custom_lock::<L>`.
+    let lock = unsafe { &*(arg as *const L) };
+    lock.unlock();
+}
+
+/// The callbacks of a custom regmap lock, as set by [`RegmapConfig::custom_lock`].
+#[derive(Clone)]
+struct CustomLock {
+    lock: unsafe extern "C" fn(*mut c_types::c_void),
+    unlock: unsafe extern "C" fn(*mut c_types::c_void),
+    arg: *mut c_types::c_void,
+}
+
+/// Register accesses implemented in software, for regmaps that are not on any bus.
+///
+/// This gives devices that do not fit any bus model, e.g. a register file emulated on top of
+/// other state or accessed through firmware calls, the caching, locking and fields of regmap.
+/// The callbacks are called with the regmap lock held, so they are serialised with each other.
+pub trait RegmapAccess: Sync {
+    /// Reads the value of register `reg`.
+    fn reg_read(&self, reg: u32) -> Result<u32>;
+
+    /// Writes `val` to register `reg`.
+    fn reg_write(&self, reg: u32, val: u32) -> Result;
+}
+
+unsafe extern "C" fn reg_read_callback<A: RegmapAccess>(
+    context: *mut c_types::c_void,
+    reg: c_types::c_uint,
+    val: *mut c_types::c_uint,
+) -> c_types::c_int {
+    from_kernel_result! {
+        // SAFETY: `context` was set to a `&'static A` by `RegmapConfig::reg_read_write::<A>`.
+        let access = unsafe { &*(context as *const A) };
+        let v = access.reg_read(reg)?;
+        // SAFETY: regmap passes a valid location for the value.
+        unsafe { *val = v };
+        Ok(0)
+    }
+}
+
+unsafe extern "C" fn reg_write_callback<A: RegmapAccess>(
+    context: *mut c_types::c_void,
+    reg: c_types::c_uint,
+    val: c_types::c_uint,
+) -> c_types::c_int {
+    from_kernel_result! {
+        // SAFETY: `context` was set to a `&'static A` by `RegmapConfig::reg_read_write::<A>`.
+        let access = unsafe { &*(context as *const A) };
+        access.reg_write(reg, val)?;
+        Ok(0)
+    }
+}
+
+/// The callbacks of software register accesses, as set by [`RegmapConfig::reg_read_write`].
+#[derive(Clone)]
+struct CustomAccess {
+    reg_read: RegReadFn,
+    reg_write: RegWriteFn,
+    context: *mut c_types::c_void,
+}
+
+type RegReadFn = unsafe extern "C" fn(
+    *mut c_types::c_void,
+    c_types::c_uint,
+    *mut c_types::c_uint,
+) -> c_types::c_int;
+
+type RegWriteFn =
+    unsafe extern "C" fn(*mut c_types::c_void, c_types::c_uint, c_types::c_uint) -> c_types::c_int;
+
+/// A predicate on register addresses, e.g. whether a register is volatile, as called by regmap.
+type RegPredicateFn = unsafe extern "C" fn(*mut bindings::device, c_types::c_uint) -> bool;
+
+unsafe extern "C" fn reg_predicate_callback<F: Fn(u32) -> bool + Copy>(
+    _dev: *mut bindings::device,
+    reg: c_types::c_uint,
+) -> bool {
+    // SAFETY: `F` is zero-sized, as checked by `reg_predicate`, so it has no bytes to initialise,
+    // and it is inhabited since a value of it was passed to `reg_predicate`.
+    let f: F = unsafe { core::mem::MaybeUninit::uninit().assume_init() };
+    f(reg)
+}
+
+/// Returns the trampoline calling `f` from regmap.
+///
+/// The trampolines have no context to find `f` in, so `f` must be zero-sized, i.e., a function
+/// item or a closure that captures nothing, which the trampoline can conjure up. This fails the
+/// build otherwise, e.g. for function pointers.
+fn reg_predicate<F: Fn(u32) -> bool + Copy + 'static>(_f: F) -> RegPredicateFn {
+    crate::build_assert!(
+        core::mem::size_of::<F>() == 0,
+        "register predicates must be functions or closures capturing nothing"
+    );
+    reg_predicate_callback::<F>
+}
+
+/// The type of the register cache of a regmap.
+///
+/// Equivalent to `enum regcache_type`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RegcacheType {
+    /// No cache: every access goes to the device (`REGCACHE_NONE`).
+    None,
+
+    /// A red-black tree of register blocks (`REGCACHE_RBTREE`).
+    ///
+    /// Only the registers that were written or read are cached, so this suits large, sparse
+    /// register spaces.
+    RbTree,
+
+    /// A flat array indexed by register address (`REGCACHE_FLAT`).
+    ///
+    /// The fastest cache, for small and dense register spaces: its size is set by
+    /// [`RegmapConfig::max_register`].
+    Flat,
+}
+
+impl RegcacheType {
+    fn to_raw(self) -> bindings::regcache_type {
+        match self {
+            RegcacheType::None => bindings::regcache_type_REGCACHE_NONE,
+            RegcacheType::RbTree => bindings::regcache_type_REGCACHE_RBTREE,
+            RegcacheType::Flat => bindings::regcache_type_REGCACHE_FLAT,
+        }
+    }
+}
+
+/// Configuration of a register map.
+///
+/// A configuration can be cloned, e.g. to derive the configurations of several similar devices
+/// from a common one. A clone builds the same `struct regmap_config` as the original; in
+/// particular, both refer to the same custom lock and register accessors, if any.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::regmap::RegmapConfig;
+/// let cfg = RegmapConfig::new(32, 32).reg_stride(4).max_register(0x10);
+/// let big_cfg = cfg.clone().max_register(0x40);
+/// ```
+#[derive(Clone)]
+pub struct RegmapConfig {
+    reg_bits: u32,
+    val_bits: u32,
+    reg_stride: u32,
+    max_register: u32,
+    use_single_read: bool,
+    use_single_write: bool,
+    read_flag_mask: u32,
+    write_flag_mask: u32,
+    pad_bits: u32,
+    reg_defaults_raw: Option<&'static [u8]>,
+    custom_lock: Option<CustomLock>,
+    can_multi_write: bool,
+    zero_flag_mask: bool,
+    cache_type: RegcacheType,
+    max_raw_read: usize,
+    max_raw_write: usize,
+    custom_access: Option<CustomAccess>,
+    volatile_reg: Option<RegPredicateFn>,
+    readable_reg: Option<RegPredicateFn>,
+    writeable_reg: Option<RegPredicateFn>,
+}
+
+impl RegmapConfig {
+    /// Creates a new configuration for registers of `reg_bits` address bits holding values of
+    /// `val_bits` bits.
+    pub fn new(reg_bits: u32, val_bits: u32) -> Self {
+        Self {
+            reg_bits,
+            val_bits,
+            reg_stride: 0,
+            max_register: 0,
+            use_single_read: false,
+            use_single_write: false,
+            read_flag_mask: 0,
+            write_flag_mask: 0,
+            pad_bits: 0,
+            reg_defaults_raw: None,
+            custom_lock: None,
+            can_multi_write: false,
+            zero_flag_mask: false,
+            cache_type: RegcacheType::None,
+            max_raw_read: 0,
+            max_raw_write: 0,
+            custom_access: None,
+            volatile_reg: None,
+            readable_reg: None,
+            writeable_reg: None,
+        }
+    }
+
+    /// Sets the distance between two consecutive valid register addresses.
+    ///
+    /// If not set, the stride is 1.
+    pub fn reg_stride(mut self, reg_stride: u32) -> Self {
+        self.reg_stride = reg_stride;
+        self
+    }
+
+    /// Sets the highest valid register address.
+    pub fn max_register(mut self, max_register: u32) -> Self {
+        self.max_register = max_register;
+        self
+    }
+
+    /// Makes regmap decompose multi-register reads (e.g. bulk reads) into single register reads.
+    ///
+    /// Needed for buses and devices that cannot do multi-register transfers.
+    pub fn use_single_read(mut self, use_single_read: bool) -> Self {
+        self.use_single_read = use_single_read;
+        self
+    }
+
+    /// Makes regmap decompose multi-register writes (e.g. bulk writes) into single register
+    /// writes.
+    ///
+    /// Needed for buses and devices that cannot do multi-register transfers.
+    pub fn use_single_write(mut self, use_single_write: bool) -> Self {
+        self.use_single_write = use_single_write;
+        self
+    }
+
+    /// Sets the mask that is OR'ed into the top byte of the register address on reads.
+    ///
+    /// Typically used by SPI devices that encode the transfer direction in the address.
+    pub fn read_flag_mask(mut self, read_flag_mask: u32) -> Self {
+        self.read_flag_mask = read_flag_mask;
+        self
+    }
+
+    /// Sets the mask that is OR'ed into the top byte of the register address on writes.
+    ///
+    /// Typically used by SPI devices that encode the transfer direction in the address.
+    pub fn write_flag_mask(mut self, write_flag_mask: u32) -> Self {
+        self.write_flag_mask = write_flag_mask;
+        self
+    }
+
+    /// Sets the number of padding bits between the register address and the value.
+    pub fn pad_bits(mut self, pad_bits: u32) -> Self {
+        self.pad_bits = pad_bits;
+        self
+    }
+
+    /// Sets the power-on values of the registers, from a flat image of the register space.
+    ///
+    /// `defaults` holds the values of consecutive registers starting at address 0, each encoded
+    /// as `ceil(val_bits / 8)` bytes in device byte order, e.g. as read from a firmware blob. It is
+    /// an alternative to listing (register, value) pairs that suits devices with large, contiguous
+    /// register spaces. The defaults are only used to initialise the register cache, so they have
+    /// no effect when the cache type of the regmap is `REGCACHE_NONE`; they are typically combined
+    /// with `REGCACHE_FLAT`, whose layout they match. Any trailing partial value is ignored.
+    ///
+    /// The kernel keeps referring to `defaults` for the lifetime of the regmap, hence the static
+    /// lifetime.
+    pub fn reg_defaults_raw(mut self, defaults: &'static [u8]) -> Self {
+        self.reg_defaults_raw = Some(defaults);
+        self
+    }
+
+    /// Makes the regmap use `lock` instead of its own lock to serialise accesses.
+    ///
+    /// Locking is delegated to `lock`, never disabled, so the regmap remains safe to use
+    /// concurrently. See [`RegmapLock`].
+    pub fn custom_lock<L: RegmapLock>(mut self, lock: &'static L) -> Self {
+        self.custom_lock = Some(CustomLock {
+            lock: lock_callback::<L>,
+            unlock: unlock_callback::<L>,
+            arg: lock as *const L as *mut c_types::c_void,
+        });
+        self
+    }
+
+    /// Declares that the device supports writing several, not necessarily consecutive, registers
+    /// in a single bus transfer.
+    ///
+    /// Only honoured by buses with raw byte-level transfers, e.g. I2C and SPI: multi-register
+    /// writes are then sent as one transfer of (register, value) pairs instead of one transfer per
+    /// register. It has no effect on memory-mapped regmaps, which always write registers one by
+    /// one.
+    pub fn can_multi_write(mut self, can_multi_write: bool) -> Self {
+        self.can_multi_write = can_multi_write;
+        self
+    }
+
+    /// Makes regmap use the read and write flag masks set in the configuration even if both are
+    /// zero.
+    ///
+    /// By default, when neither [`RegmapConfig::read_flag_mask`] nor
+    /// [`RegmapConfig::write_flag_mask`] is set, the defaults of the bus are used instead, e.g. the
+    /// SPI bus sets the top bit of the address on reads. This is needed for devices on such buses
+    /// that do not encode the transfer direction in the address at all. Buses without default
+    /// masks, e.g. I2C and MMIO, are not affected.
+    pub fn zero_flag_mask(mut self, zero_flag_mask: bool) -> Self {
+        self.zero_flag_mask = zero_flag_mask;
+        self
+    }
+
+    /// Sets the type of the register cache.
+    ///
+    /// If not set, registers are not cached, and every access goes to the device.
+    pub fn cache_type(mut self, cache_type: RegcacheType) -> Self {
+        self.cache_type = cache_type;
+        self
+    }
+
+    /// Sets the maximum number of bytes the bus can read in a single raw transfer.
+    ///
+    /// Bulk reads larger than this are automatically split by regmap into several transfers of
+    /// at most this size. If not set, or set to `0`, the limit of the bus applies, e.g. that of the
+    /// I2C adapter, if any.
+    pub fn max_raw_read(mut self, max_raw_read: usize) -> Self {
+        self.max_raw_read = max_raw_read;
+        self
+    }
+
+    /// Sets the maximum number of bytes the bus can write in a single raw transfer.
+    ///
+    /// Bulk writes larger than this are automatically split by regmap into several transfers of
+    /// at most this size. If not set, or set to `0`, the limit of the bus applies, e.g. that of the
+    /// I2C adapter, if any.
+    pub fn max_raw_write(mut self, max_raw_write: usize) -> Self {
+        self.max_raw_write = max_raw_write;
+        self
+    }
+
+    /// Makes the regmap access registers through `access` instead of a bus.
+    ///
+    /// Only used by regmaps created with [`Regmap::init_with_config`], which requires it. See
+    /// [`RegmapAccess`].
+    pub fn reg_read_write<A: RegmapAccess>(mut self, access: &'static A) -> Self {
+        self.custom_access = Some(CustomAccess {
+            reg_read: reg_read_callback::<A>,
+            reg_write: reg_write_callback::<A>,
+            context: access as *const A as *mut c_types::c_void,
+        });
+        self
+    }
+
+    /// Sets the predicate telling whether a register is volatile, i.e., whether its value may
+    /// change without being written to, e.g. a status register.
+    ///
+    /// Volatile registers are never cached: they are always read from the device. If not set, all
+    /// registers are volatile on regmaps without a cache, and none are on regmaps with one, which
+    /// is only correct for devices that have no volatile registers.
+    ///
+    /// `volatile` must be a function, e.g. `fn is_volatile(reg: u32) -> bool`, or a closure that
+    /// captures nothing; anything else, e.g. a function pointer, fails the build, since regmap
+    /// provides no context to find it in. The same holds for [`RegmapConfig::readable_reg`] and
+    /// [`RegmapConfig::writeable_reg`].
+    pub fn volatile_reg<F: Fn(u32) -> bool + Copy + 'static>(mut self, volatile: F) -> Self {
+        self.volatile_reg = Some(reg_predicate(volatile));
+        self
+    }
+
+    /// Sets the predicate telling whether a register can be read.
+    ///
+    /// Reads of other registers fail with `EIO`. If not set, all registers up to
+    /// [`RegmapConfig::max_register`] are readable.
+    pub fn readable_reg<F: Fn(u32) -> bool + Copy + 'static>(mut self, readable: F) -> Self {
+        self.readable_reg = Some(reg_predicate(readable));
+        self
+    }
+
+    /// Sets the predicate telling whether a register can be written to.
+    ///
+    /// Writes to other registers fail with `EIO`. If not set, all registers up to
+    /// [`RegmapConfig::max_register`] are writeable.
+    pub fn writeable_reg<F: Fn(u32) -> bool + Copy + 'static>(mut self, writeable: F) -> Self {
+        self.writeable_reg = Some(reg_predicate(writeable));
+        self
+    }
+
+    /// Returns whether the register addresses and values have widths supported over I2C.
+    #[cfg(CONFIG_REGMAP_I2C)]
+    fn fits_i2c(&self) -> bool {
+        matches!(self.reg_bits, 8 | 16) && matches!(self.val_bits, 8 | 16 | 32)
+    }
+
+    /// Builds the kernel's `struct regmap_config`.
+    ///
+    /// Locking is never disabled, only possibly delegated to a [`RegmapLock`], which is relied
+    /// upon by the [`Regmap`] type invariants.
+    fn build(&self) -> bindings::regmap_config {
+        bindings::regmap_config {
+            reg_bits: self.reg_bits as _,
+            val_bits: self.val_bits as _,
+            reg_stride: self.reg_stride as _,
+            max_register: self.max_register,
+            use_single_read: self.use_single_read,
+            use_single_write: self.use_single_write,
+            read_flag_mask: self.read_flag_mask as _,
+            write_flag_mask: self.write_flag_mask as _,
+            pad_bits: self.pad_bits as _,
+            reg_defaults_raw: self.reg_defaults_raw.map_or(ptr::null(), |d| d.as_ptr() as _),
+            // `num_reg_defaults_raw` counts register values, not bytes.
+            num_reg_defaults_raw: self.reg_defaults_raw.map_or(0, |d| {
+                (d.len() / ((self.val_bits as usize + 7) / 8)) as _
+            }),
+            can_multi_write: self.can_multi_write,
+            zero_flag_mask: self.zero_flag_mask,
+            cache_type: self.cache_type.to_raw(),
+            max_raw_read: self.max_raw_read as _,
+            max_raw_write: self.max_raw_write as _,
+            reg_read: self.custom_access.as_ref().map(|a| a.reg_read),
+            reg_write: self.custom_access.as_ref().map(|a| a.reg_write),
+            volatile_reg: self.volatile_reg,
+            readable_reg: self.readable_reg,
+            writeable_reg: self.writeable_reg,
+            disable_locking: false,
+            lock: self.custom_lock.as_ref().map(|l| l.lock),
+            unlock: self.custom_lock.as_ref().map(|l| l.unlock),
+            lock_arg: self.custom_lock.as_ref().map_or(ptr::null_mut(), |l| l.arg),
+            ..Default::default()
+        }
+    }
+}
+
+/// Checks that `cfg` does not disable regmap locking, which the type invariants of [`Regmap`]
+/// rely on.
+///
+/// [`RegmapConfig::build`] never disables locking. With `CONFIG_RUST_DEBUG_ASSERTIONS`, this
+/// catches any change that breaks that before a regmap is created from `cfg`.
+fn debug_assert_locking(cfg: &bindings::regmap_config) {
+    debug_assert!(
+        !cfg.disable_locking,
+        "regmap locking must never be disabled"
+    );
+}
+
+/// Packs `vals` into `packed`, failing with `EINVAL` if one of them does not fit in a `T`.
+fn pack_vals<T: TryFrom<u32>>(vals: &[u32], packed: &mut Vec<T>) -> Result {
+    packed.try_reserve_exact(vals.len())?;
+    for &val in vals {
+        packed.push(T::try_from(val).map_err(|_| Error::EINVAL)?);
+    }
+    Ok(())
+}
+
+/// The address of a register.
+///
+/// Register addresses and values are both `u32`s, so they are easily swapped by mistake, e.g. in
+/// a call to [`Regmap::write`]. Declaring the registers of a device as [`Register`]s and accessing
+/// them with [`Regmap::read_reg`] and [`Regmap::write_reg`] makes such mistakes fail to compile.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::regmap::{Regmap, Register};
+/// const RNG_CTRL: Register = Register::new(0x00);
+/// const RNG_CTRL_RBGEN: u32 = 0x1;
+///
+/// fn enable(regmap: &Regmap) -> Result {
+///     regmap.write_reg(RNG_CTRL, RNG_CTRL_RBGEN)
+/// }
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Register(u32);
+
+impl Register {
+    /// Creates the register at address `addr`.
+    pub const fn new(addr: u32) -> Self {
+        Self(addr)
+    }
+
+    /// Returns the address of the register.
+    pub const fn addr(self) -> u32 {
+        self.0
+    }
+}
+
+/// A register map.
+///
+/// Regmaps created by the `init_*` functions are device-managed: they are freed when the driver
+/// is unbound from the device. Regmaps shared with contexts that may outlive the binding, e.g. the
+/// state of an open file, must be wrapped with [`devm_revocable`](crate::device::devm_revocable),
+/// so that accesses from those contexts fail once the driver is unbound instead of using the freed
+/// regmap. Since accesses through a [`Revocable`](crate::revocable::Revocable) must not sleep,
+/// only regmaps whose accesses do not sleep, e.g. MMIO ones, can be shared this way.
+///
+/// # Invariants
+///
+/// The pointer `Regmap::ptr` is non-null and valid, and the regmap has a device and was created
+/// with `cache_type` as its cache type. Regmap locking is never disabled, though it may be
+/// delegated to a [`RegmapLock`], so the regmap can be accessed concurrently from several threads.
+pub struct Regmap {
+    ptr: *mut bindings::regmap,
+    cache_type: RegcacheType,
+}
+
+// SAFETY: Regmap locking is never disabled, as per the type invariants, so all accesses through the
+// regmap pointer are serialised internally by the kernel.
+unsafe impl Send for Regmap {}
+
+// SAFETY: Regmap locking is never disabled, as per the type invariants, so all accesses through the
+// regmap pointer are serialised internally by the kernel.
+unsafe impl Sync for Regmap {}
+
+impl Regmap {
+    /// Creates a [`Regmap`] from a raw `struct regmap` pointer.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null and valid for the lifetime of the returned object, and the regmap
+    /// must have been created for a device, with `cache_type` as its cache type, and not have been
+    /// created with locking disabled.
+    pub unsafe fn from_ptr(ptr: *mut bindings::regmap, cache_type: RegcacheType) -> Self {
+        // INVARIANTS: The safety requirements of this function ensure that the invariants hold.
+        Self { ptr, cache_type }
+    }
+
+    /// Creates a device-managed regmap for `dev` that is not on any bus, whose registers are
+    /// accessed through the [`RegmapAccess`] set with [`RegmapConfig::reg_read_write`].
+    ///
+    /// Fails with `EINVAL` if `cfg` has no such accesses.
+    pub fn init_with_config(dev: &dyn Device, cfg: &RegmapConfig) -> Result<Self> {
+        let context = cfg.custom_access.as_ref().ok_or(Error::EINVAL)?.context;
+        let cache_type = cfg.cache_type;
+        let cfg = cfg.build();
+        debug_assert_locking(&cfg);
+        // SAFETY: `raw_device()` is valid as guaranteed by `Device`, `context` is the `'static`
+        // reference the callbacks of `cfg` expect, and the kernel does not keep a reference to
+        // `cfg` after the call returns.
+        let ptr = from_kernel_err_ptr(unsafe {
+            rust_helper_devm_regmap_init(dev.raw_device(), ptr::null(), context, &cfg)
+        })?;
+        // INVARIANTS: `ptr` is valid since the call succeeded, it was created with the cache type of
+        // `cfg`, and `cfg` does not disable locking.
+        Ok(Self { ptr, cache_type })
+    }
+
+    /// Creates a device-managed regmap over the memory-mapped I/O resource `index` of a platform
+    /// device.
+    #[cfg(CONFIG_REGMAP_MMIO)]
+    pub fn init_mmio_platform_resource(
+        pdev: &PlatformDevice,
+        index: u32,
+        cfg: &RegmapConfig,
+    ) -> Result<Self> {
+        // SAFETY: `pdev.to_ptr()` is valid as guaranteed by `PlatformDevice`.
+        let regs = from_kernel_err_ptr(unsafe {
+            bindings::devm_platform_ioremap_resource(pdev.to_ptr(), index)
+        })?;
+        let cache_type = cfg.cache_type;
+        let cfg = cfg.build();
+        debug_assert_locking(&cfg);
+        // SAFETY: `raw_device()` is valid as guaranteed by `PlatformDevice`, `regs` was just
+        // mapped and the kernel does not keep a reference to `cfg` after the call returns.
+        let ptr = from_kernel_err_ptr(unsafe {
+            rust_helper_devm_regmap_init_mmio(pdev.raw_device(), regs, &cfg)
+        })?;
+        // INVARIANTS: `ptr` is valid since the call succeeded, it was created with the cache type of
+        // `cfg`, and `cfg` does not disable locking.
+        Ok(Self { ptr, cache_type })
+    }
+
+    /// Creates a device-managed regmap over the memory-mapped registers at `regs`, whose accesses
+    /// require the clock `clk_id` of `dev` to be running.
+    ///
+    /// The clock is looked up by name, so it must be listed in the `clock-names` property of the
+    /// devicetree node of `dev`; creating the regmap fails otherwise. It is prepared when the
+    /// regmap is created, and enabled around every register access, so it can be gated the rest
+    /// of the time. With `None` as `clk_id`, no clock is used, as with
+    /// [`Regmap::init_mmio_platform_resource`].
+    ///
+    /// # Safety
+    ///
+    /// `regs` must point to memory-mapped registers covering all the registers described by `cfg`,
+    /// and remain mapped for as long as the regmap exists, e.g. because they were mapped with a
+    /// devm-managed function for `dev`.
+    #[cfg(CONFIG_REGMAP_MMIO)]
+    pub unsafe fn init_mmio_clk(
+        dev: &dyn Device,
+        clk_id: Option<&CStr>,
+        regs: *mut c_types::c_void,
+        cfg: &RegmapConfig,
+    ) -> Result<Self> {
+        let cache_type = cfg.cache_type;
+        let cfg = cfg.build();
+        debug_assert_locking(&cfg);
+        let clk_id = clk_id.map_or(ptr::null(), |id| id.as_char_ptr());
+        // SAFETY: `raw_device()` is valid as guaranteed by `Device`, `regs` is valid by the safety
+        // requirements of this function, `clk_id` is null or a valid string, and the kernel does
+        // not keep a reference to `clk_id` or `cfg` after the call returns.
+        let ptr = from_kernel_err_ptr(unsafe {
+            rust_helper_devm_regmap_init_mmio_clk(dev.raw_device(), clk_id, regs, &cfg)
+        })?;
+        // INVARIANTS: `ptr` is valid since the call succeeded, it was created with the cache type of
+        // `cfg`, and `cfg` does not disable locking.
+        Ok(Self { ptr, cache_type })
+    }
+
+    /// Creates a device-managed regmap over the memory-mapped I/O resource `index` of a platform
+    /// device, whose accesses require the clock `clk_id` of the device to be running.
+    ///
+    /// See [`Regmap::init_mmio_clk`].
+    #[cfg(CONFIG_REGMAP_MMIO)]
+    pub fn init_mmio_clk_platform_resource(
+        pdev: &PlatformDevice,
+        clk_id: &CStr,
+        index: u32,
+        cfg: &RegmapConfig,
+    ) -> Result<Self> {
+        // SAFETY: `pdev.to_ptr()` is valid as guaranteed by `PlatformDevice`.
+        let regs = from_kernel_err_ptr(unsafe {
+            bindings::devm_platform_ioremap_resource(pdev.to_ptr(), index)
+        })?;
+        // SAFETY: `regs` was just mapped for the whole resource, and it remains mapped until the
+        // driver is unbound from `pdev`, when the regmap is freed too.
+        unsafe { Self::init_mmio_clk(pdev, Some(clk_id), regs, cfg) }
+    }
+
+    /// Creates a device-managed regmap over the registers of an I2C device.
+    ///
+    /// Register addresses must be 8 or 16 bits wide, and values 8, 16 or 32 bits wide, as the
+    /// I2C register maps of chips are; `EINVAL` is returned otherwise. Accesses to the device
+    /// sleep, so the regmap must only be used from process context.
+    #[cfg(CONFIG_REGMAP_I2C)]
+    pub fn init_i2c(client: &I2cClient, cfg: &RegmapConfig) -> Result<Self> {
+        if !cfg.fits_i2c() {
+            return Err(Error::EINVAL);
+        }
+        let cache_type = cfg.cache_type;
+        let cfg = cfg.build();
+        debug_assert_locking(&cfg);
+        // SAFETY: `client.to_ptr()` is valid as guaranteed by `I2cClient`, and the kernel does not
+        // keep a reference to `cfg` after the call returns.
+        let ptr = from_kernel_err_ptr(unsafe {
+            rust_helper_devm_regmap_init_i2c(client.to_ptr(), &cfg)
+        })?;
+        // INVARIANTS: `ptr` is valid since the call succeeded, it was created with the cache type of
+        // `cfg`, and `cfg` does not disable locking.
+        Ok(Self { ptr, cache_type })
+    }
+
+    /// Takes a reference to the device the regmap was created for.
+    ///
+    /// This allows code that is only given the regmap to log messages in the context of the
+    /// device. The reference keeps the `struct device` alive, but not the regmap, which is still
+    /// freed when the driver is unbound from the device.
+    pub fn device(&self) -> DeviceRef {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        let dev = unsafe { bindings::regmap_get_device(self.ptr) };
+        // SAFETY: By the type invariants, the regmap has a device, which is valid for as long as
+        // the regmap exists: devm-managed regmaps are freed when the driver is unbound from the
+        // device, before the device may be freed itself.
+        unsafe { DeviceRef::from_ptr(dev) }
+    }
+
+    /// Reads the value of a single register.
+    ///
+    /// This is the lower-level form of [`Regmap::read_reg`], for registers whose address is
+    /// computed at runtime.
+    pub fn read(&self, reg: u32) -> Result<u32> {
+        let mut val = 0;
+        // SAFETY: `self.ptr` is valid by the type invariants, and `val` is a valid location to
+        // store the result.
+        to_result(unsafe { bindings::regmap_read(self.ptr, reg, &mut val) })?;
+        Ok(val)
+    }
+
+    /// Writes a value to a single register.
+    ///
+    /// This is the lower-level form of [`Regmap::write_reg`], for registers whose address is
+    /// computed at runtime.
+    pub fn write(&self, reg: u32, val: u32) -> Result {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        to_result(unsafe { bindings::regmap_write(self.ptr, reg, val) })
+    }
+
+    /// Reads the value of the register `reg`.
+    pub fn read_reg(&self, reg: Register) -> Result<u32> {
+        self.read(reg.addr())
+    }
+
+    /// Writes `val` to the register `reg`.
+    pub fn write_reg(&self, reg: Register, val: u32) -> Result {
+        self.write(reg.addr(), val)
+    }
+
+    /// Writes a value to a single register and reads it back to check that it was applied.
+    ///
+    /// Returns `EIO` if the value read back differs from `val`. This is meant for registers that
+    /// are both readable and writable and whose value only changes when written to. Fails with
+    /// `EINVAL`, without writing anything, if the register is not readable, e.g. write-only, or
+    /// if the regmap has a register cache and the register is volatile, i.e., the device may
+    /// update its bits by itself (e.g. a status register), which would report spurious
+    /// mismatches. Without a register cache, volatility is not known, so the caller must not use
+    /// this for such registers.
+    ///
+    /// Note that with a register cache, the value of a non-volatile register is read back from
+    /// the cache rather than from the device.
+    pub fn write_verify(&self, reg: u32, val: u32) -> Result {
+        if !self.is_readable(reg)
+            || (self.cache_type != RegcacheType::None && self.is_volatile(reg))
+        {
+            return Err(Error::EINVAL);
+        }
+        self.write(reg, val)?;
+        if self.read(reg)? != val {
+            return Err(Error::EIO);
+        }
+        Ok(())
+    }
+
+    /// Updates the bits of register `reg` that are set in `mask` to their values in `val`,
+    /// leaving the other bits unchanged.
+    ///
+    /// The read-modify-write sequence is done with the regmap lock held, so it is atomic with
+    /// respect to other accesses to the regmap, e.g. a concurrent update of other bits of the same
+    /// register. The register is only written to if its value changes.
+    pub fn update_bits(&self, reg: u32, mask: u32, val: u32) -> Result {
+        // SAFETY: `self.ptr` is valid by the type invariants, and the change and async pointers
+        // may be null and false respectively.
+        to_result(unsafe {
+            bindings::regmap_update_bits_base(
+                self.ptr,
+                reg,
+                mask,
+                val,
+                ptr::null_mut(),
+                false,
+                false,
+            )
+        })
+    }
+
+    /// Updates the bits of register `reg` that are set in `mask` to their values in `val`, like
+    /// [`Regmap::update_bits`], and returns whether the value of the register changed.
+    pub fn update_bits_check(&self, reg: u32, mask: u32, val: u32) -> Result<bool> {
+        let mut change = false;
+        // SAFETY: `self.ptr` is valid by the type invariants, and `change` is a valid location to
+        // store whether the register changed.
+        to_result(unsafe {
+            bindings::regmap_update_bits_base(self.ptr, reg, mask, val, &mut change, false, false)
+        })?;
+        Ok(change)
+    }
+
+    /// Reads register `reg` until `cond` is true for its value, and returns that value.
+    ///
+    /// This is the equivalent of the kernel's `regmap_read_poll_timeout`: `cond` is called with
+    /// the value of each read, and the register is read again after sleeping for up to
+    /// `sleep_us` microseconds, or immediately if `sleep_us` is zero. Fails with `ETIMEDOUT` if
+    /// `cond` is still false after `timeout_us` microseconds; the register is read one last time
+    /// once the timeout expired, so a value that became ready while sleeping is not missed. If
+    /// `timeout_us` is zero, the register is polled until `cond` is true. Fails with the error of
+    /// the first read that fails.
+    ///
+    /// It sleeps unless `sleep_us` is zero, so it must then only be called from process context.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// # use kernel::prelude::*;
+    /// # use kernel::regmap::Regmap;
+    /// const FIFO_COUNT: u32 = 0x24;
+    ///
+    /// /// Waits for up to 10ms for the FIFO to hold data, and returns the number of words in it.
+    /// fn wait_for_words(regmap: &Regmap) -> Result<u32> {
+    ///     let count = regmap.read_poll_timeout(FIFO_COUNT, |c| c & 0xff != 0, 100, 10_000)?;
+    ///     Ok(count & 0xff)
+    /// }
+    /// ```
+    pub fn read_poll_timeout(
+        &self,
+        reg: u32,
+        cond: impl Fn(u32) -> bool,
+        sleep_us: u64,
+        timeout_us: u64,
+    ) -> Result<u32> {
+        // SAFETY: FFI call without preconditions.
+        let start = unsafe { bindings::ktime_get() };
+        loop {
+            let val = self.read(reg)?;
+            if cond(val) {
+                return Ok(val);
+            }
+            // SAFETY: FFI call without preconditions.
+            let elapsed_ns = unsafe { bindings::ktime_get() } - start;
+            // CAST: The clock is monotonic, so `elapsed_ns` is not negative.
+            if timeout_us != 0 && elapsed_ns as u64 > timeout_us.saturating_mul(1000) {
+                let val = self.read(reg)?;
+                return if cond(val) {
+                    Ok(val)
+                } else {
+                    Err(Error::ETIMEDOUT)
+                };
+            }
+            if sleep_us != 0 {
+                // SAFETY: FFI call without preconditions.
+                unsafe { rust_helper_usleep_range(((sleep_us >> 2) + 1) as _, sleep_us as _) };
+            }
+        }
+    }
+
+    /// Reads the values of the (not necessarily contiguous) registers `regs` into `vals`.
+    ///
+    /// `vals[i]` receives the value of `regs[i]`; `regs` and `vals` must have the same length,
+    /// otherwise `EINVAL` is returned. Stops at the first failed read.
+    ///
+    /// The kernel's regmap API has no asynchronous read path, so the registers are read one after
+    /// the other, without holding the regmap lock in between, and this function returns once all
+    /// values are available. It may sleep, so it must only be called from process context.
+    pub fn read_many(&self, regs: &[u32], vals: &mut [u32]) -> Result {
+        if regs.len() != vals.len() {
+            return Err(Error::EINVAL);
+        }
+        for (reg, val) in regs.iter().zip(vals.iter_mut()) {
+            *val = self.read(*reg)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the size of the values exchanged with `regmap_bulk_read` and `regmap_bulk_write`
+    /// for bulk accesses to `count` registers.
+    ///
+    /// Fails with `EINVAL` if `count` is zero, or if the register values are not 8, 16 or 32
+    /// bits wide, since they would not fit in a `u32`, or not be laid out as an array of integers.
+    fn bulk_val_bytes(&self, count: usize) -> Result<usize> {
+        let val_bytes = self.val_bytes()?;
+        if count == 0 || !matches!(val_bytes, 1 | 2 | 4) {
+            return Err(Error::EINVAL);
+        }
+        Ok(val_bytes)
+    }
+
+    /// Reads the values of `vals.len()` consecutive registers, starting at `first_reg`, into
+    /// `vals`.
+    ///
+    /// Consecutive registers are [`Regmap::reg_stride`] addresses apart. The values are returned
+    /// as native `u32`s, whatever the width and the endianness of the registers of the device,
+    /// which regmap converts from. Depending on the bus and on the configuration of the regmap,
+    /// the registers are read in a single transfer, or one by one, with the regmap lock held
+    /// throughout.
+    ///
+    /// Fails with `EINVAL` if `vals` is empty, or if the register values are not 8, 16 or 32
+    /// bits wide.
+    pub fn bulk_read(&self, first_reg: u32, vals: &mut [u32]) -> Result {
+        let val_bytes = self.bulk_val_bytes(vals.len())?;
+        let count = vals.len();
+        let base = vals.as_mut_ptr();
+        // SAFETY: `self.ptr` is valid by the type invariants, and `base` is valid for writing
+        // `count` values of `val_bytes` bytes, which is at most 4.
+        to_result(unsafe {
+            bindings::regmap_bulk_read(self.ptr, first_reg, base as _, count as _)
+        })?;
+        // `regmap_bulk_read` packs the values in an array of `val_bytes`-byte integers. Widen
+        // them in place, from the last one, so that each value is read before it is overwritten:
+        // the widened values after value `i` start at byte `(i + 1) * 4`, past its packed bytes.
+        for i in (0..count).rev() {
+            // SAFETY: The packed value `i` is within the first `count * val_bytes` bytes of
+            // `vals`, aligned to its size, and not overwritten yet, as explained above.
+            unsafe {
+                let val = match val_bytes {
+                    1 => u32::from(*(base as *const u8).add(i)),
+                    2 => u32::from(*(base as *const u16).add(i)),
+                    _ => break,
+                };
+                *base.add(i) = val;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes `vals` to `vals.len()` consecutive registers, starting at `first_reg`.
+    ///
+    /// `vals.len()` is the number of registers to write, not a number of bytes, and consecutive
+    /// registers are [`Regmap::reg_stride`] addresses apart. The values are native `u32`s, which
+    /// regmap converts to the width and the endianness of the registers of the device. All the
+    /// writes are done with the regmap lock held, in a single transfer if the bus supports it, so
+    /// this is the way to program a block of configuration registers at once.
+    ///
+    /// Fails with `EINVAL`, without writing anything, if `vals` is empty, if the register values
+    /// are not 8, 16 or 32 bits wide, or if one of `vals` does not fit in a register.
+    pub fn bulk_write(&self, first_reg: u32, vals: &[u32]) -> Result {
+        let val_bytes = self.bulk_val_bytes(vals.len())?;
+        // `regmap_bulk_write` expects an array of `val_bytes`-byte integers.
+        let mut bytes = Vec::new();
+        let mut halves = Vec::new();
+        let buf = match val_bytes {
+            1 => {
+                pack_vals::<u8>(vals, &mut bytes)?;
+                bytes.as_ptr() as *const c_types::c_void
+            }
+            2 => {
+                pack_vals::<u16>(vals, &mut halves)?;
+                halves.as_ptr() as _
+            }
+            _ => vals.as_ptr() as _,
+        };
+        // SAFETY: `self.ptr` is valid by the type invariants, and `buf` is valid for reading
+        // `vals.len()` values of `val_bytes` bytes.
+        to_result(unsafe { bindings::regmap_bulk_write(self.ptr, first_reg, buf, vals.len() as _) })
+    }
+
+    /// Reads all the registers of the regmap, e.g. to dump them for diagnostics.
+    ///
+    /// Returns the (register, value) pairs of the registers from 0 to [`Regmap::max_register`],
+    /// every [`Regmap::reg_stride`] addresses. Registers that are not readable, e.g. write-only
+    /// ones left out by [`RegmapConfig::readable_reg`], are skipped without being read. If
+    /// `skip_errors` is `true`, the registers that fail to be read are left out of the snapshot
+    /// too; otherwise the first failure is returned. Each register is read separately, so the
+    /// snapshot is not atomic with respect to other users of the regmap.
+    ///
+    /// Fails with `EINVAL`, before reading any register, if the regmap has no maximum register,
+    /// since the extent of its register space is then unknown.
+    pub fn snapshot(&self, skip_errors: bool) -> Result<Vec<(u32, u32)>> {
+        let stride = self.reg_stride().max(1);
+        let max = self.max_register()?;
+        let mut regs = Vec::new();
+        let mut reg = 0;
+        loop {
+            if self.is_readable(reg) {
+                match self.read(reg) {
+                    Ok(val) => {
+                        regs.try_reserve(1)?;
+                        regs.push((reg, val));
+                    }
+                    Err(_) if skip_errors => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            reg = match reg.checked_add(stride) {
+                Some(next) if next <= max => next,
+                _ => break,
+            };
+        }
+        Ok(regs)
+    }
+
+    /// Writes the registers whose cached values differ from their defaults back to the device.
+    ///
+    /// Typically called when the device is powered back up, e.g. in a runtime PM resume callback,
+    /// after having lost its register contents. Only meaningful for regmaps with a register
+    /// cache.
+    pub fn cache_sync(&self) -> Result {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        to_result(unsafe { bindings::regcache_sync(self.ptr) })
+    }
+
+    /// Sets or clears the cache-only mode of the regmap.
+    ///
+    /// In cache-only mode, writes only update the register cache, and are applied to the device
+    /// by the next [`Regmap::cache_sync`], while reads of volatile registers fail with `EBUSY`.
+    /// This allows staging writes while the device is powered down. The mode applies to all users
+    /// of the regmap. Without a register cache, writes done in cache-only mode are lost.
+    pub fn cache_only(&self, enable: bool) {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        unsafe { bindings::regcache_cache_only(self.ptr, enable) };
+    }
+
+    /// Sets or clears the cache-bypass mode of the regmap.
+    ///
+    /// In cache-bypass mode, reads and writes go directly to the device and the register cache is
+    /// not updated, e.g. to access registers during a reset sequence without polluting the cache.
+    /// The mode applies to all users of the regmap.
+    pub fn cache_bypass(&self, enable: bool) {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        unsafe { bindings::regcache_cache_bypass(self.ptr, enable) };
+    }
+
+    /// Drops the cached values of the registers from `first` to `last`, inclusive.
+    ///
+    /// The next reads of these registers go to the device, e.g. after the device changed them
+    /// behind the driver's back. Fails with `EINVAL` unless the cache type of the regmap is
+    /// [`RegcacheType::RbTree`]: the flat cache does not support dropping registers.
+    pub fn cache_drop(&self, first: u32, last: u32) -> Result {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        to_result(unsafe { bindings::regcache_drop_region(self.ptr, first, last) })
+    }
+
+    /// Allocates a field spanning bits `lsb` to `msb`, inclusive, of register `reg`.
+    ///
+    /// Returns `EINVAL` if `lsb` is greater than `msb`, or if `msb` is not a bit of the register
+    /// values. The field is device-managed: it is freed when the driver is unbound from the
+    /// device of the regmap, like the regmap itself.
+    pub fn alloc_field(&self, reg: u32, lsb: u8, msb: u8) -> Result<RegmapField> {
+        if lsb > msb || usize::from(msb) >= self.val_bytes()? * 8 {
+            return Err(Error::EINVAL);
+        }
+        let field = bindings::reg_field {
+            reg,
+            lsb: lsb.into(),
+            msb: msb.into(),
+            ..Default::default()
+        };
+        // SAFETY: `self.ptr` is valid by the type invariants, and so is its device.
+        let ptr = from_kernel_err_ptr(unsafe {
+            let dev = bindings::regmap_get_device(self.ptr);
+            bindings::devm_regmap_field_alloc(dev, self.ptr, field)
+        })?;
+        // INVARIANTS: `ptr` is valid since the call succeeded, and refers to `self`.
+        Ok(RegmapField { ptr })
+    }
+
+    /// Starts a transaction, to apply several register writes with all-or-nothing semantics.
+    ///
+    /// See [`RegmapTransaction`].
+    pub fn transaction(&self) -> RegmapTransaction<'_> {
+        RegmapTransaction {
+            regmap: self,
+            writes: Vec::new(),
+        }
+    }
+
+    /// Returns the number of bytes used to represent a single register value.
+    ///
+    /// Fails with `EINVAL` if the value width is not a whole number of bytes.
+    pub fn val_bytes(&self) -> Result<usize> {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        let ret = unsafe { bindings::regmap_get_val_bytes(self.ptr) };
+        if ret < 0 {
+            return Err(Error::from_kernel_errno(ret));
+        }
+        Ok(ret.try_into()?)
+    }
+
+    /// Returns the distance between two consecutive valid register addresses.
+    pub fn reg_stride(&self) -> u32 {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        let stride = unsafe { bindings::regmap_get_reg_stride(self.ptr) };
+        // CAST: the kernel rejects regmap configurations with a negative stride.
+        stride as u32
+    }
+
+    /// Returns the highest valid register address.
+    ///
+    /// Fails with `EINVAL` if the regmap was created without a maximum register, i.e., with
+    /// [`RegmapConfig::max_register`] left unset.
+    pub fn max_register(&self) -> Result<u32> {
+        // SAFETY: `self.ptr` is valid by the type invariants.
+        to_result_value(unsafe { bindings::regmap_get_max_register(self.ptr) })
+    }
+
+    /// Returns whether `reg` can be read, as per the readable registers of the regmap.
+    fn is_readable(&self, reg: u32) -> bool {
+        // SAFETY: `self.ptr` is valid by the type invariants, and the readable table and predicate
+        // of the regmap are only set when it is created.
+        unsafe { regmap_readable(self.ptr, reg) }
+    }
+
+    /// Returns whether the value of `reg` may change without being written to, so that it is
+    /// never cached.
+    ///
+    /// Without a register cache, every register is considered volatile.
+    fn is_volatile(&self, reg: u32) -> bool {
+        // SAFETY: `self.ptr` is valid by the type invariants, and the volatile table and predicate
+        // of the regmap are only set when it is created.
+        unsafe { regmap_volatile(self.ptr, reg) }
+    }
+}
+
+/// A field of a register, i.e., a range of consecutive bits of it.
+///
+/// Fields are allocated with [`Regmap::alloc_field`], and their values are shifted and masked
+/// internally, so they are accessed as if they were registers of their own. Accesses go through
+/// the regmap, and its locking.
+///
+/// Like the regmap, fields are device-managed: fields shared with contexts that may outlive the
+/// binding of the driver to the device must also be wrapped with
+/// [`devm_revocable`](crate::device::devm_revocable), after the field is allocated.
+///
+/// # Invariants
+///
+/// The pointer `RegmapField::ptr` is non-null and valid, and refers to a [`Regmap`], whose type
+/// invariants hold.
+pub struct RegmapField {
+    ptr: *mut bindings::regmap_field,
+}
+
+// SAFETY: Accesses to the field go through its regmap, whose locking is never disabled, as per the
+// type invariants of `Regmap`.
+unsafe impl Send for RegmapField {}
+
+// SAFETY: Accesses to the field go through its regmap, whose locking is never disabled, as per the
+// type invariants of `Regmap`.
+unsafe impl Sync for RegmapField {}
+
+impl RegmapField {
+    /// Reads the value of the field, shifted down so that bit 0 is the lowest bit of the field.
+    pub fn read(&self) -> Result<u32> {
+        let mut val = 0;
+        // SAFETY: `self.ptr` is valid by the type invariants, and `val` is a valid location to
+        // store the result.
+        to_result(unsafe { bindings::regmap_field_read(self.ptr, &mut val) })?;
+        Ok(val)
+    }
+
+    /// Writes `val` to the field, leaving the other bits of the register unchanged.
+    ///
+    /// `val` is shifted up to the position of the field, and its bits beyond the width of the
+    /// field are ignored. Like [`RegmapField::update_bits`], this is a read-modify-write of the
+    /// register done with the regmap lock held.
+    pub fn write(&self, val: u32) -> Result {
+        self.update_bits(!0, val)
+    }
+
+    /// Updates the bits of the field that are set in `mask` to their values in `val`, leaving the
+    /// other bits of the field, and of the register, unchanged.
+    ///
+    /// Unlike for [`Regmap::update_bits`], `mask` and `val` are relative to the field, not to the
+    /// whole register: bit 0 of `mask` is the lowest bit of the field, whatever its position in
+    /// the register. Bits of `mask` beyond the width of the field are ignored. The
+    /// read-modify-write sequence is done with the regmap lock held, so it is atomic with respect
+    /// to other accesses to the regmap.
+    pub fn update_bits(&self, mask: u32, val: u32) -> Result {
+        // SAFETY: `self.ptr` is valid by the type invariants, and the change and async pointers
+        // may be null and false respectively.
+        to_result(unsafe {
+            bindings::regmap_field_update_bits_base(
+                self.ptr,
+                mask,
+                val,
+                ptr::null_mut(),
+                false,
+                false,
+            )
+        })
+    }
+}
+
+/// A set of register writes to be applied together.
+///
+/// Writes are accumulated with [`RegmapTransaction::write`] and applied by
+/// [`RegmapTransaction::commit`], which first applies them to the register cache only, with the
+/// device untouched, and then synchronises the cache to the device. A failure while updating the
+/// cache is rolled back before the device is ever written to; if the synchronisation itself fails,
+/// the previous values are written back to the device on a best-effort basis, so that it is not
+/// left half-configured.
+///
+/// This is only meaningful for regmaps with a register cache, and for registers that are not
+/// volatile: in cache-only mode, writes to the other registers are silently discarded. So
+/// [`RegmapTransaction::commit`] fails with `EINVAL`, before anything is written, if the regmap
+/// has no register cache or if one of the registers of the transaction is volatile. Also, the
+/// cache-only mode applies to the whole regmap, so other users of the regmap must not access it
+/// while a transaction is being committed: their writes would be deferred until the
+/// synchronisation, and their reads of volatile registers would fail.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::regmap::Regmap;
+/// fn configure(regmap: &Regmap) -> Result {
+///     let mut t = regmap.transaction();
+///     t.write(0x10, 0x1)?.write(0x14, 0xff)?.write(0x18, 0x3)?;
+///     t.commit()
+/// }
+/// ```
+pub struct RegmapTransaction<'a> {
+    regmap: &'a Regmap,
+    writes: Vec<(u32, u32)>,
+}
+
+impl RegmapTransaction<'_> {
+    /// Adds a write of `val` to the register `reg` to the transaction.
+    ///
+    /// Writes are applied in the order in which they are added.
+    pub fn write(&mut self, reg: u32, val: u32) -> Result<&mut Self> {
+        self.writes.try_reserve(1)?;
+        self.writes.push((reg, val));
+        Ok(self)
+    }
+
+    /// Writes `vals` to the registers of the first `vals.len()` writes of the transaction,
+    /// ignoring errors.
+    fn restore(&self, vals: &[u32]) {
+        for ((reg, _), val) in self.writes.iter().zip(vals) {
+            let _ = self.regmap.write(*reg, *val);
+        }
+    }
+
+    /// Applies all the writes of the transaction, or none of them.
+    ///
+    /// Fails with `EINVAL`, without writing anything, if the regmap has no register cache or if
+    /// one of the registers is volatile. May sleep, so it must only be called from process
+    /// context.
+    pub fn commit(self) -> Result {
+        let regmap = self.regmap;
+        if regmap.cache_type == RegcacheType::None
+            || self.writes.iter().any(|(reg, _)| regmap.is_volatile(*reg))
+        {
+            return Err(Error::EINVAL);
+        }
+
+        let mut old = Vec::new();
+        old.try_reserve_exact(self.writes.len())?;
+        for (reg, _) in &self.writes {
+            old.push(self.regmap.read(*reg)?);
+        }
+
+        self.regmap.cache_only(true);
+        for (i, (reg, val)) in self.writes.iter().enumerate() {
+            if let Err(e) = self.regmap.write(*reg, *val) {
+                self.restore(&old[..i]);
+                self.regmap.cache_only(false);
+                return Err(e);
+            }
+        }
+        self.regmap.cache_only(false);
+
+        if let Err(e) = self.regmap.cache_sync() {
+            self.restore(&old);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopLock;
+
+    // SAFETY: The lock is never used by a regmap.
+    unsafe impl RegmapLock for NoopLock {
+        fn lock(&self) {}
+        fn unlock(&self) {}
+    }
+
+    static LOCK: NoopLock = NoopLock;
+
+    #[test]
+    fn test_build_never_disables_locking() {
+        let base = RegmapConfig::new(32, 32).reg_stride(4).max_register(0x10);
+        for cfg in &[
+            base.clone(),
+            base.clone().cache_type(RegcacheType::Flat),
+            base.clone().custom_lock(&LOCK),
+        ] {
+            let raw = cfg.build();
+            assert!(!raw.disable_locking);
+            debug_assert_locking(&raw);
+        }
+    }
+
+    #[test]
+    fn test_reg_predicates_forward_reg() {
+        fn is_volatile(reg: u32) -> bool {
+            reg == 0x10
+        }
+
+        let raw = RegmapConfig::new(8, 8)
+            .volatile_reg(is_volatile)
+            .writeable_reg(|reg| reg < 0x10)
+            .build();
+        let volatile = raw.volatile_reg.unwrap();
+        let writeable = raw.writeable_reg.unwrap();
+        assert!(raw.readable_reg.is_none());
+        // SAFETY: The trampolines ignore the device.
+        unsafe {
+            assert!(volatile(ptr::null_mut(), 0x10));
+            assert!(!volatile(ptr::null_mut(), 0x11));
+            assert!(writeable(ptr::null_mut(), 0x0f));
+            assert!(!writeable(ptr::null_mut(), 0x10));
+        }
+    }
+
+    #[test]
+    fn test_cache_type_defaults_to_none() {
+        let cfg = RegmapConfig::new(8, 8);
+        assert_eq!(
+            cfg.build().cache_type,
+            bindings::regcache_type_REGCACHE_NONE
+        );
+        assert_eq!(
+            cfg.cache_type(RegcacheType::RbTree).build().cache_type,
+            bindings::regcache_type_REGCACHE_RBTREE
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "regmap locking must never be disabled")]
+    fn test_disabled_locking_trips_assertion() {
+        let mut raw = RegmapConfig::new(8, 8).build();
+        raw.disable_locking = true;
+        debug_assert_locking(&raw);
+    }
+
+    #[test]
+    #[cfg(CONFIG_REGMAP_I2C)]
+    fn test_fits_i2c() {
+        assert!(RegmapConfig::new(8, 8).fits_i2c());
+        assert!(RegmapConfig::new(16, 32).fits_i2c());
+        assert!(!RegmapConfig::new(32, 8).fits_i2c());
+        assert!(!RegmapConfig::new(8, 12).fits_i2c());
+    }
+}
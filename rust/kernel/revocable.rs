@@ -0,0 +1,327 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Revocable objects.
+//!
+//! The data of a [`Revocable`] can be accessed until it is revoked, e.g. when the driver is
+//! unbound from the device that owns the resources the data refers to. Revocation waits for
+//! concurrent accesses to complete, frees the data, and makes subsequent accesses fail.
+//!
+//! C header: [`include/linux/rcupdate.h`](../../../../include/linux/rcupdate.h)
+
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::Deref,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(not(test))]
+mod rcu {
+    use crate::bindings;
+
+    extern "C" {
+        fn rust_helper_rcu_read_lock();
+        fn rust_helper_rcu_read_unlock();
+    }
+
+    pub(super) fn read_lock() {
+        // SAFETY: Just an FFI call, there are no further requirements.
+        unsafe { rust_helper_rcu_read_lock() };
+    }
+
+    /// # Safety
+    ///
+    /// The caller must hold the RCU read-side lock.
+    pub(super) unsafe fn read_unlock() {
+        // SAFETY: The safety requirements of this function ensure that the lock is held.
+        unsafe { rust_helper_rcu_read_unlock() };
+    }
+
+    pub(super) fn synchronize() {
+        // SAFETY: Just an FFI call, there are no further requirements.
+        unsafe { bindings::synchronize_rcu() };
+    }
+}
+
+/// A stand-in for RCU in userspace tests: `synchronize` waits until no reader at all is in its
+/// read-side critical section, which is stricter than RCU requires.
+#[cfg(test)]
+mod rcu {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static READERS: AtomicUsize = AtomicUsize::new(0);
+
+    pub(super) fn read_lock() {
+        READERS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(super) unsafe fn read_unlock() {
+        READERS.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub(super) fn synchronize() {
+        while READERS.load(Ordering::SeqCst) != 0 {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// An object that can become inaccessible at runtime.
+///
+/// Once access is revoked and all concurrent users complete (i.e., all existing instances of
+/// [`RevocableGuard`] are dropped), the wrapped object is also dropped.
+///
+/// This is how devm-managed resources, e.g. a [`Regmap`](crate::regmap::Regmap), are shared
+/// with contexts that may outlive the binding of the driver to the device, such as the state of
+/// an open file: [`devm_revocable`](crate::device::devm_revocable) wraps them in a revocable that
+/// is revoked when the driver is unbound, and accesses from the file operations then fail with
+/// `ENODEV` instead of touching freed memory or hardware.
+///
+/// Accesses happen within an RCU read-side critical section, so the guard must not be held
+/// across operations that may sleep, e.g. copies to or from userspace: the data is copied out of
+/// the guarded object before writing it to userspace, and conversely.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::{device, file::File, file_operations::FileOperations};
+/// # use kernel::{io_buffer::IoBufferWriter, platdev::PlatformDevice};
+/// # use kernel::{regmap::Regmap, revocable::Revocable};
+/// struct Token;
+///
+/// impl FileOperations for Token {
+///     type Wrapper = Arc<Revocable<Regmap>>;
+///
+///     kernel::declare_file_operations!(read);
+///
+///     fn read<T: IoBufferWriter>(
+///         regmap: &Revocable<Regmap>,
+///         _: &File,
+///         data: &mut T,
+///         offset: u64,
+///     ) -> Result<usize> {
+///         if offset != 0 {
+///             return Ok(0);
+///         }
+///         // MMIO regmaps use a spinlock, so reading from them does not sleep.
+///         let val = regmap.try_access().ok_or(Error::ENODEV)?.read(0)?;
+///         data.write(&val)?;
+///         Ok(core::mem::size_of::<u32>())
+///     }
+/// }
+///
+/// // From the `probe` callback of the driver: the regmap is revoked when the driver is unbound,
+/// // before it is freed, so reads from files opened before then fail with `ENODEV`.
+/// fn share(pdev: &PlatformDevice, regmap: Regmap) -> Result<Arc<Revocable<Regmap>>> {
+///     device::devm_revocable(pdev, regmap)
+/// }
+/// ```
+pub struct Revocable<T> {
+    is_available: AtomicBool,
+    data: ManuallyDrop<UnsafeCell<T>>,
+}
+
+// SAFETY: `Revocable` is `Send` if the wrapped object is also `Send`. This is because while the
+// functionality exposed by `Revocable` can be accessed from any thread/CPU, it is possible that
+// this isn't supported by the wrapped object.
+unsafe impl<T: Send> Send for Revocable<T> {}
+
+// SAFETY: `Revocable` is `Sync` if the wrapped object is both `Send` and `Sync`. We require `Send`
+// from the wrapped object as well because of `Revocable::revoke`, which can trigger the `Drop`
+// implementation of the wrapped object from an arbitrary thread.
+unsafe impl<T: Sync + Send> Sync for Revocable<T> {}
+
+impl<T> Revocable<T> {
+    /// Creates a new revocable instance of the given data.
+    pub const fn new(data: T) -> Self {
+        Self {
+            is_available: AtomicBool::new(true),
+            data: ManuallyDrop::new(UnsafeCell::new(data)),
+        }
+    }
+
+    /// Tries to access the wrapped object.
+    ///
+    /// Returns `None` if the object has been revoked and is therefore no longer accessible.
+    ///
+    /// Returns a guard that gives access to the object otherwise; the object is guaranteed to
+    /// remain accessible while the guard is alive. Callers are not allowed to sleep while holding
+    /// on to the guard.
+    pub fn try_access(&self) -> Option<RevocableGuard<'_, T>> {
+        let guard = RevocableGuard::new(self.data.get());
+        if self.is_available.load(Ordering::Relaxed) {
+            Some(guard)
+        } else {
+            None
+        }
+    }
+
+    /// Revokes access to and drops the wrapped object.
+    ///
+    /// Access to the object is revoked immediately to new callers of [`Revocable::try_access`].
+    /// If there are concurrent users of the object (i.e., ones that called
+    /// [`Revocable::try_access`] beforehand and still haven't dropped the returned guard), this
+    /// function waits for the concurrent access to complete before dropping the wrapped object.
+    ///
+    /// Revoking an object that was already revoked does nothing. This function may sleep.
+    pub fn revoke(&self) {
+        if self
+            .is_available
+            .compare_exchange(true, false, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            rcu::synchronize();
+
+            // SAFETY: We know `self.data` is valid because only one CPU can succeed the
+            // `compare_exchange` above that takes `is_available` from `true` to `false`, and all
+            // the concurrent users, which entered their read-side critical section before it,
+            // have completed.
+            unsafe { core::ptr::drop_in_place(self.data.get()) };
+        }
+    }
+}
+
+impl<T> Drop for Revocable<T> {
+    fn drop(&mut self) {
+        // Drop only if the data hasn't been revoked yet (in which case it has already been
+        // dropped).
+        if *self.is_available.get_mut() {
+            // SAFETY: We know `self.data` is valid because no other CPU has changed
+            // `is_available` to `false` yet, and no other CPU can do it anymore because this CPU
+            // holds the only reference (mutable) to `self` now.
+            unsafe { core::ptr::drop_in_place(self.data.get()) };
+        }
+    }
+}
+
+/// A guard that allows access to a revocable object and keeps it alive.
+///
+/// CPUs may not sleep while holding on to [`RevocableGuard`] because it's in atomic context
+/// holding the RCU read-side lock.
+///
+/// # Invariants
+///
+/// The RCU read-side lock is held while the guard is alive.
+pub struct RevocableGuard<'a, T> {
+    data_ref: *const T,
+    _p: PhantomData<&'a ()>,
+}
+
+impl<T> RevocableGuard<'_, T> {
+    fn new(data_ref: *const T) -> Self {
+        rcu::read_lock();
+
+        // INVARIANTS: The RCU read-side lock was just acquired.
+        Self {
+            data_ref,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for RevocableGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: By the type invariants, we hold the rcu read-side lock.
+        unsafe { rcu::read_unlock() };
+    }
+}
+
+impl<T> Deref for RevocableGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: By the type invariants, we hold the rcu read-side lock, so the object is
+        // guaranteed to remain valid.
+        unsafe { &*self.data_ref }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+    use std::{sync::Arc, thread, time::Duration};
+
+    struct DropCounter(Arc<AtomicUsize>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_access_until_revoked() {
+        let r = Revocable::new(42);
+        assert_eq!(*r.try_access().unwrap(), 42);
+        r.revoke();
+        assert!(r.try_access().is_none());
+    }
+
+    #[test]
+    fn test_data_dropped_exactly_once() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let r = Revocable::new(DropCounter(drops.clone()));
+        r.revoke();
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        r.revoke();
+        drop(r);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        let r = Revocable::new(DropCounter(drops.clone()));
+        drop(r);
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_revoke_waits_for_guards() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let r = Arc::new(Revocable::new(DropCounter(drops.clone())));
+        let guard = r.try_access().unwrap();
+
+        let revoker = {
+            let r = r.clone();
+            thread::spawn(move || r.revoke())
+        };
+        thread::sleep(Duration::from_millis(50));
+
+        // The data is only dropped once the guard taken before the revocation is released.
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert_eq!(guard.0.load(Ordering::SeqCst), 0);
+        drop(guard);
+        revoker.join().unwrap();
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert!(r.try_access().is_none());
+    }
+
+    #[test]
+    fn test_revoke_racing_with_accesses() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let r = Arc::new(Revocable::new(DropCounter(drops.clone())));
+
+        let accessors: std::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let r = r.clone();
+                let drops = drops.clone();
+                thread::spawn(move || {
+                    while let Some(guard) = r.try_access() {
+                        // The data is never dropped while it is being accessed.
+                        assert_eq!(drops.load(Ordering::SeqCst), 0);
+                        drop(guard);
+                        thread::yield_now();
+                    }
+                })
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(10));
+        r.revoke();
+        for accessor in accessors {
+            accessor.join().unwrap();
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+}
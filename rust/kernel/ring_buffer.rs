@@ -0,0 +1,355 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! A bounded single-producer single-consumer ring buffer.
+//!
+//! It is meant for the canonical streaming driver pattern: an interrupt handler pushes data as
+//! it arrives from the device, and `read` drains it to userspace, sleeping while the buffer is
+//! empty. It is lock-free, so it can be used from interrupt handlers without disabling
+//! interrupts in the reader.
+
+use crate::{error::Error, sync::CondVar, Result};
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// What [`RingBuffer::try_push`] does when the ring buffer is full.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// The new element is rejected.
+    Reject,
+
+    /// The oldest element is dropped to make room for the new one.
+    ///
+    /// If the consumer is popping an element at the same time, the new element is rejected
+    /// instead.
+    DropOldest,
+}
+
+/// A bounded ring buffer holding up to `N` elements of type `T`.
+///
+/// It is designed for a single producer and a single consumer, which may run concurrently on
+/// different CPUs. Concurrent calls to [`RingBuffer::try_push`] (or to [`RingBuffer::try_pop`])
+/// are nevertheless safe: all but one of them fail as if the buffer were full (or empty).
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::ring_buffer::RingBuffer;
+/// # use kernel::sync::CondVar;
+/// struct Samples {
+///     ring: RingBuffer<u32, 64>,
+///     // Initialised with `condvar_init!`.
+///     wait: CondVar,
+/// }
+///
+/// // From the interrupt handler.
+/// fn on_sample(s: &Samples, sample: u32) {
+///     if s.ring.push_notify(sample, &s.wait).is_err() {
+///         pr_warn!("sample dropped\n");
+///     }
+/// }
+///
+/// // From `read`.
+/// fn next_sample(s: &Samples) -> Result<u32> {
+///     s.ring.pop_wait(&s.wait)
+/// }
+/// ```
+///
+/// # Invariants
+///
+/// `head` and `tail` count the elements popped and pushed so far, modulo `usize::MAX + 1`; the
+/// slots with indices in `head..tail` (modulo `N`) are initialised, and there are at most `N` of
+/// them. `tail` is only modified by the holder of `producer`, `head` by the holder of
+/// `consumer`.
+pub struct RingBuffer<T, const N: usize> {
+    slots: UnsafeCell<MaybeUninit<[T; N]>>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    producer: AtomicBool,
+    consumer: AtomicBool,
+    overflow: Overflow,
+}
+
+// SAFETY: Elements are moved from the producer to the consumer, which may run on different
+// threads, so `T` must be `Send`. The buffer does not otherwise give access to its elements.
+unsafe impl<T: Send, const N: usize> Send for RingBuffer<T, N> {}
+
+// SAFETY: Accesses to the slots are synchronised through `head`, `tail` and the `producer` and
+// `consumer` flags, and elements are only moved in and out, never shared.
+unsafe impl<T: Send, const N: usize> Sync for RingBuffer<T, N> {}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    /// Creates a new, empty ring buffer with the given overflow policy.
+    pub const fn new(overflow: Overflow) -> Self {
+        // INVARIANTS: The buffer is empty, and no slot is initialised.
+        Self {
+            slots: UnsafeCell::new(MaybeUninit::uninit()),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            producer: AtomicBool::new(false),
+            consumer: AtomicBool::new(false),
+            overflow,
+        }
+    }
+
+    fn slot(&self, index: usize) -> *mut T {
+        // SAFETY: `index % N` is within the bounds of the array.
+        unsafe { (self.slots.get() as *mut T).add(index % N) }
+    }
+
+    /// Returns the number of elements in the buffer.
+    ///
+    /// This is only a snapshot when the producer or the consumer run concurrently.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Relaxed);
+        self.tail.load(Ordering::Relaxed).wrapping_sub(head)
+    }
+
+    /// Returns whether the buffer is empty.
+    ///
+    /// This is only a snapshot when the producer or the consumer run concurrently.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pops the oldest element, if any. Must be called with the `consumer` flag held.
+    fn pop_locked(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: The slot at `head` is initialised by the type invariants, since the buffer is
+        // not empty. Holding `consumer`, we own it; the producer does not reuse it before `head`
+        // is advanced below.
+        let value = unsafe { ptr::read(self.slot(head)) };
+        // INVARIANTS: We moved out of the slot at `head`, which is no longer part of the buffer.
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Appends `value` to the buffer.
+    ///
+    /// If the buffer is full, the behaviour depends on the [`Overflow`] policy it was created
+    /// with. On failure, `value` is given back to the caller. Never sleeps, so it may be called
+    /// from interrupt handlers.
+    pub fn try_push(&self, value: T) -> core::result::Result<(), T> {
+        if N == 0
+            || self
+                .producer
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+        {
+            return Err(value);
+        }
+
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(self.head.load(Ordering::Acquire)) == N {
+            let dropped = match self.overflow {
+                Overflow::DropOldest
+                    if self
+                        .consumer
+                        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                        .is_ok() =>
+                {
+                    let oldest = self.pop_locked();
+                    self.consumer.store(false, Ordering::Release);
+                    oldest
+                }
+                _ => None,
+            };
+            if dropped.is_none() {
+                self.producer.store(false, Ordering::Release);
+                return Err(value);
+            }
+        }
+
+        // SAFETY: The buffer is not full, so the slot at `tail` is not initialised and not used by
+        // the consumer. Holding `producer`, we own it.
+        unsafe { ptr::write(self.slot(tail), value) };
+        // INVARIANTS: We initialised the slot at `tail`, which is now part of the buffer.
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        self.producer.store(false, Ordering::Release);
+        Ok(())
+    }
+
+    /// Removes the oldest element from the buffer and returns it, or returns [`None`] if the
+    /// buffer is empty.
+    ///
+    /// Never sleeps, so it may be called from interrupt handlers.
+    pub fn try_pop(&self) -> Option<T> {
+        if self
+            .consumer
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return None;
+        }
+        let value = self.pop_locked();
+        self.consumer.store(false, Ordering::Release);
+        value
+    }
+
+    /// Appends `value` to the buffer, like [`RingBuffer::try_push`], and wakes up the tasks
+    /// waiting for data in [`RingBuffer::pop_wait`] on `cv`.
+    pub fn push_notify(&self, value: T, cv: &CondVar) -> core::result::Result<(), T> {
+        self.try_push(value)?;
+        cv.notify_all();
+        Ok(())
+    }
+
+    /// Removes the oldest element from the buffer and returns it, sleeping until one is pushed
+    /// with [`RingBuffer::push_notify`] on the same `cv` if the buffer is empty.
+    ///
+    /// Returns `ERESTARTSYS` if a signal is pending while waiting. As this may sleep, it must only
+    /// be called from process context.
+    pub fn pop_wait(&self, cv: &CondVar) -> Result<T> {
+        let mut value = None;
+        cv.wait_for(|| {
+            value = self.try_pop();
+            value.is_some()
+        })?;
+        // `wait_for` only succeeds once the condition popped an element.
+        value.ok_or(Error::EAGAIN)
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        while self.try_pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+        vec::Vec,
+    };
+
+    /// Records its identifier in a shared list when dropped.
+    struct DropRecorder(u32, Arc<Mutex<Vec<u32>>>);
+
+    impl Drop for DropRecorder {
+        fn drop(&mut self) {
+            self.1.lock().unwrap().push(self.0);
+        }
+    }
+
+    #[test]
+    fn test_fifo_across_wrap_around() {
+        let ring = RingBuffer::<u32, 4>::new(Overflow::Reject);
+        let mut next_push = 0;
+        let mut next_pop = 0;
+        // Three elements per round, so the indices wrap around the slots at different offsets.
+        for _ in 0..10 {
+            for _ in 0..3 {
+                assert_eq!(ring.try_push(next_push), Ok(()));
+                next_push += 1;
+            }
+            assert_eq!(ring.len(), 3);
+            for _ in 0..3 {
+                assert_eq!(ring.try_pop(), Some(next_pop));
+                next_pop += 1;
+            }
+            assert!(ring.is_empty());
+        }
+        assert_eq!(ring.try_pop(), None);
+    }
+
+    #[test]
+    fn test_reject_returns_value_when_full() {
+        let ring = RingBuffer::<u32, 2>::new(Overflow::Reject);
+        assert_eq!(ring.try_push(1), Ok(()));
+        assert_eq!(ring.try_push(2), Ok(()));
+        assert_eq!(ring.try_push(3), Err(3));
+        assert_eq!(ring.len(), 2);
+        assert_eq!(ring.try_pop(), Some(1));
+        assert_eq!(ring.try_pop(), Some(2));
+        assert_eq!(ring.try_pop(), None);
+    }
+
+    #[test]
+    fn test_drop_oldest_drops_exactly_the_oldest() {
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let ring = RingBuffer::<DropRecorder, 3>::new(Overflow::DropOldest);
+        for id in 0..3 {
+            assert!(ring.try_push(DropRecorder(id, dropped.clone())).is_ok());
+        }
+        assert!(ring.try_push(DropRecorder(3, dropped.clone())).is_ok());
+        assert_eq!(*dropped.lock().unwrap(), [0]);
+        assert_eq!(ring.len(), 3);
+
+        for id in 1..4 {
+            let value = ring.try_pop().unwrap();
+            assert_eq!(value.0, id);
+        }
+        assert!(ring.try_pop().is_none());
+        assert_eq!(*dropped.lock().unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        for &overflow in &[Overflow::Reject, Overflow::DropOldest] {
+            let ring = RingBuffer::<u32, 0>::new(overflow);
+            assert_eq!(ring.try_push(1), Err(1));
+            assert_eq!(ring.try_pop(), None);
+            assert!(ring.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_drop_releases_queued_elements() {
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let ring = RingBuffer::<DropRecorder, 3>::new(Overflow::Reject);
+        for id in 0..5 {
+            assert!(ring.try_push(DropRecorder(id, dropped.clone())).is_ok());
+            if id < 3 {
+                drop(ring.try_pop());
+            }
+        }
+        assert_eq!(*dropped.lock().unwrap(), [0, 1, 2]);
+
+        drop(ring);
+        assert_eq!(*dropped.lock().unwrap(), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_concurrent_producer_and_consumer() {
+        const COUNT: u32 = 100_000;
+        let ring = Arc::new(RingBuffer::<u32, 8>::new(Overflow::Reject));
+
+        let producer = {
+            let ring = ring.clone();
+            thread::spawn(move || {
+                for i in 0..COUNT {
+                    let mut value = i;
+                    while let Err(v) = ring.try_push(value) {
+                        value = v;
+                        thread::yield_now();
+                    }
+                }
+            })
+        };
+
+        let mut expected = 0;
+        while expected < COUNT {
+            match ring.try_pop() {
+                Some(value) => {
+                    assert_eq!(value, expected);
+                    expected += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+        assert!(ring.is_empty());
+    }
+}
@@ -4,7 +4,7 @@
 //!
 //! C header: [`include/linux/security.h`](../../../../include/linux/security.h).
 
-use crate::{bindings, c_types, error::Error, file::File, task::Task, Result};
+use crate::{bindings, c_types, error::to_result, file::File, task::Task, Result};
 
 extern "C" {
     #[allow(improper_ctypes)]
@@ -33,36 +33,21 @@ extern "C" {
 /// context.
 pub fn binder_set_context_mgr(mgr: &Task) -> Result {
     // SAFETY: By the `Task` invariants, `mgr.ptr` is valid.
-    let ret = unsafe { rust_helper_security_binder_set_context_mgr(mgr.ptr) };
-    if ret != 0 {
-        Err(Error::from_kernel_errno(ret))
-    } else {
-        Ok(())
-    }
+    to_result(unsafe { rust_helper_security_binder_set_context_mgr(mgr.ptr) })
 }
 
 /// Calls the security modules to determine if binder transactions are allowed from task `from` to
 /// task `to`.
 pub fn binder_transaction(from: &Task, to: &Task) -> Result {
     // SAFETY: By the `Task` invariants, `from.ptr` and `to.ptr` are valid.
-    let ret = unsafe { rust_helper_security_binder_transaction(from.ptr, to.ptr) };
-    if ret != 0 {
-        Err(Error::from_kernel_errno(ret))
-    } else {
-        Ok(())
-    }
+    to_result(unsafe { rust_helper_security_binder_transaction(from.ptr, to.ptr) })
 }
 
 /// Calls the security modules to determine if task `from` is allowed to send binder objects
 /// (owned by itself or other processes) to task `to` through a binder transaction.
 pub fn binder_transfer_binder(from: &Task, to: &Task) -> Result {
     // SAFETY: By the `Task` invariants, `from.ptr` and `to.ptr` are valid.
-    let ret = unsafe { rust_helper_security_binder_transfer_binder(from.ptr, to.ptr) };
-    if ret != 0 {
-        Err(Error::from_kernel_errno(ret))
-    } else {
-        Ok(())
-    }
+    to_result(unsafe { rust_helper_security_binder_transfer_binder(from.ptr, to.ptr) })
 }
 
 /// Calls the security modules to determine if task `from` is allowed to send the given file to
@@ -70,10 +55,5 @@ pub fn binder_transfer_binder(from: &Task, to: &Task) -> Result {
 pub fn binder_transfer_file(from: &Task, to: &Task, file: &File) -> Result {
     // SAFETY: By the `Task` invariants, `from.ptr` and `to.ptr` are valid. Similarly, by the
     // `File` invariants, `file.ptr` is also valid.
-    let ret = unsafe { rust_helper_security_binder_transfer_file(from.ptr, to.ptr, file.ptr) };
-    if ret != 0 {
-        Err(Error::from_kernel_errno(ret))
-    } else {
-        Ok(())
-    }
+    to_result(unsafe { rust_helper_security_binder_transfer_file(from.ptr, to.ptr, file.ptr) })
 }
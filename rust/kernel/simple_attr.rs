@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Files exposing a single numeric value.
+//!
+//! Many devices only have a single knob to expose, e.g. a brightness or a threshold. A
+//! [`SimpleAttr`] implements the file operations for such a value, in the manner of the kernel's
+//! `DEFINE_SIMPLE_ATTRIBUTE`: reading the file returns the value as decimal text, and writing a
+//! number to it sets the value.
+//!
+//! C header: [`include/linux/fs.h`](../../../../include/linux/fs.h)
+
+use crate::{
+    error::Error,
+    file::File,
+    file_operations::{FileOpener, FileOperations},
+    io_buffer::{IoBufferReader, IoBufferWriter},
+    Result,
+};
+use alloc::boxed::Box;
+use core::convert::TryFrom;
+
+/// The maximum length of the text of a value, including its trailing newline.
+const MAX_LEN: usize = 24;
+
+/// The text of `val`: its decimal representation, followed by a newline.
+fn format_value(val: u64, buf: &mut [u8; MAX_LEN]) -> &[u8] {
+    let mut start = MAX_LEN - 1;
+    buf[start] = b'\n';
+    let mut val = val;
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (val % 10) as u8;
+        val /= 10;
+        if val == 0 {
+            break;
+        }
+    }
+    &buf[start..]
+}
+
+/// Parses a value written by userspace, decimal or hexadecimal with a `0x` prefix, and optionally
+/// followed by a newline, as written by `echo`.
+fn parse_value(text: &[u8]) -> Result<u64> {
+    let text = core::str::from_utf8(text)?;
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    let (digits, radix) = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => (digits, 16),
+        None => (text, 10),
+    };
+    // `from_str_radix` accepts a leading `+`, which the kernel's `kstrtoull` does not.
+    if digits.starts_with('+') {
+        return Err(Error::EINVAL);
+    }
+    u64::from_str_radix(digits, radix).map_err(|_| Error::EINVAL)
+}
+
+/// A file exposing a single value, read with a getter and written with a setter.
+///
+/// The value is read anew on every read, starting at the offset of the read, so a read at the
+/// end of the text returns `0` (end of file). A value read in several parts may thus be torn if
+/// it changes in between, which tools like `cat` avoid by reading it at once. Writes must contain
+/// the whole number, and fail with `EINVAL` if it cannot be parsed, e.g. because of trailing
+/// garbage, or with `EACCES` if the file has no setter.
+///
+/// The [`SimpleAttr`] is the context of the registration of the file, e.g. a
+/// [`miscdev::Registration`], and is copied to the state of each open file.
+///
+/// [`miscdev::Registration`]: crate::miscdev::Registration
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::{c_str, miscdev, simple_attr::SimpleAttr};
+/// # use core::sync::atomic::{AtomicU64, Ordering};
+/// static THRESHOLD: AtomicU64 = AtomicU64::new(100);
+///
+/// fn get() -> Result<u64> {
+///     Ok(THRESHOLD.load(Ordering::Relaxed))
+/// }
+///
+/// fn set(val: u64) -> Result {
+///     if val > 1000 {
+///         return Err(Error::EINVAL);
+///     }
+///     THRESHOLD.store(val, Ordering::Relaxed);
+///     Ok(())
+/// }
+///
+/// fn register() -> Result<Pin<Box<miscdev::Registration<SimpleAttr>>>> {
+///     miscdev::Registration::new_pinned::<SimpleAttr>(
+///         c_str!("threshold"),
+///         None,
+///         SimpleAttr::new(get, Some(set)),
+///     )
+/// }
+/// ```
+#[derive(Clone, Copy)]
+pub struct SimpleAttr {
+    get: fn() -> Result<u64>,
+    set: Option<fn(u64) -> Result>,
+}
+
+impl SimpleAttr {
+    /// Creates a new attribute whose value is read with `get` and, unless `set` is [`None`],
+    /// written with `set`.
+    pub const fn new(get: fn() -> Result<u64>, set: Option<fn(u64) -> Result>) -> Self {
+        Self { get, set }
+    }
+}
+
+impl FileOpener<SimpleAttr> for SimpleAttr {
+    fn open(attr: &SimpleAttr) -> Result<Box<Self>> {
+        Ok(Box::try_new(*attr)?)
+    }
+}
+
+impl FileOperations for SimpleAttr {
+    crate::declare_file_operations!(read, write);
+
+    fn read<T: IoBufferWriter>(this: &Self, _: &File, data: &mut T, offset: u64) -> Result<usize> {
+        let mut buf = [0; MAX_LEN];
+        let text = format_value((this.get)()?, &mut buf);
+        let start = match usize::try_from(offset) {
+            Ok(start) if start < text.len() => start,
+            _ => return Ok(0),
+        };
+        let len = (text.len() - start).min(data.len());
+        data.write_slice(&text[start..start + len])?;
+        Ok(len)
+    }
+
+    fn write<T: IoBufferReader>(this: &Self, _: &File, data: &mut T, _: u64) -> Result<usize> {
+        let set = this.set.ok_or(Error::EACCES)?;
+        let len = data.len();
+        if len > MAX_LEN {
+            return Err(Error::EINVAL);
+        }
+        let mut buf = [0; MAX_LEN];
+        data.read_slice(&mut buf[..len])?;
+        set(parse_value(&buf[..len])?)?;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_value() {
+        let mut buf = [0; MAX_LEN];
+        assert_eq!(format_value(0, &mut buf), b"0\n");
+        assert_eq!(format_value(42, &mut buf), b"42\n");
+        assert_eq!(format_value(u64::MAX, &mut buf), b"18446744073709551615\n");
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert!(parse_value(b"42") == Ok(42));
+        assert!(parse_value(b"42\n") == Ok(42));
+        assert!(parse_value(b"0x2a\n") == Ok(42));
+        assert!(parse_value(b"18446744073709551615") == Ok(u64::MAX));
+    }
+
+    #[test]
+    fn test_parse_value_rejects_garbage() {
+        for text in &[
+            &b""[..],
+            b"\n",
+            b"42 \n",
+            b"42\n\n",
+            b"42abc",
+            b"-1",
+            b"+1",
+            b"0x+1",
+            b"0x",
+            b"18446744073709551616",
+        ] {
+            assert!(parse_value(text) == Err(Error::EINVAL));
+        }
+    }
+}
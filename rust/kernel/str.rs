@@ -2,6 +2,7 @@
 
 //! String representations.
 
+use alloc::vec::Vec;
 use core::ops::{self, Deref, Index};
 
 use crate::bindings;
@@ -257,3 +258,91 @@ macro_rules! c_str {
         C
     }};
 }
+
+/// Possible errors when creating a [`CString`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CStringError {
+    /// Supplied bytes contain a `NUL` at the given position.
+    InteriorNul(usize),
+
+    /// Memory for the string could not be allocated.
+    AllocError,
+}
+
+impl From<CStringError> for crate::Error {
+    #[inline]
+    fn from(e: CStringError) -> crate::Error {
+        match e {
+            CStringError::InteriorNul(_) => crate::Error::EINVAL,
+            CStringError::AllocError => crate::Error::ENOMEM,
+        }
+    }
+}
+
+/// An owned string that is guaranteed to have exactly one `NUL` byte, which is at the end.
+///
+/// Used to pass names built at runtime, e.g. from user input, to kernel APIs that take C strings.
+/// It dereferences to [`CStr`].
+///
+/// # Invariants
+///
+/// `CString::buf` contains exactly one `NUL` byte, which is its last byte.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::str::CString;
+/// fn name_from_user(bytes: &[u8]) -> Result<CString> {
+///     // Fails with `EINVAL` if `bytes` contains a `NUL`.
+///     Ok(CString::try_from_bytes(bytes)?)
+/// }
+/// ```
+pub struct CString {
+    buf: Vec<u8>,
+}
+
+impl CString {
+    /// Creates a new [`CString`] from the given bytes, which are copied and `NUL`-terminated.
+    ///
+    /// Fails if `bytes` contains a `NUL`.
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, CStringError> {
+        if let Some(pos) = bytes.iter().position(|&b| b == 0) {
+            return Err(CStringError::InteriorNul(pos));
+        }
+        let mut buf = Vec::new();
+        buf.try_reserve_exact(bytes.len() + 1).map_err(|_| CStringError::AllocError)?;
+        buf.extend_from_slice(bytes);
+        buf.push(0);
+        // INVARIANTS: `bytes` has no `NUL`, and we just appended one.
+        Ok(Self { buf })
+    }
+}
+
+impl Deref for CString {
+    type Target = CStr;
+
+    #[inline]
+    fn deref(&self) -> &CStr {
+        // SAFETY: By the type invariants, `buf` has exactly one `NUL`, which is its last byte.
+        unsafe { CStr::from_bytes_with_nul_unchecked(&self.buf) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cstring_from_bytes() {
+        let s = CString::try_from_bytes(b"name").unwrap();
+        assert_eq!(s.as_bytes_with_nul(), b"name\0");
+    }
+
+    #[test]
+    fn test_cstring_interior_nul() {
+        let e = CString::try_from_bytes(b"na\0me").err().unwrap();
+        assert_eq!(e, CStringError::InteriorNul(2));
+        assert!(crate::Error::from(e) == crate::Error::EINVAL);
+    }
+}
@@ -18,6 +18,59 @@ extern "C" {
     fn rust_helper_put_task_struct(t: *mut bindings::task_struct);
 }
 
+/// A POSIX capability, as checked by [`capable`].
+///
+/// Only the capabilities commonly checked by drivers are listed.
+///
+/// C header: [`include/uapi/linux/capability.h`](../../../../include/uapi/linux/capability.h)
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Bypass file read, write and execute permission checks (`CAP_DAC_OVERRIDE`).
+    DacOverride = bindings::CAP_DAC_OVERRIDE,
+
+    /// Perform network administration (`CAP_NET_ADMIN`).
+    NetAdmin = bindings::CAP_NET_ADMIN,
+
+    /// Perform I/O port operations and access raw devices (`CAP_SYS_RAWIO`).
+    SysRawio = bindings::CAP_SYS_RAWIO,
+
+    /// Perform a range of system administration operations (`CAP_SYS_ADMIN`).
+    SysAdmin = bindings::CAP_SYS_ADMIN,
+
+    /// Raise process priorities and change scheduling policies (`CAP_SYS_NICE`).
+    SysNice = bindings::CAP_SYS_NICE,
+
+    /// Override resource limits (`CAP_SYS_RESOURCE`).
+    SysResource = bindings::CAP_SYS_RESOURCE,
+
+    /// Set the system and real-time clocks (`CAP_SYS_TIME`).
+    SysTime = bindings::CAP_SYS_TIME,
+}
+
+/// Determines whether the current task has the given capability in the initial user namespace.
+///
+/// Sets `PF_SUPERPRIV` on the task if the capability is available, as the C `capable()` does.
+///
+/// # Examples
+///
+/// ```
+/// # use kernel::prelude::*;
+/// use kernel::task::{self, Capability};
+///
+/// fn privileged_op() -> Result {
+///     if !task::capable(Capability::SysAdmin) {
+///         return Err(Error::EPERM);
+///     }
+///     // ...
+///     Ok(())
+/// }
+/// ```
+pub fn capable(cap: Capability) -> bool {
+    // SAFETY: FFI call; `cap` is a valid capability by construction.
+    unsafe { bindings::capable(cap as c_types::c_int) }
+}
+
 /// Wraps the kernel's `struct task_struct`.
 ///
 /// # Invariants
@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Thermal sensors.
+//!
+//! Drivers of temperature sensors register them with the thermal core, which reads them to
+//! enforce the trip points and cooling policies of the thermal zones described in the
+//! devicetree.
+//!
+//! C header: [`include/linux/thermal.h`](../../../../include/linux/thermal.h)
+
+use crate::{
+    bindings, c_types,
+    device::{Device, DeviceRef},
+    error::from_kernel_err_ptr,
+    from_kernel_result, Result,
+};
+use alloc::boxed::Box;
+use core::{marker::PhantomData, pin::Pin};
+
+/// A temperature sensor, as read by the thermal core.
+pub trait ThermalSensor: Sync {
+    /// Returns the current temperature, in millidegrees Celsius.
+    ///
+    /// Called by the thermal core, in process context, whenever it polls the thermal zone or is
+    /// asked for its temperature, e.g. through sysfs.
+    fn get_temp(&self) -> Result<i32>;
+}
+
+unsafe extern "C" fn get_temp_callback<T: ThermalSensor>(
+    data: *mut c_types::c_void,
+    temp: *mut c_types::c_int,
+) -> c_types::c_int {
+    from_kernel_result! {
+        // SAFETY: `data` was set by `ThermalZone::<T>::register` to a `T` that remains alive while
+        // the zone is registered.
+        let sensor = unsafe { &*(data as *const T) };
+        let val = sensor.get_temp()?;
+        // SAFETY: The thermal core passes a valid location for the temperature.
+        unsafe { *temp = val };
+        Ok(0)
+    }
+}
+
+struct OpsVtable<T>(PhantomData<T>);
+
+impl<T: ThermalSensor> OpsVtable<T> {
+    const VTABLE: bindings::thermal_zone_of_device_ops = bindings::thermal_zone_of_device_ops {
+        get_temp: Some(get_temp_callback::<T>),
+        get_trend: None,
+        set_trips: None,
+        set_emul_temp: None,
+        set_trip_temp: None,
+    };
+}
+
+/// A temperature sensor registered with the thermal zone of the devicetree that refers to it.
+///
+/// The registration is owned by the [`ThermalZone`], and is undone when it is dropped, which then
+/// frees the sensor. It holds a reference to the device, so it remains valid even if it outlives
+/// the binding of the driver to the device, though drivers usually keep it in their driver data.
+///
+/// # Invariants
+///
+/// `tzd` is a valid thermal zone registered for `dev` with `sensor` as its data, by
+/// `thermal_zone_of_sensor_register`.
+pub struct ThermalZone<T: ThermalSensor> {
+    dev: DeviceRef,
+    tzd: *mut bindings::thermal_zone_device,
+    sensor: Pin<Box<T>>,
+}
+
+// SAFETY: The zone can be unregistered from any thread, and the thermal core calls the sensor from
+// any thread, so the sensor must be `Send` and is `Sync` by the bounds of `ThermalSensor`.
+unsafe impl<T: ThermalSensor + Send> Send for ThermalZone<T> {}
+
+// SAFETY: `ThermalZone` only gives shared access to the sensor, which is `Sync`.
+unsafe impl<T: ThermalSensor> Sync for ThermalZone<T> {}
+
+impl<T: ThermalSensor> ThermalZone<T> {
+    /// Registers `sensor` as the sensor `sensor_id` of `dev`.
+    ///
+    /// The sensor is bound to the thermal zone of the devicetree whose `thermal-sensors` property
+    /// refers to the devicetree node of `dev` with `sensor_id` as its specifier. Fails with
+    /// `ENODEV` if there is no such zone.
+    pub fn register(dev: &dyn Device, sensor_id: u32, sensor: T) -> Result<Self> {
+        let sensor = Pin::from(Box::try_new(sensor)?);
+        let data = &*sensor as *const T as *mut c_types::c_void;
+        // SAFETY: `raw_device()` is valid as guaranteed by the `Device` trait, `data` points to
+        // the pinned sensor, which is only freed after the zone is unregistered, and the vtable
+        // has static lifetime.
+        let tzd = from_kernel_err_ptr(unsafe {
+            bindings::thermal_zone_of_sensor_register(
+                dev.raw_device(),
+                sensor_id as _,
+                data,
+                &OpsVtable::<T>::VTABLE,
+            )
+        })?;
+        // INVARIANTS: The zone was registered above.
+        Ok(Self {
+            dev: DeviceRef::from_dev(dev),
+            tzd,
+            sensor,
+        })
+    }
+
+    /// Returns the registered sensor.
+    pub fn sensor(&self) -> &T {
+        &self.sensor
+    }
+
+    /// Makes the thermal core read the temperature of the zone and evaluate its trip points now,
+    /// e.g. when a sensor signals a temperature change.
+    ///
+    /// This takes the mutex of the zone and may sleep, so it must only be called from process
+    /// context, e.g. from a threaded interrupt handler, never from a hard interrupt handler.
+    pub fn update(&self) {
+        // SAFETY: `self.tzd` is valid by the type invariants.
+        unsafe {
+            bindings::thermal_zone_device_update(
+                self.tzd,
+                bindings::thermal_notify_event_THERMAL_EVENT_UNSPECIFIED,
+            )
+        };
+    }
+}
+
+impl<T: ThermalSensor> Drop for ThermalZone<T> {
+    fn drop(&mut self) {
+        // SAFETY: `self.tzd` was registered for `self.dev` by the type invariants, and is only
+        // unregistered here. Once this returns, the thermal core no longer calls the sensor, which
+        // can then be freed.
+        unsafe { bindings::thermal_zone_of_sensor_unregister(self.dev.raw_device(), self.tzd) };
+    }
+}
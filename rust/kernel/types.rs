@@ -9,7 +9,10 @@ use crate::{
     sync::{Ref, RefBorrow},
 };
 use alloc::{boxed::Box, sync::Arc};
-use core::{ops::Deref, pin::Pin, ptr::NonNull};
+use core::{
+    cell::UnsafeCell, marker::PhantomPinned, mem::MaybeUninit, ops::Deref, pin::Pin,
+    ptr::NonNull,
+};
 
 /// Permissions.
 ///
@@ -121,6 +124,21 @@ impl<T> PointerWrapper for Arc<T> {
     }
 }
 
+// For objects that have no data to store, e.g. drivers that keep no driver data.
+impl PointerWrapper for () {
+    type Borrowed = &'static ();
+
+    fn into_pointer(self) -> *const c_types::c_void {
+        core::ptr::null()
+    }
+
+    unsafe fn borrow(_ptr: *const c_types::c_void) -> Self::Borrowed {
+        &()
+    }
+
+    unsafe fn from_pointer(_ptr: *const c_types::c_void) -> Self {}
+}
+
 /// A reference with manually-managed lifetime.
 ///
 /// # Invariants
@@ -158,6 +176,9 @@ impl<T: ?Sized> Deref for UnsafeReference<T> {
     }
 }
 
+// The pointee of a `Pin<T>` is never moved: `into_pointer` and `from_pointer` only unwrap and
+// rewrap the pointer `T`, so the address returned by `into_pointer` is the address of the pinned
+// object, which can therefore safely be self-referential or referenced by C code.
 impl<T: PointerWrapper + Deref> PointerWrapper for Pin<T> {
     type Borrowed = T::Borrowed;
 
@@ -181,6 +202,51 @@ impl<T: PointerWrapper + Deref> PointerWrapper for Pin<T> {
     }
 }
 
+/// Stores a kernel struct that is only ever accessed through raw pointers.
+///
+/// Many C structs embedded in Rust abstractions (e.g. a `struct platform_driver` in a
+/// registration) are self-referential or referenced by the kernel once registered, so they must
+/// never move, and the kernel may modify them at any time. [`Opaque`] captures these
+/// requirements: it is `!Unpin`, so it can only be used in place once pinned; it has interior
+/// mutability, so the kernel may write to it while Rust holds shared references; and it gives no
+/// safe access to its contents, only a raw pointer via [`Opaque::get`].
+///
+/// The contents may be left uninitialised with [`Opaque::uninit`], in which case the user must
+/// initialise them in place, through the pointer returned by [`Opaque::get`], before passing it
+/// to any C function that reads them.
+#[repr(transparent)]
+pub struct Opaque<T> {
+    value: MaybeUninit<UnsafeCell<T>>,
+    _pin: PhantomPinned,
+}
+
+impl<T> Opaque<T> {
+    /// Creates a new opaque value initialised to `value`.
+    pub const fn new(value: T) -> Self {
+        Self {
+            value: MaybeUninit::new(UnsafeCell::new(value)),
+            _pin: PhantomPinned,
+        }
+    }
+
+    /// Creates a new uninitialised opaque value.
+    pub const fn uninit() -> Self {
+        Self {
+            value: MaybeUninit::uninit(),
+            _pin: PhantomPinned,
+        }
+    }
+
+    /// Returns a raw pointer to the contents.
+    ///
+    /// The pointer remains valid for as long as `self` does not move.
+    pub fn get(&self) -> *mut T {
+        // CAST: `UnsafeCell<T>` is `repr(transparent)` over `T`, and casting a pointer to it into
+        // a pointer to `T` is how its contents are meant to be mutated.
+        self.value.as_ptr() as *mut T
+    }
+}
+
 /// Runs a cleanup function/closure when dropped.
 ///
 /// The [`ScopeGuard::dismiss`] function prevents the cleanup function from running.
@@ -247,3 +313,22 @@ impl<T: FnOnce()> Drop for ScopeGuard<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pinned_box_round_trip_keeps_address() {
+        let pinned = Box::pin(42u64);
+        let addr = &*pinned as *const u64;
+
+        let ptr = pinned.into_pointer();
+        assert_eq!(ptr as *const u64, addr);
+
+        // SAFETY: `ptr` was returned by `into_pointer` above and no borrows are outstanding.
+        let pinned = unsafe { Pin::<Box<u64>>::from_pointer(ptr) };
+        assert_eq!(&*pinned as *const u64, addr);
+        assert_eq!(*pinned, 42);
+    }
+}
@@ -7,10 +7,12 @@
 use crate::{
     c_types,
     error::Error,
-    io_buffer::{IoBufferReader, IoBufferWriter},
+    io_buffer::{IoBufferReader, IoBufferWriter, ReadableFromBytes, WritableToBytes},
     Result,
 };
 use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
 
 extern "C" {
     fn rust_helper_copy_from_user(
@@ -108,6 +110,79 @@ impl UserSlicePtr {
     }
 }
 
+/// A pointer to a single value of type `T` in userspace memory, e.g. the argument of an ioctl.
+///
+/// Like [`UserSlicePtr`], all methods are safe, and each instance reads or writes the value at
+/// most once, which avoids double-fetches: [`UserPtr::read`] and [`UserPtr::write`] take `self`
+/// by value. `T` is typically a `#[repr(C)]` structure shared with userspace, which implements
+/// [`ReadableFromBytes`] to be read and [`WritableToBytes`] to be written.
+///
+/// # Examples
+///
+/// ```ignore
+/// # use kernel::prelude::*;
+/// # use kernel::user_ptr::UserPtr;
+/// #[repr(C)]
+/// #[derive(Clone, Copy)]
+/// struct Config {
+///     rate: u32,
+///     flags: u32,
+/// }
+///
+/// // SAFETY: `Config` only has `u32` fields, so all bit patterns are valid and it has no padding.
+/// unsafe impl kernel::io_buffer::ReadableFromBytes for Config {}
+///
+/// fn set_config(cfg: UserPtr<Config>) -> Result<u32> {
+///     let cfg = cfg.read()?;
+///     Ok(cfg.rate)
+/// }
+/// ```
+pub struct UserPtr<T>(*mut c_types::c_void, PhantomData<T>);
+
+impl<T> UserPtr<T> {
+    /// Constructs a pointer to a value of type `T` in userspace memory.
+    ///
+    /// # Safety
+    ///
+    /// Callers must be careful to avoid time-of-check-time-of-use
+    /// (TOCTOU) issues. The simplest way is to create a single instance of
+    /// [`UserPtr`] or [`UserSlicePtr`] per user memory block.
+    pub unsafe fn new(ptr: *mut c_types::c_void) -> Self {
+        UserPtr(ptr, PhantomData)
+    }
+
+    /// Returns the user slice covering the value, after checking the alignment of the pointer.
+    fn slice(self) -> Result<UserSlicePtr> {
+        if self.0 as usize % align_of::<T>() != 0 {
+            return Err(Error::EFAULT);
+        }
+        // SAFETY: `self` is consumed, so the user memory block is only accessed once through it.
+        Ok(unsafe { UserSlicePtr::new(self.0, size_of::<T>()) })
+    }
+
+    /// Reads the value from userspace memory.
+    ///
+    /// Returns `EFAULT` if the pointer is not aligned for `T`, or if it does not currently point
+    /// to mapped, readable memory.
+    pub fn read(self) -> Result<T>
+    where
+        T: ReadableFromBytes,
+    {
+        self.slice()?.reader().read()
+    }
+
+    /// Writes `value` to userspace memory.
+    ///
+    /// Returns `EFAULT` if the pointer is not aligned for `T`, or if it does not currently point
+    /// to mapped, writable memory (in which case part of the value may be written).
+    pub fn write(self, value: &T) -> Result
+    where
+        T: WritableToBytes,
+    {
+        self.slice()?.writer().write(value)
+    }
+}
+
 /// A reader for [`UserSlicePtr`].
 ///
 /// Used to incrementally read from the user slice.
@@ -0,0 +1,54 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Waiting for conditions.
+//!
+//! C header: [`include/linux/wait.h`](../../../../include/linux/wait.h)
+
+use crate::{bindings, error::Error, sync::CondVar, Result};
+
+impl CondVar {
+    /// Sleeps until `cond` returns `true`, like `wait_event_interruptible` in C.
+    ///
+    /// `cond` is evaluated once before sleeping, then again after each wake-up, always after the
+    /// task is queued on the condition variable and its state is set. A waker that makes the
+    /// condition true before calling [`CondVar::notify_all`] (or [`CondVar::notify_one`]) is
+    /// therefore never missed: either `cond` sees its changes, or the wake-up makes the task
+    /// run again.
+    ///
+    /// Returns `ERESTARTSYS` if a signal is pending while waiting. As this may sleep, it must only
+    /// be called from process context.
+    pub fn wait_for(&self, mut cond: impl FnMut() -> bool) -> Result {
+        if cond() {
+            return Ok(());
+        }
+
+        let mut wait = bindings::wait_queue_entry::default();
+        // SAFETY: `wait` is a valid wait queue entry, which is initialised here.
+        unsafe { bindings::init_wait_entry(&mut wait, 0) };
+        let ret = loop {
+            // SAFETY: `self.wait_list` is a valid wait queue, and `wait` is a valid entry that
+            // stays in place until it is removed from the queue by `finish_wait` below.
+            let pending = unsafe {
+                bindings::prepare_to_wait_event(
+                    self.wait_list.get(),
+                    &mut wait,
+                    bindings::TASK_INTERRUPTIBLE as _,
+                )
+            };
+            // `prepare_to_wait_event` sets the state of the task with a full memory barrier, so
+            // the loads done by `cond` are ordered after it.
+            if cond() {
+                break Ok(());
+            }
+            if pending != 0 {
+                break Err(Error::ERESTARTSYS);
+            }
+            // SAFETY: FFI call without preconditions.
+            unsafe { bindings::schedule() };
+        };
+        // SAFETY: `self.wait_list` and `wait` are valid, and `wait` was queued by
+        // `prepare_to_wait_event` above.
+        unsafe { bindings::finish_wait(self.wait_list.get(), &mut wait) };
+        ret
+    }
+}
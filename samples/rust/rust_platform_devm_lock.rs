@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Rust platform driver sample keeping its lock-protected state in a device-managed allocation.
+//!
+//! The state is allocated and its spinlock initialised in probe, and devm revokes it after
+//! remove, which drops it.
+//! The module counts the states that are alive, and reports any that were leaked when it is
+//! unloaded, i.e., after all its devices have been unbound.
+
+#![no_std]
+#![feature(allocator_api, global_asm)]
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use kernel::{
+    c_str, device,
+    of::ConstOfMatchTable,
+    platdev::{self, PlatformDevice, PlatformDriver},
+    prelude::*,
+    revocable::Revocable,
+    sync::SpinLock,
+};
+
+module! {
+    type: RustPlatformDevmLock,
+    name: b"rust_platform_devm_lock",
+    author: b"Rust for Linux Contributors",
+    description: b"Rust platform driver devm-managed lock sample",
+    license: b"GPL v2",
+}
+
+/// The number of [`DevState`] instances that were not dropped yet.
+static LIVE_STATES: AtomicUsize = AtomicUsize::new(0);
+
+struct Stats {
+    accesses: u32,
+}
+
+/// The per-device state of the driver, dropped by devm when the device is unbound.
+struct DevState {
+    stats: SpinLock<Stats>,
+}
+
+impl DevState {
+    fn new() -> Self {
+        LIVE_STATES.fetch_add(1, Ordering::Relaxed);
+        Self {
+            // SAFETY: `stats` is initialised in the call to `spinlock_init` in `probe`.
+            stats: unsafe { SpinLock::new(Stats { accesses: 0 }) },
+        }
+    }
+}
+
+impl Drop for DevState {
+    fn drop(&mut self) {
+        LIVE_STATES.fetch_sub(1, Ordering::Relaxed);
+        pr_info!("device state dropped\n");
+    }
+}
+
+struct SampleDriver;
+
+impl PlatformDriver for SampleDriver {
+    type DrvData = Arc<Revocable<Pin<Box<DevState>>>>;
+
+    fn probe(pdev: &mut PlatformDevice, _match_data: Option<&'static ()>) -> Result<Self::DrvData> {
+        let state = device::devm_alloc_pinned(pdev, DevState::new(), |state| {
+            // SAFETY: `stats` is pinned when `state` is.
+            let stats = unsafe { state.map_unchecked_mut(|s| &mut s.stats) };
+            kernel::spinlock_init!(stats, "DevState::stats");
+        })?;
+        state
+            .try_access()
+            .ok_or(Error::ENODEV)?
+            .stats
+            .lock()
+            .accesses += 1;
+        dev_info!(pdev, "probed\n");
+        Ok(state)
+    }
+
+    fn remove(pdev: &mut PlatformDevice, drv_data: Self::DrvData) -> Result {
+        // The state is only revoked once this returns, so it is still accessible here.
+        let accesses = drv_data
+            .try_access()
+            .ok_or(Error::ENODEV)?
+            .stats
+            .lock()
+            .accesses;
+        dev_info!(pdev, "removing after {} accesses\n", accesses);
+        Ok(())
+    }
+}
+
+const OF_MATCH_TBL: ConstOfMatchTable<1> =
+    ConstOfMatchTable::new_const([c_str!("rust,sample-devm-lock")]);
+
+struct RustPlatformDevmLock {
+    pdev: Option<Pin<Box<platdev::Registration>>>,
+}
+
+impl KernelModule for RustPlatformDevmLock {
+    fn init() -> Result<Self> {
+        let pdev = platdev::Registration::new_pinned::<SampleDriver>(
+            c_str!("rust-platform-devm-lock"),
+            Some(&OF_MATCH_TBL),
+            &THIS_MODULE,
+        )?;
+
+        Ok(RustPlatformDevmLock { pdev: Some(pdev) })
+    }
+}
+
+impl Drop for RustPlatformDevmLock {
+    fn drop(&mut self) {
+        // Unregistering the driver unbinds all its devices, which drops their states.
+        drop(self.pdev.take());
+        let live = LIVE_STATES.load(Ordering::Relaxed);
+        if live != 0 {
+            pr_err!("{} device states leaked\n", live);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Rust platform driver sample probed for a device added after the driver was registered.
+//!
+//! The module registers its driver first, while no matching device exists, then adds a device
+//! with the same name, as happens e.g. when a devicetree overlay is applied at runtime. The driver
+//! stays registered for the lifetime of the module, so it is probed for the new device.
+
+#![no_std]
+#![feature(allocator_api, global_asm)]
+
+use kernel::{
+    c_str,
+    platdev::{self, PlatformDevice, SimplePlatformDriver},
+    prelude::*,
+};
+
+module! {
+    type: RustPlatformLateDevice,
+    name: b"rust_platform_late_device",
+    author: b"Rust for Linux Contributors",
+    description: b"Rust platform driver late device sample",
+    license: b"GPL v2",
+}
+
+struct SampleDriver;
+
+impl SimplePlatformDriver for SampleDriver {
+    fn probe(pdev: &mut PlatformDevice) -> Result {
+        dev_info!(pdev, "probed\n");
+        Ok(())
+    }
+}
+
+struct RustPlatformLateDevice {
+    // Dropped first, so the device is unbound and removed before the driver is unregistered.
+    _dev: platdev::DeviceRegistration,
+    _drv: Pin<Box<platdev::Registration>>,
+}
+
+impl KernelModule for RustPlatformLateDevice {
+    fn init() -> Result<Self> {
+        let drv = platdev::Registration::new_pinned::<SampleDriver>(
+            c_str!("rust-platform-late-device"),
+            None,
+            &THIS_MODULE,
+        )?;
+        pr_info!("driver registered, bound: {}\n", drv.is_bound());
+
+        let dev = platdev::DeviceRegistration::new(c_str!("rust-platform-late-device"))?;
+        if drv.probe_count() == 0 {
+            // The driver is still registered, so it is probed once the deferred or asynchronous
+            // probe of the device runs.
+            pr_info!("device added, probe pending\n");
+        } else {
+            pr_info!("device added, probed {} time(s)\n", drv.probe_count());
+        }
+
+        Ok(RustPlatformLateDevice {
+            _dev: dev,
+            _drv: drv,
+        })
+    }
+}
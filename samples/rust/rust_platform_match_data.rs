@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Rust platform driver sample handling two hardware variants with per-compatible match data.
+
+#![no_std]
+#![feature(allocator_api, global_asm)]
+
+use kernel::{
+    c_str,
+    of::ConstOfMatchDataTable,
+    platdev::{self, PlatformDevice, PlatformDriver},
+    prelude::*,
+};
+
+module! {
+    type: RustPlatformMatchData,
+    name: b"rust_platform_match_data",
+    author: b"Rust for Linux Contributors",
+    description: b"Rust platform driver match data sample",
+    license: b"GPL v2",
+}
+
+/// The variants of the hardware.
+#[derive(Clone, Copy)]
+enum Variant {
+    V1,
+    V2,
+}
+
+impl Variant {
+    fn name(self) -> &'static str {
+        match self {
+            Variant::V1 => "v1",
+            Variant::V2 => "v2",
+        }
+    }
+
+    fn fifo_depth(self) -> u32 {
+        match self {
+            Variant::V1 => 16,
+            Variant::V2 => 64,
+        }
+    }
+
+    fn has_dma(self) -> bool {
+        matches!(self, Variant::V2)
+    }
+}
+
+struct SampleDriver;
+
+impl PlatformDriver for SampleDriver {
+    type DrvData = Box<Variant>;
+    type MatchData = Variant;
+
+    fn probe(
+        pdev: &mut PlatformDevice,
+        match_data: Option<&'static Variant>,
+    ) -> Result<Self::DrvData> {
+        let variant = match match_data {
+            Some(variant) => *variant,
+            // Devices registered by name, without a devicetree node, are assumed to be the
+            // oldest variant.
+            None if pdev.of_node().is_none() => {
+                dev_info!(pdev, "no devicetree node, using defaults\n");
+                Variant::V1
+            }
+            None => return Err(Error::EINVAL),
+        };
+        dev_info!(
+            pdev,
+            "probing {} variant, fifo depth {}\n",
+            variant.name(),
+            variant.fifo_depth()
+        );
+        if variant.has_dma() {
+            dev_info!(pdev, "using dma\n");
+        } else {
+            dev_info!(pdev, "using pio\n");
+        }
+        Ok(Box::try_new(variant)?)
+    }
+
+    fn remove(pdev: &mut PlatformDevice, drv_data: Self::DrvData) -> Result {
+        dev_info!(pdev, "removing {} variant\n", drv_data.name());
+        Ok(())
+    }
+}
+
+const OF_MATCH_TBL: ConstOfMatchDataTable<Variant, 2> = ConstOfMatchDataTable::new_const([
+    (c_str!("rust,sample-v1"), &Variant::V1),
+    (c_str!("rust,sample-v2"), &Variant::V2),
+]);
+
+struct RustPlatformMatchData {
+    _pdev: Pin<Box<platdev::Registration>>,
+}
+
+impl KernelModule for RustPlatformMatchData {
+    fn init() -> Result<Self> {
+        let pdev = platdev::Registration::new_pinned_with_data::<SampleDriver>(
+            c_str!("rust-platform-match-data"),
+            &OF_MATCH_TBL,
+            &THIS_MODULE,
+        )?;
+
+        Ok(RustPlatformMatchData { _pdev: pdev })
+    }
+}
@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Rust pollable random number generator sample.
+//!
+//! A timer feeds random words into a ring buffer. Reading the character device blocks until a word
+//! is available, unless the file is non-blocking, and the file can be waited on with `poll(2)` and
+//! `select(2)`: both sleep on the same wait queue, which the timer wakes up.
+//!
+//! The state is the context of the character device registration, so every open file gets a
+//! reference to it. The major number is allocated dynamically and logged on init, e.g. for
+//! `mknod /dev/rust_rng_poll c <major> 0`.
+
+#![no_std]
+#![feature(allocator_api, global_asm)]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use kernel::prelude::*;
+use kernel::{
+    bindings, c_str, chrdev,
+    file::File,
+    file_operations::{FileOpener, FileOperations, PollTable},
+    hrtimer::{HrTimer, HrTimerCallback, HrTimerRestart},
+    io_buffer::IoBufferWriter,
+    random,
+    ring_buffer::{Overflow, RingBuffer},
+    sync::CondVar,
+};
+
+module! {
+    type: RustRngPoll,
+    name: b"rust_rng_poll",
+    author: b"Rust for Linux Contributors",
+    description: b"Rust pollable random number generator sample",
+    license: b"GPL v2",
+}
+
+/// The period of the timer feeding the ring buffer, in nanoseconds.
+const PERIOD_NS: i64 = 10_000_000;
+
+/// The state shared by the timer and all the open files.
+struct RngState {
+    words: RingBuffer<u32, 16>,
+    wait: CondVar,
+    /// The state of the xorshift generator, only used by the timer.
+    seed: AtomicU32,
+}
+
+impl RngState {
+    fn try_new() -> Result<Arc<Self>> {
+        let mut seed = [0; 4];
+        random::getrandom(&mut seed)?;
+        let mut state = Arc::try_new(Self {
+            // Readers get the freshest words.
+            words: RingBuffer::new(Overflow::DropOldest),
+            // SAFETY: `wait` is initialised in the call to `condvar_init` below.
+            wait: unsafe { CondVar::new() },
+            // xorshift never leaves zero, so the seed must not be zero.
+            seed: AtomicU32::new(u32::from_ne_bytes(seed) | 1),
+        })?;
+        let s = Arc::get_mut(&mut state).unwrap();
+        // SAFETY: `wait` is pinned behind the `Arc` reference.
+        let wait = unsafe { Pin::new_unchecked(&mut s.wait) };
+        kernel::condvar_init!(wait, "RngState::wait");
+        Ok(state)
+    }
+
+    fn next_word(&self) -> u32 {
+        let mut x = self.seed.load(Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.seed.store(x, Ordering::Relaxed);
+        x
+    }
+}
+
+/// The callback of the timer feeding the ring buffer.
+struct Feeder(Arc<RngState>);
+
+impl HrTimerCallback for Feeder {
+    fn run(timer: &HrTimer<Self>) -> HrTimerRestart {
+        let state = &timer.0;
+        // This only fails if a reader is popping a word at the same time, in which case the
+        // reader was not waiting anyway.
+        let _ = state.words.push_notify(state.next_word(), &state.wait);
+        timer.forward_now(PERIOD_NS);
+        HrTimerRestart::Restart
+    }
+}
+
+struct RngFile {
+    state: Arc<RngState>,
+}
+
+impl FileOpener<Arc<RngState>> for RngFile {
+    fn open(state: &Arc<RngState>) -> Result<Box<Self>> {
+        Ok(Box::try_new(Self {
+            state: state.clone(),
+        })?)
+    }
+}
+
+impl FileOperations for RngFile {
+    kernel::declare_file_operations!(read, poll);
+
+    fn read<T: IoBufferWriter>(this: &Self, file: &File, data: &mut T, _: u64) -> Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let words = &this.state.words;
+        let mut word = Some(if file.is_blocking() {
+            words.pop_wait(&this.state.wait)?
+        } else {
+            words.try_pop().ok_or(Error::EAGAIN)?
+        });
+
+        // Return the words that are available without blocking again.
+        let mut len = 0;
+        while let Some(w) = word {
+            let bytes = w.to_ne_bytes();
+            let n = bytes.len().min(data.len());
+            data.write_slice(&bytes[..n])?;
+            len += n;
+            if data.is_empty() {
+                break;
+            }
+            word = words.try_pop();
+        }
+        Ok(len)
+    }
+
+    fn poll(this: &Self, file: &File, table: &PollTable) -> Result<u32> {
+        // SAFETY: The condition variable is part of `this.state`, which the file keeps alive.
+        unsafe { table.register_wait(file, &this.state.wait) };
+        if this.state.words.is_empty() {
+            Ok(0)
+        } else {
+            Ok(bindings::POLLIN | bindings::POLLRDNORM)
+        }
+    }
+}
+
+struct RustRngPoll {
+    _dev: Pin<Box<chrdev::Registration<Arc<RngState>, 1>>>,
+    _feeder: Pin<Box<HrTimer<Feeder>>>,
+}
+
+impl KernelModule for RustRngPoll {
+    fn init() -> Result<Self> {
+        pr_info!("Rust pollable RNG sample (init)\n");
+
+        let state = RngState::try_new()?;
+        let feeder = HrTimer::try_new(Feeder(state.clone()))?;
+        feeder.start(PERIOD_NS);
+        let mut dev =
+            chrdev::Registration::new_pinned(c_str!("rust_rng_poll"), 0, state, &THIS_MODULE)?;
+        dev.as_mut().register::<RngFile>()?;
+        if let Some(major) = dev.major() {
+            pr_info!("registered with major {}\n", major);
+        }
+
+        // The device is deregistered before the timer is cancelled.
+        Ok(RustRngPoll {
+            _dev: dev,
+            _feeder: feeder,
+        })
+    }
+}
+
+impl Drop for RustRngPoll {
+    fn drop(&mut self) {
+        pr_info!("Rust pollable RNG sample (exit)\n");
+    }
+}